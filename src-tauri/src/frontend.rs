@@ -1,6 +1,12 @@
-use std::time::Duration;
-
-use crate::midi::{MidiStateInner, playback::MidiPlayback};
+use crate::midi::{
+    MidiStateInner,
+    filter::InputFilter,
+    library::{OverdubSource, RecordingGrid, TakeSelection},
+    message::{MessageError, MidiChannel},
+    playback::{LoadedMidiFile, MidiPlayback, TrackInfo},
+    rmid::RmidInfo,
+    smf::{MidiFileError, ParseWarning},
+};
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
@@ -68,14 +74,36 @@ pub enum RecorderState {
 #[serde(rename_all = "camelCase")]
 pub struct Recording {
     index: usize,
+    name: String,
+    created_milliseconds: u64,
     duration_milliseconds: u32,
+    tags: Vec<String>,
+    input_device: Option<String>,
+    grid: Option<RecordingGrid>,
+    overdub_of: Option<OverdubSource>,
+    take_count: usize,
+    take_selection: Option<TakeSelection>,
+}
+
+/// A recording that was interrupted in an earlier session and can be recovered from its journal.
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UnfinishedRecording {
+    index: usize,
+    created_milliseconds: u64,
+    message_count: usize,
+    input_device: Option<String>,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Recorder {
     pub state: RecorderState,
+    pub can_pause: bool,
     pub recordings: Vec<Recording>,
+    pub unfinished_recordings: Vec<UnfinishedRecording>,
+    pub capture_minutes: u32,
+    pub input_filter: InputFilter,
 }
 
 impl From<&crate::midi::recorder::MidiRecorder> for Recorder {
@@ -83,31 +111,47 @@ impl From<&crate::midi::recorder::MidiRecorder> for Recorder {
         let state = match value.get_state() {
             crate::midi::recorder::RecorderState::Stopped => RecorderState::Stopped,
             crate::midi::recorder::RecorderState::Recording => RecorderState::Recording,
+            crate::midi::recorder::RecorderState::Paused => RecorderState::Paused,
         };
 
-        let calc_duration_milliseconds =
-            |recording: &Vec<crate::midi::message::TimeStampedMidiMessage>| {
-                if recording.is_empty() {
-                    0
-                } else if recording.len() == 1 {
-                    (recording.first().unwrap().timestamp_microseconds / 1000) as u32
-                } else {
-                    let start_time = recording.first().unwrap().timestamp_microseconds;
-                    let end_time = recording.last().unwrap().timestamp_microseconds;
-                    ((end_time - start_time) / 1000) as u32
-                }
-            };
         let recordings = value
             .get_recordings()
             .iter()
             .enumerate()
             .map(|(index, recording)| Recording {
                 index,
-                duration_milliseconds: calc_duration_milliseconds(recording),
+                name: recording.metadata.name.clone(),
+                created_milliseconds: recording.metadata.created_milliseconds,
+                duration_milliseconds: recording.metadata.duration_milliseconds,
+                tags: recording.metadata.tags.clone(),
+                input_device: recording.metadata.input_device.clone(),
+                grid: recording.metadata.grid.clone(),
+                overdub_of: recording.metadata.overdub_of.clone(),
+                take_count: recording.takes.len(),
+                take_selection: recording.metadata.take_selection.clone(),
+            })
+            .collect();
+
+        let unfinished_recordings = value
+            .get_unfinished_journals()
+            .iter()
+            .enumerate()
+            .map(|(index, journal)| UnfinishedRecording {
+                index,
+                created_milliseconds: journal.header.created_milliseconds,
+                message_count: journal.messages.len(),
+                input_device: journal.header.input_device.clone(),
             })
             .collect();
 
-        Recorder { state, recordings }
+        Recorder {
+            state,
+            can_pause: value.can_pause(),
+            recordings,
+            unfinished_recordings,
+            capture_minutes: value.get_capture_minutes(),
+            input_filter: value.get_input_filter().clone(),
+        }
     }
 }
 
@@ -124,77 +168,244 @@ pub enum PlaybackState {
 #[serde(tag = "type")]
 pub enum PlaybackIdentifier {
     Recording { index: usize },
-    MidiFile { path: String },
+    MidiFile { path: String, track: Option<usize> },
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Track {
     index: usize,
+    name: Option<String>,
     duration_milliseconds: u32,
 }
 
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MidiFile {
+    path: String,
+    tracks: Vec<Track>,
+    duration_milliseconds: u32,
+    info: RmidInfo,
+    warnings: Vec<ParseWarning>,
+}
+
+impl From<&LoadedMidiFile> for MidiFile {
+    fn from(file: &LoadedMidiFile) -> Self {
+        let tracks = file
+            .get_tracks()
+            .iter()
+            .zip(file.get_track_names())
+            .enumerate()
+            .map(|(index, (track, name))| Track {
+                index,
+                name: name.clone(),
+                duration_milliseconds: track.get_duration().as_millis() as u32,
+            })
+            .collect();
+
+        MidiFile {
+            path: file.get_path().to_string(),
+            tracks,
+            duration_milliseconds: file.get_merged_track().get_duration().as_millis() as u32,
+            info: file.get_info().clone(),
+            warnings: file.get_warnings().to_vec(),
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LoopRegion {
+    start_milliseconds: u32,
+    end_milliseconds: u32,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelMapping {
+    pub from: MidiChannel,
+    pub to: MidiChannel,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaybackTransform {
+    pub transpose: i8,
+    pub velocity_scale: f64,
+    pub channel_map: Vec<ChannelMapping>,
+}
+
+impl From<crate::midi::playback::transform::PlaybackTransform> for PlaybackTransform {
+    fn from(transform: crate::midi::playback::transform::PlaybackTransform) -> Self {
+        PlaybackTransform {
+            transpose: transform.transpose,
+            velocity_scale: transform.velocity_scale,
+            channel_map: transform
+                .channel_map
+                .into_iter()
+                .map(|(from, to)| ChannelMapping { from, to })
+                .collect(),
+        }
+    }
+}
+
+impl From<PlaybackTransform> for crate::midi::playback::transform::PlaybackTransform {
+    fn from(transform: PlaybackTransform) -> Self {
+        crate::midi::playback::transform::PlaybackTransform {
+            transpose: transform.transpose,
+            velocity_scale: transform.velocity_scale,
+            channel_map: transform
+                .channel_map
+                .into_iter()
+                .map(|mapping| (mapping.from, mapping.to))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MuteState {
+    pub muted_channels: Vec<MidiChannel>,
+    pub soloed_channels: Vec<MidiChannel>,
+    pub muted_tracks: Vec<usize>,
+    pub soloed_tracks: Vec<usize>,
+}
+
+impl From<&crate::midi::playback::mute::MuteState> for MuteState {
+    fn from(state: &crate::midi::playback::mute::MuteState) -> Self {
+        MuteState {
+            muted_channels: state.get_muted_channels(),
+            soloed_channels: state.get_soloed_channels(),
+            muted_tracks: state.get_muted_tracks(),
+            soloed_tracks: state.get_soloed_tracks(),
+        }
+    }
+}
+
 #[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct Playback {
     pub state: PlaybackState,
     pub identifier: Option<PlaybackIdentifier>,
-    pub tracks: Vec<Track>,
+    pub file: Option<MidiFile>,
     pub duration_milliseconds: Option<u32>,
     pub position_milliseconds: u32,
+    /// Time it takes to play the whole piece at the current rate.
+    pub wall_clock_duration_milliseconds: Option<u32>,
+    /// Time it takes to reach the current position at the current rate.
+    pub wall_clock_position_milliseconds: u32,
+    pub rate: f64,
+    pub loop_region: Option<LoopRegion>,
+    pub transform: PlaybackTransform,
+    pub mute_state: MuteState,
 }
 
 impl From<&MidiPlayback> for Playback {
     fn from(playback: &MidiPlayback) -> Self {
-        let src_state = playback.get_state();
-        let (state, identifier) = match src_state {
+        let file = playback.get_file();
+        let to_identifier = |track_info: TrackInfo| match track_info {
+            TrackInfo::Recording(index) => PlaybackIdentifier::Recording { index },
+            TrackInfo::StandardMidiFile(track) => PlaybackIdentifier::MidiFile {
+                path: file
+                    .as_ref()
+                    .map(|file| file.get_path().to_string())
+                    .unwrap_or_default(),
+                track,
+            },
+        };
+        let (state, identifier) = match playback.get_state() {
             crate::midi::playback::PlaybackState::Stopped => (PlaybackState::Stopped, None),
-            crate::midi::playback::PlaybackState::Playing(track_info) => (
-                PlaybackState::Playing,
-                match track_info {
-                    crate::midi::playback::TrackInfo::Recording(index) => {
-                        Some(PlaybackIdentifier::Recording { index })
-                    }
-                    crate::midi::playback::TrackInfo::StandardMidiFile(index) => {
-                        Some(PlaybackIdentifier::MidiFile {
-                            path: format!("Track {}", index),
-                        })
-                    }
-                },
-            ),
-            crate::midi::playback::PlaybackState::Paused(track_info) => (
-                PlaybackState::Paused,
-                match track_info {
-                    crate::midi::playback::TrackInfo::Recording(index) => {
-                        Some(PlaybackIdentifier::Recording { index })
-                    }
-                    crate::midi::playback::TrackInfo::StandardMidiFile(index) => {
-                        Some(PlaybackIdentifier::MidiFile {
-                            path: format!("Track {}", index),
-                        })
-                    }
-                },
-            ),
+            crate::midi::playback::PlaybackState::Playing(track_info) => {
+                (PlaybackState::Playing, Some(to_identifier(track_info)))
+            }
+            crate::midi::playback::PlaybackState::Paused(track_info) => {
+                (PlaybackState::Paused, Some(to_identifier(track_info)))
+            }
         };
-        let src_tracks = playback.get_tracks();
-        let tracks = src_tracks
-            .iter()
-            .enumerate()
-            .map(|(index, track)| Track {
-                duration_milliseconds: (track.iter().map(|(delta, _)| delta).sum::<u64>() / 1000)
-                    as u32,
-                index,
-            })
-            .collect();
-        let duration_milliseconds = playback.get_duration().map(|d| d.as_millis() as u32);
-        let position_milliseconds = playback.get_position().as_millis() as u32;
+        let rate = playback.get_rate();
+        let duration = playback.get_duration();
+        let position = playback.get_position();
+        let duration_milliseconds = duration.map(|d| d.as_millis() as u32);
+        let position_milliseconds = position.as_millis() as u32;
+        let wall_clock_duration_milliseconds = duration.map(|d| d.div_f64(rate).as_millis() as u32);
+        let wall_clock_position_milliseconds = position.div_f64(rate).as_millis() as u32;
+        let loop_region = playback.get_loop().map(|region| LoopRegion {
+            start_milliseconds: region.start.as_millis() as u32,
+            end_milliseconds: region.end.as_millis() as u32,
+        });
 
         Playback {
             state,
             identifier,
-            tracks,
+            file: file.as_ref().map(MidiFile::from),
             duration_milliseconds,
             position_milliseconds,
+            wall_clock_duration_milliseconds,
+            wall_clock_position_milliseconds,
+            rate,
+            loop_region,
+            transform: playback.get_transform().into(),
+            mute_state: (&playback.get_mute_state()).into(),
         }
     }
 }
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaybackJitter {
+    pub message_count: u64,
+    pub mean_lateness_microseconds: u64,
+    pub max_lateness_microseconds: u64,
+}
+
+impl From<&crate::midi::playback::scheduler::JitterStats> for PlaybackJitter {
+    fn from(stats: &crate::midi::playback::scheduler::JitterStats) -> Self {
+        PlaybackJitter {
+            message_count: stats.get_message_count(),
+            mean_lateness_microseconds: stats.get_mean_lateness().as_micros() as u64,
+            max_lateness_microseconds: stats.get_max_lateness().as_micros() as u64,
+        }
+    }
+}
+
+/// An error returned by a command, tagged so that the frontend can tell where it comes from.
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum CommandError {
+    MidiFile {
+        error: MidiFileError,
+        message: String,
+    },
+    MidiMessage {
+        error: MessageError,
+        message: String,
+    },
+    Other {
+        message: String,
+    },
+}
+
+impl From<MidiFileError> for CommandError {
+    fn from(error: MidiFileError) -> Self {
+        CommandError::MidiFile {
+            message: error.to_string(),
+            error,
+        }
+    }
+}
+
+impl From<MessageError> for CommandError {
+    fn from(error: MessageError) -> Self {
+        CommandError::MidiMessage {
+            message: error.to_string(),
+            error,
+        }
+    }
+}
+
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        CommandError::Other { message }
+    }
+}
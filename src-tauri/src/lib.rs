@@ -6,17 +6,28 @@ use midi::{
         connect_midi_input, connect_midi_output, disconnect_midi_input, disconnect_midi_output,
         get_midi, scan_midi_input, scan_midi_output,
     },
+    library::{RecordingLibrary, get_library_path},
 };
 
 use crate::midi::commands::{
-    delete_midi_recording, eject_midi_track, get_midi_playback, get_midi_recorder, load_midi_track,
-    pause_midi_playback, play_midi_recording, play_midi_track, register_midi_channel,
-    resume_midi_playback, save_midi_recording, send_midi_message, start_midi_recording,
-    stop_midi_playback, stop_midi_recording,
+    clear_midi_playback_loop, comp_midi_takes, delete_midi_recording,
+    discard_unfinished_midi_recording, eject_midi_file, get_midi_export_settings,
+    get_midi_metronome, get_midi_playback, get_midi_playback_jitter, get_midi_recorder,
+    load_midi_file, merge_midi_overdub, panic_midi_playback, pause_midi_playback,
+    pause_midi_recording, pick_midi_take, play_midi_file, play_midi_recording, play_midi_take,
+    play_midi_track, recover_midi_recording, register_midi_channel, register_midi_playback_channel,
+    rename_midi_recording, resume_midi_playback, resume_midi_recording, save_midi_capture,
+    save_midi_recording, seek_midi_playback, send_midi_message, set_midi_capture_minutes,
+    set_midi_channel_muted, set_midi_channel_soloed, set_midi_export_settings,
+    set_midi_input_filter, set_midi_metronome, set_midi_playback_loop, set_midi_playback_rate,
+    set_midi_playback_transform, set_midi_recording_tags, set_midi_track_muted,
+    set_midi_track_soloed, start_midi_overdub, start_midi_recording, stop_midi_playback,
+    stop_midi_recording,
 };
 
 mod frontend;
 mod midi;
+mod settings;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -33,20 +44,51 @@ pub fn run() {
             disconnect_midi_input,
             disconnect_midi_output,
             register_midi_channel,
+            register_midi_playback_channel,
             send_midi_message,
             get_midi_recorder,
             start_midi_recording,
             stop_midi_recording,
+            start_midi_overdub,
+            pause_midi_recording,
+            resume_midi_recording,
+            save_midi_capture,
+            set_midi_capture_minutes,
+            set_midi_input_filter,
+            get_midi_metronome,
+            set_midi_metronome,
+            get_midi_export_settings,
+            set_midi_export_settings,
             save_midi_recording,
+            merge_midi_overdub,
+            rename_midi_recording,
+            set_midi_recording_tags,
+            recover_midi_recording,
+            discard_unfinished_midi_recording,
             delete_midi_recording,
             get_midi_playback,
+            get_midi_playback_jitter,
             play_midi_recording,
+            play_midi_take,
+            pick_midi_take,
+            comp_midi_takes,
             pause_midi_playback,
             resume_midi_playback,
             stop_midi_playback,
-            load_midi_track,
+            seek_midi_playback,
+            panic_midi_playback,
+            set_midi_playback_loop,
+            clear_midi_playback_loop,
+            set_midi_playback_rate,
+            set_midi_playback_transform,
+            set_midi_channel_muted,
+            set_midi_channel_soloed,
+            set_midi_track_muted,
+            set_midi_track_soloed,
+            load_midi_file,
+            play_midi_file,
             play_midi_track,
-            eject_midi_track,
+            eject_midi_file,
         ])
         .setup(|app| {
             #[cfg(debug_assertions)] // only include this code on debug builds
@@ -58,11 +100,30 @@ pub fn run() {
             }
             // initialize MIDI state
             {
+                let settings = settings::get_settings_path(app.handle())
+                    .map(|path| settings::Settings::load(&path))
+                    .unwrap_or_default();
                 let midi = app.state::<MidiState>();
                 tauri::async_runtime::block_on(async {
                     let mut midi = midi.lock().await;
                     let _ = midi.scan_input();
                     let _ = midi.scan_output();
+                    let mut recorder = midi.recorder.lock().unwrap();
+                    recorder.set_input_filter(settings.input_filter);
+                    midi.metronome
+                        .lock()
+                        .unwrap()
+                        .set_settings(settings.metronome)
+                        .unwrap_or_else(|e| {
+                            eprintln!("Invalid metronome settings, using defaults: {}", e);
+                        });
+                    if let Ok(directory) = get_library_path(app.handle()) {
+                        recorder
+                            .open_library(RecordingLibrary::new(directory))
+                            .unwrap_or_else(|e| {
+                                eprintln!("Failed to open recording library: {}", e);
+                            });
+                    }
                 });
             }
 
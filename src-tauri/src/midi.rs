@@ -5,15 +5,21 @@ use tauri::async_runtime::Mutex as AsyncMutex;
 use tauri::ipc::Channel;
 
 use crate::midi::{
-    message::MidiMessage,
+    message::{MessageError, MidiMessage},
+    metronome::Metronome,
     playback::MidiPlayback,
     recorder::{MidiRecorder, RecorderState},
 };
 
 pub mod commands;
+pub mod filter;
+pub mod journal;
+pub mod library;
 pub mod message;
+pub mod metronome;
 pub mod playback;
 pub mod recorder;
+pub mod rmid;
 pub mod smf;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -45,6 +51,7 @@ pub struct MidiStateInner {
     pub input_connection: Option<MidiInputConnection>,
     pub output_connection: Option<MidiOutputConnection>,
     pub recorder: Arc<Mutex<MidiRecorder>>,
+    pub metronome: Arc<Mutex<Metronome>>,
     pub playback: Arc<AsyncMutex<MidiPlayback>>,
     pub frontend_channel: Arc<Mutex<Option<Channel<MidiMessage>>>>,
 }
@@ -89,6 +96,9 @@ impl MidiStateInner {
 
     pub fn disconnect_input(&mut self) {
         self.input_connection = None;
+        let mut recorder = self.recorder.lock().unwrap();
+        recorder.clear_capture_buffer();
+        recorder.set_input_device(None);
     }
 
     pub fn connect_input(&mut self, index: usize) -> Result<(), String> {
@@ -117,7 +127,7 @@ impl MidiStateInner {
                 &midi_port,
                 port.name.as_str(),
                 move |timestamp, message, _| {
-                    let midi_message_result: Result<MidiMessage, String> = message.try_into();
+                    let midi_message_result: Result<MidiMessage, MessageError> = message.try_into();
                     let message = match midi_message_result {
                         Ok(msg) => msg,
                         Err(e) => {
@@ -127,13 +137,20 @@ impl MidiStateInner {
                     };
 
                     let mut recorder = recorder.lock().unwrap();
-                    if let RecorderState::Recording { .. } = recorder.get_state() {
+                    let is_monitored = recorder.get_input_filter().accepts_monitored(&message);
+                    recorder.capture_message(message.clone(), timestamp);
+                    if let RecorderState::Recording | RecorderState::Paused = recorder.get_state() {
                         recorder
                             .add_message(message.clone(), timestamp)
                             .unwrap_or_else(|e| {
                                 eprintln!("Failed to record MIDI message: {}", e);
                             });
                     }
+                    drop(recorder);
+
+                    if !is_monitored {
+                        return;
+                    }
 
                     // FIXME: introduce debounce mechanism to avoid flooding the frontend
                     let frontend_channel = frontend_channel.lock().unwrap();
@@ -147,6 +164,10 @@ impl MidiStateInner {
             )
             .map_err(|e| format!("Failed to connect to input port: {}", e))?;
 
+        self.recorder
+            .lock()
+            .unwrap()
+            .set_input_device(Some(port.name.clone()));
         self.input_connection = Some(MidiInputConnection {
             port: port.to_owned(),
             _connection: connection,
@@ -188,13 +209,27 @@ impl MidiStateInner {
         });
 
         let connection = self.output_connection.as_ref().unwrap()._connection.clone();
-        self.playback.lock().await.set_player(move |msg| {
+        let player = move |msg: &[u8]| {
             connection
                 .lock()
                 .unwrap()
                 .send(msg)
                 .map_err(|e| format!("Failed to send MIDI message to output port: {}", e))
-        })?;
+        };
+        self.metronome.lock().unwrap().set_player(player.clone());
+        let mut playback = self.playback.lock().await;
+        playback.set_player(player)?;
+
+        // an overdub cannot outlast the playback it is recorded along with
+        let recorder = self.recorder.clone();
+        playback.set_end_handler(move || {
+            let mut recorder = recorder.lock().unwrap();
+            if recorder.is_overdubbing()
+                && let Err(error) = recorder.stop_recording()
+            {
+                eprintln!("Failed to finish the overdub: {error}");
+            }
+        });
 
         Ok(())
     }
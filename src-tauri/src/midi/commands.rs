@@ -1,31 +1,47 @@
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
 use tauri::ipc::Channel;
 use tauri_plugin_dialog::{DialogExt, FilePath};
 
 use super::MidiState;
 use crate::{
-    frontend::{Midi, Playback, Recorder},
+    frontend::{
+        CommandError, Midi, Playback, PlaybackIdentifier, PlaybackJitter, PlaybackTransform,
+        Recorder,
+    },
     midi::{
-        message::MidiMessage,
-        playback::TrackInfo,
-        smf::{MidiFile, MidiHeader, MidiTrack},
+        filter::InputFilter,
+        library::{CompSegment, OverdubSource},
+        message::{MidiChannel, MidiMessage},
+        metronome::MetronomeSettings,
+        playback::{LoopRegion, PlaybackEvent, TrackInfo},
+        recorder::{CaptureSelection, OVERDUB_LEAD, PunchRange, RecorderState, RecordingAlignment},
+        rmid::{RMID_FILE_EXTENSION, RmidInfo},
+        smf::{MidiFile, MidiFileError, MidiWriterConfig, ParseMode},
     },
+    settings::{Settings, get_settings_path},
 };
 
 #[tauri::command]
-pub async fn get_midi<'a>(state: tauri::State<'a, MidiState>) -> Result<Midi, String> {
+pub async fn get_midi<'a>(state: tauri::State<'a, MidiState>) -> Result<Midi, CommandError> {
     let midi = state.lock().await;
     Ok((&*midi).into())
 }
 
 #[tauri::command]
-pub async fn scan_midi_input<'a>(state: tauri::State<'a, MidiState>) -> Result<Midi, String> {
+pub async fn scan_midi_input<'a>(state: tauri::State<'a, MidiState>) -> Result<Midi, CommandError> {
     let mut midi = state.lock().await;
     midi.scan_input()?;
     Ok((&*midi).into())
 }
 
 #[tauri::command]
-pub async fn scan_midi_output<'a>(state: tauri::State<'a, MidiState>) -> Result<Midi, String> {
+pub async fn scan_midi_output<'a>(
+    state: tauri::State<'a, MidiState>,
+) -> Result<Midi, CommandError> {
     let mut midi = state.lock().await;
     midi.scan_output()?;
     Ok((&*midi).into())
@@ -35,7 +51,7 @@ pub async fn scan_midi_output<'a>(state: tauri::State<'a, MidiState>) -> Result<
 pub async fn connect_midi_input<'a>(
     input_id: String,
     state: tauri::State<'a, MidiState>,
-) -> Result<Midi, String> {
+) -> Result<Midi, CommandError> {
     let mut midi = state.lock().await;
     let input_index = midi
         .available_input_ports
@@ -50,7 +66,7 @@ pub async fn connect_midi_input<'a>(
 pub async fn connect_midi_output<'a>(
     output_id: String,
     state: tauri::State<'a, MidiState>,
-) -> Result<Midi, String> {
+) -> Result<Midi, CommandError> {
     let mut midi = state.lock().await;
     let output_index = midi
         .available_output_ports
@@ -62,7 +78,9 @@ pub async fn connect_midi_output<'a>(
 }
 
 #[tauri::command]
-pub async fn disconnect_midi_input<'a>(state: tauri::State<'a, MidiState>) -> Result<Midi, String> {
+pub async fn disconnect_midi_input<'a>(
+    state: tauri::State<'a, MidiState>,
+) -> Result<Midi, CommandError> {
     let mut midi = state.lock().await;
     midi.disconnect_input();
     Ok((&*midi).into())
@@ -71,7 +89,7 @@ pub async fn disconnect_midi_input<'a>(state: tauri::State<'a, MidiState>) -> Re
 #[tauri::command]
 pub async fn disconnect_midi_output<'a>(
     state: tauri::State<'a, MidiState>,
-) -> Result<Midi, String> {
+) -> Result<Midi, CommandError> {
     let mut midi = state.lock().await;
     midi.disconnect_output();
     Ok((&*midi).into())
@@ -81,24 +99,36 @@ pub async fn disconnect_midi_output<'a>(
 pub async fn register_midi_channel<'a>(
     state: tauri::State<'a, MidiState>,
     channel: Channel<MidiMessage>,
-) -> Result<(), String> {
+) -> Result<(), CommandError> {
     let mut midi = state.lock().await;
     midi.set_frontend_channel(channel);
     Ok(())
 }
 
+#[tauri::command]
+pub async fn register_midi_playback_channel<'a>(
+    state: tauri::State<'a, MidiState>,
+    channel: Channel<PlaybackEvent>,
+) -> Result<(), CommandError> {
+    let midi = state.lock().await;
+    midi.playback.lock().await.set_frontend_channel(channel);
+    Ok(())
+}
+
 #[tauri::command]
 pub async fn send_midi_message<'a>(
     state: tauri::State<'a, MidiState>,
     midi_message: MidiMessage,
-) -> Result<(), String> {
+) -> Result<(), CommandError> {
     let midi = state.lock().await;
     midi.send_message(midi_message)?;
     Ok(())
 }
 
 #[tauri::command]
-pub async fn get_midi_recorder<'a>(state: tauri::State<'a, MidiState>) -> Result<Recorder, String> {
+pub async fn get_midi_recorder<'a>(
+    state: tauri::State<'a, MidiState>,
+) -> Result<Recorder, CommandError> {
     let midi = state.lock().await;
     let recorder = midi.recorder.lock().unwrap();
     Ok((&*recorder).into())
@@ -107,11 +137,28 @@ pub async fn get_midi_recorder<'a>(state: tauri::State<'a, MidiState>) -> Result
 #[tauri::command]
 pub async fn start_midi_recording<'a>(
     state: tauri::State<'a, MidiState>,
-) -> Result<Recorder, String> {
+) -> Result<Recorder, CommandError> {
     let midi = state.lock().await;
 
+    let mut metronome = midi.metronome.lock().unwrap();
     let mut recorder = midi.recorder.lock().unwrap();
-    recorder.start_recording()?;
+    let settings = metronome.get_settings().clone();
+    if settings.enabled {
+        // the count-in consists of whole bars, so the recording starts on a bar as well
+        let count_in_start = Instant::now();
+        metronome.start(count_in_start)?;
+        recorder
+            .start_aligned_recording(RecordingAlignment {
+                origin: count_in_start + settings.get_count_in_duration(),
+                grid: Some(settings.get_grid()),
+                overdub_of: None,
+                punch: None,
+                loop_region: None,
+            })
+            .inspect_err(|_| metronome.stop())?;
+    } else {
+        recorder.start_recording()?;
+    }
 
     Ok((&*recorder).into())
 }
@@ -119,25 +166,312 @@ pub async fn start_midi_recording<'a>(
 #[tauri::command]
 pub async fn stop_midi_recording<'a>(
     state: tauri::State<'a, MidiState>,
-) -> Result<Recorder, String> {
+) -> Result<Recorder, CommandError> {
+    let midi = state.lock().await;
+
+    midi.metronome.lock().unwrap().stop();
+    // the recorder stops first, so the end of the playback does not finish the overdub again
+    let (is_overdubbing, result) = {
+        let mut recorder = midi.recorder.lock().unwrap();
+        (recorder.is_overdubbing(), recorder.stop_recording())
+    };
+    if is_overdubbing {
+        midi.playback.lock().await.stop().await?;
+    }
+    result?;
+
+    let recorder = midi.recorder.lock().unwrap();
+    Ok((&*recorder).into())
+}
+
+/// Plays a recording or the loaded file and records a new take along with it.
+///
+/// With punch positions, the take replaces that range of the recording instead.
+/// With a loop region, every pass over the loop is kept as a take of one recording.
+#[tauri::command]
+pub async fn start_midi_overdub<'a>(
+    identifier: PlaybackIdentifier,
+    punch_start_milliseconds: Option<u32>,
+    punch_end_milliseconds: Option<u32>,
+    state: tauri::State<'a, MidiState>,
+) -> Result<Recorder, CommandError> {
+    let midi = state.lock().await;
+
+    let punch = match (punch_start_milliseconds, punch_end_milliseconds) {
+        (Some(start), Some(end)) if start < end => Some(PunchRange {
+            start: Duration::from_millis(start as u64),
+            end: Duration::from_millis(end as u64),
+        }),
+        (None, None) => None,
+        (Some(_), Some(_)) => return Err("Punch-in must be before punch-out".to_string().into()),
+        _ => {
+            return Err("Punch-in and punch-out must be set together"
+                .to_string()
+                .into());
+        }
+    };
+    if punch.is_some() && !matches!(identifier, PlaybackIdentifier::Recording { .. }) {
+        return Err("Only recordings can be punched in".to_string().into());
+    }
+
+    if midi.recorder.lock().unwrap().get_state() != RecorderState::Stopped {
+        return Err("Recorder is already recording".to_string().into());
+    }
+
+    let mut playback = midi.playback.lock().await;
+    // the take is recorded in real time, so it only lines up with an unchanged playback
+    if playback.get_rate() != 1.0 {
+        return Err("Overdubbing requires the normal playback rate"
+            .to_string()
+            .into());
+    }
+    let loop_region = playback.get_loop();
+    if loop_region.is_some() && punch.is_some() {
+        return Err("Punching in requires the loop to be cleared"
+            .to_string()
+            .into());
+    }
+
+    let origin = Instant::now() + OVERDUB_LEAD;
+    let overdub_of = match identifier {
+        PlaybackIdentifier::Recording { index } => {
+            let (messages, id) = {
+                let recorder = midi.recorder.lock().unwrap();
+                let recording = recorder
+                    .get_recordings()
+                    .get(index)
+                    .ok_or_else(|| format!("Recording with index {} not found", index))?;
+                (recording.messages.clone(), recording.metadata.id.clone())
+            };
+            playback
+                .play_at(&messages, TrackInfo::Recording(index), origin)
+                .await?;
+            OverdubSource::Recording { id }
+        }
+        PlaybackIdentifier::MidiFile { path, track } => {
+            if playback
+                .get_file()
+                .is_none_or(|file| file.get_path() != path)
+            {
+                return Err(format!("MIDI file {} is not loaded", path).into());
+            }
+            playback.play_file_at(track, origin).await?;
+            OverdubSource::MidiFile { path }
+        }
+    };
+
+    let result = midi
+        .recorder
+        .lock()
+        .unwrap()
+        .start_aligned_recording(RecordingAlignment {
+            origin,
+            grid: None,
+            overdub_of: Some(overdub_of),
+            punch,
+            loop_region,
+        });
+    if let Err(e) = result {
+        playback.stop().await?;
+        return Err(e.into());
+    }
+
+    let recorder = midi.recorder.lock().unwrap();
+    Ok((&*recorder).into())
+}
+
+/// Adds an overdub as a new track to what it was recorded along with and saves the result,
+/// which is then loaded for playback.
+#[tauri::command]
+pub async fn merge_midi_overdub<'a>(
+    index: usize,
+    app: tauri::AppHandle,
+    state: tauri::State<'a, MidiState>,
+) -> Result<Playback, CommandError> {
+    let merged_file = {
+        let midi = state.lock().await;
+        let recorder = midi.recorder.lock().unwrap();
+        let take = recorder
+            .get_recordings()
+            .get(index)
+            .ok_or_else(|| format!("Recording with index {} not found", index))?;
+        let (source_file, offset) = match &take.metadata.overdub_of {
+            Some(OverdubSource::Recording { id }) => {
+                let source = recorder
+                    .get_recordings()
+                    .iter()
+                    .find(|recording| recording.metadata.id == *id)
+                    .ok_or_else(|| "The overdubbed recording no longer exists".to_string())?;
+                (source.to_midi_file(), source.get_playback_offset())
+            }
+            Some(OverdubSource::MidiFile { path }) => {
+                let midi_bytes =
+                    std::fs::read(path).map_err(|e| format!("Failed to read MIDI file: {}", e))?;
+                // the warnings were shown when the file was loaded for the overdub
+                let (midi_file, _) = MidiFile::parse(&midi_bytes, ParseMode::Lenient)?;
+                (midi_file, 0)
+            }
+            None => return Err("The recording is not an overdub".to_string().into()),
+        };
+        source_file.with_track(&take.messages, offset, &take.metadata.name)?
+    };
+
+    let file_path = app
+        .dialog()
+        .file()
+        .add_filter("Standard MIDI Files", &["mid"])
+        .add_filter("RIFF MIDI Files", &[RMID_FILE_EXTENSION])
+        .blocking_save_file();
+
+    let midi = state.lock().await;
+    let mut playback = midi.playback.lock().await;
+
+    if let Some(path) = file_path
+        && let FilePath::Path(path_buf) = path
+    {
+        let export_settings = Settings::load(&get_settings_path(&app)?).export;
+        let midi_bytes = to_file_bytes(&merged_file, &path_buf, &export_settings)?;
+        std::fs::write(&path_buf, midi_bytes)
+            .map_err(|e| format!("Failed to write MIDI file: {}", e))?;
+        playback.load_file(
+            merged_file,
+            path_buf.to_string_lossy().to_string(),
+            Vec::new(),
+        )?;
+    }
+
+    Ok((&*playback).into())
+}
+
+#[tauri::command]
+pub async fn pause_midi_recording<'a>(
+    state: tauri::State<'a, MidiState>,
+) -> Result<Recorder, CommandError> {
+    let midi = state.lock().await;
+
+    let mut recorder = midi.recorder.lock().unwrap();
+    recorder.pause_recording()?;
+
+    Ok((&*recorder).into())
+}
+
+#[tauri::command]
+pub async fn resume_midi_recording<'a>(
+    state: tauri::State<'a, MidiState>,
+) -> Result<Recorder, CommandError> {
     let midi = state.lock().await;
 
     let mut recorder = midi.recorder.lock().unwrap();
-    recorder.stop_recording()?;
+    recorder.resume_recording()?;
 
     Ok((&*recorder).into())
 }
 
+#[tauri::command]
+pub async fn save_midi_capture<'a>(
+    selection: CaptureSelection,
+    state: tauri::State<'a, MidiState>,
+) -> Result<Recorder, CommandError> {
+    let midi = state.lock().await;
+
+    let mut recorder = midi.recorder.lock().unwrap();
+    recorder.save_capture(selection)?;
+
+    Ok((&*recorder).into())
+}
+
+#[tauri::command]
+pub async fn set_midi_capture_minutes<'a>(
+    minutes: u32,
+    state: tauri::State<'a, MidiState>,
+) -> Result<Recorder, CommandError> {
+    let midi = state.lock().await;
+
+    let mut recorder = midi.recorder.lock().unwrap();
+    recorder.set_capture_minutes(minutes)?;
+
+    Ok((&*recorder).into())
+}
+
+#[tauri::command]
+pub async fn set_midi_input_filter<'a>(
+    input_filter: InputFilter,
+    app: tauri::AppHandle,
+    state: tauri::State<'a, MidiState>,
+) -> Result<Recorder, CommandError> {
+    let settings_path = get_settings_path(&app)?;
+    let mut settings = Settings::load(&settings_path);
+    settings.input_filter = input_filter.clone();
+    settings.save(&settings_path)?;
+
+    let midi = state.lock().await;
+
+    let mut recorder = midi.recorder.lock().unwrap();
+    recorder.set_input_filter(input_filter);
+
+    Ok((&*recorder).into())
+}
+
+#[tauri::command]
+pub async fn get_midi_metronome<'a>(
+    state: tauri::State<'a, MidiState>,
+) -> Result<MetronomeSettings, CommandError> {
+    let midi = state.lock().await;
+    let metronome = midi.metronome.lock().unwrap();
+
+    Ok(metronome.get_settings().clone())
+}
+
+#[tauri::command]
+pub async fn set_midi_metronome<'a>(
+    metronome_settings: MetronomeSettings,
+    app: tauri::AppHandle,
+    state: tauri::State<'a, MidiState>,
+) -> Result<MetronomeSettings, CommandError> {
+    let midi = state.lock().await;
+    let mut metronome = midi.metronome.lock().unwrap();
+    metronome.set_settings(metronome_settings.clone())?;
+
+    let settings_path = get_settings_path(&app)?;
+    let mut settings = Settings::load(&settings_path);
+    settings.metronome = metronome_settings;
+    settings.save(&settings_path)?;
+
+    Ok(metronome.get_settings().clone())
+}
+
+#[tauri::command]
+pub async fn get_midi_export_settings(
+    app: tauri::AppHandle,
+) -> Result<MidiWriterConfig, CommandError> {
+    Ok(Settings::load(&get_settings_path(&app)?).export)
+}
+
+/// Sets how recordings and merged overdubs are written when they are exported.
+#[tauri::command]
+pub async fn set_midi_export_settings(
+    export_settings: MidiWriterConfig,
+    app: tauri::AppHandle,
+) -> Result<MidiWriterConfig, CommandError> {
+    let settings_path = get_settings_path(&app)?;
+    let mut settings = Settings::load(&settings_path);
+    settings.export = export_settings;
+    settings.save(&settings_path)?;
+
+    Ok(settings.export)
+}
+
 #[tauri::command]
 pub async fn save_midi_recording<'a>(
     index: usize,
     app: tauri::AppHandle,
     state: tauri::State<'a, MidiState>,
-) -> Result<(), String> {
+) -> Result<(), CommandError> {
     let file_path = app
         .dialog()
         .file()
         .add_filter("Standard MIDI Files", &["mid"])
+        .add_filter("RIFF MIDI Files", &[RMID_FILE_EXTENSION])
         .blocking_save_file();
 
     if let Some(path) = file_path
@@ -150,15 +484,15 @@ pub async fn save_midi_recording<'a>(
                 .get_recordings()
                 .get(index)
                 .ok_or_else(|| format!("Recording with index {} not found", index))?;
-            recording.clone()
+            let mut midi_file = recording.to_midi_file();
+            midi_file.set_info(RmidInfo {
+                title: Some(recording.metadata.name.clone()),
+                ..Default::default()
+            });
+            midi_file
         };
-        let tempo = 500_000;
-        let midi_header = MidiHeader::single_multi_channel_track();
-        let track =
-            MidiTrack::from_time_stamped_messages(recording, tempo, midi_header.get_division());
-
-        let midi_file = MidiFile::new(midi_header, vec![track]);
-        let midi_bytes: Vec<u8> = (&midi_file).try_into()?;
+        let export_settings = Settings::load(&get_settings_path(&app)?).export;
+        let midi_bytes = to_file_bytes(&recording, &path_buf, &export_settings)?;
 
         std::fs::write(path_buf, midi_bytes)
             .map_err(|e| format!("Failed to write MIDI file: {}", e))?;
@@ -167,11 +501,65 @@ pub async fn save_midi_recording<'a>(
     Ok(())
 }
 
+#[tauri::command]
+pub async fn rename_midi_recording<'a>(
+    index: usize,
+    name: String,
+    state: tauri::State<'a, MidiState>,
+) -> Result<Recorder, CommandError> {
+    let midi = state.lock().await;
+
+    let mut recorder = midi.recorder.lock().unwrap();
+    recorder.rename_recording(index, name)?;
+
+    Ok((&*recorder).into())
+}
+
+#[tauri::command]
+pub async fn set_midi_recording_tags<'a>(
+    index: usize,
+    tags: Vec<String>,
+    state: tauri::State<'a, MidiState>,
+) -> Result<Recorder, CommandError> {
+    let midi = state.lock().await;
+
+    let mut recorder = midi.recorder.lock().unwrap();
+    recorder.set_recording_tags(index, tags)?;
+
+    Ok((&*recorder).into())
+}
+
+#[tauri::command]
+pub async fn recover_midi_recording<'a>(
+    index: usize,
+    state: tauri::State<'a, MidiState>,
+) -> Result<Recorder, CommandError> {
+    let midi = state.lock().await;
+
+    let mut recorder = midi.recorder.lock().unwrap();
+    recorder.recover_recording(index)?;
+
+    Ok((&*recorder).into())
+}
+
+#[tauri::command]
+pub async fn discard_unfinished_midi_recording<'a>(
+    index: usize,
+    state: tauri::State<'a, MidiState>,
+) -> Result<Recorder, CommandError> {
+    let midi = state.lock().await;
+
+    let mut recorder = midi.recorder.lock().unwrap();
+    recorder.discard_unfinished_journal(index)?;
+
+    Ok((&*recorder).into())
+}
+
 #[tauri::command]
 pub async fn delete_midi_recording<'a>(
     index: usize,
     state: tauri::State<'a, MidiState>,
-) -> Result<Recorder, String> {
+) -> Result<Recorder, CommandError> {
     let midi = state.lock().await;
 
     let mut recorder = midi.recorder.lock().unwrap();
@@ -181,7 +569,9 @@ pub async fn delete_midi_recording<'a>(
 }
 
 #[tauri::command]
-pub async fn get_midi_playback<'a>(state: tauri::State<'a, MidiState>) -> Result<Playback, String> {
+pub async fn get_midi_playback<'a>(
+    state: tauri::State<'a, MidiState>,
+) -> Result<Playback, CommandError> {
     let midi = state.lock().await;
     let playback = midi.playback.lock().await;
 
@@ -192,8 +582,13 @@ pub async fn get_midi_playback<'a>(state: tauri::State<'a, MidiState>) -> Result
 pub async fn play_midi_recording<'a>(
     index: usize,
     state: tauri::State<'a, MidiState>,
-) -> Result<Playback, String> {
+) -> Result<Playback, CommandError> {
     let midi = state.lock().await;
+    if midi.recorder.lock().unwrap().is_overdubbing() {
+        return Err("Cannot start another playback while overdubbing"
+            .to_string()
+            .into());
+    }
 
     let recording = {
         let recorder = midi.recorder.lock().unwrap();
@@ -201,7 +596,7 @@ pub async fn play_midi_recording<'a>(
             .get_recordings()
             .get(index)
             .ok_or_else(|| format!("Recording with index {} not found", index))?;
-        recording.clone()
+        recording.messages.clone()
     };
 
     let mut playback = midi.playback.lock().await;
@@ -212,15 +607,76 @@ pub async fn play_midi_recording<'a>(
     Ok((&*playback).into())
 }
 
+/// Plays a single take of a loop recording, to compare it with the others.
+#[tauri::command]
+pub async fn play_midi_take<'a>(
+    index: usize,
+    take: usize,
+    state: tauri::State<'a, MidiState>,
+) -> Result<Playback, CommandError> {
+    let midi = state.lock().await;
+    if midi.recorder.lock().unwrap().is_overdubbing() {
+        return Err("Cannot start another playback while overdubbing"
+            .to_string()
+            .into());
+    }
+
+    let messages = {
+        let recorder = midi.recorder.lock().unwrap();
+        let recording = recorder
+            .get_recordings()
+            .get(index)
+            .ok_or_else(|| format!("Recording with index {} not found", index))?;
+        recording
+            .takes
+            .get(take)
+            .ok_or_else(|| format!("Take with index {} not found", take))?
+            .clone()
+    };
+
+    let mut playback = midi.playback.lock().await;
+    playback
+        .play(&messages, TrackInfo::Recording(index))
+        .await?;
+
+    Ok((&*playback).into())
+}
+
+#[tauri::command]
+pub async fn pick_midi_take<'a>(
+    index: usize,
+    take: usize,
+    state: tauri::State<'a, MidiState>,
+) -> Result<Recorder, CommandError> {
+    let midi = state.lock().await;
+    let mut recorder = midi.recorder.lock().unwrap();
+    recorder.pick_take(index, take)?;
+
+    Ok((&*recorder).into())
+}
+
+#[tauri::command]
+pub async fn comp_midi_takes<'a>(
+    index: usize,
+    segments: Vec<CompSegment>,
+    state: tauri::State<'a, MidiState>,
+) -> Result<Recorder, CommandError> {
+    let midi = state.lock().await;
+    let mut recorder = midi.recorder.lock().unwrap();
+    recorder.comp_takes(index, segments)?;
+
+    Ok((&*recorder).into())
+}
+
 #[tauri::command]
-pub async fn load_midi_track<'a>(
+pub async fn load_midi_file<'a>(
     app: tauri::AppHandle,
     state: tauri::State<'a, MidiState>,
-) -> Result<Playback, String> {
+) -> Result<Playback, CommandError> {
     let file_path = app
         .dialog()
         .file()
-        .add_filter("Standard MIDI Files", &["mid"])
+        .add_filter("MIDI Files", &["mid", RMID_FILE_EXTENSION])
         .blocking_pick_file();
 
     let midi = state.lock().await;
@@ -229,41 +685,63 @@ pub async fn load_midi_track<'a>(
     if let Some(path) = file_path
         && let FilePath::Path(path_buf) = path
     {
-        let result =
-            std::fs::read(path_buf).map_err(|e| format!("Failed to read MIDI file: {}", e));
-        let midi_bytes = result.unwrap();
-        let midi_file: MidiFile = midi_bytes
-            .as_slice()
-            .try_into()
-            .map_err(|e| format!("Failed to parse MIDI file: {}", e))?;
-        playback.load_track(midi_file)?;
+        let midi_bytes =
+            std::fs::read(&path_buf).map_err(|e| format!("Failed to read MIDI file: {}", e))?;
+        let (midi_file, warnings) = MidiFile::parse(&midi_bytes, ParseMode::Lenient)?;
+        playback.load_file(midi_file, path_buf.to_string_lossy().to_string(), warnings)?;
     }
 
     Ok((&*playback).into())
 }
 
+#[tauri::command]
+pub async fn play_midi_file<'a>(
+    state: tauri::State<'a, MidiState>,
+) -> Result<Playback, CommandError> {
+    let midi = state.lock().await;
+    if midi.recorder.lock().unwrap().is_overdubbing() {
+        return Err("Cannot start another playback while overdubbing"
+            .to_string()
+            .into());
+    }
+
+    let mut playback = midi.playback.lock().await;
+    playback.play_file(None).await?;
+
+    Ok((&*playback).into())
+}
+
 #[tauri::command]
 pub async fn play_midi_track<'a>(
     index: usize,
     state: tauri::State<'a, MidiState>,
-) -> Result<Playback, String> {
+) -> Result<Playback, CommandError> {
     let midi = state.lock().await;
+    if midi.recorder.lock().unwrap().is_overdubbing() {
+        return Err("Cannot start another playback while overdubbing"
+            .to_string()
+            .into());
+    }
 
     let mut playback = midi.playback.lock().await;
-    playback.play_track(index).await?;
+    playback.play_file(Some(index)).await?;
 
     Ok((&*playback).into())
 }
 
 #[tauri::command]
-pub async fn eject_midi_track<'a>(
-    index: usize,
+pub async fn eject_midi_file<'a>(
     state: tauri::State<'a, MidiState>,
-) -> Result<Playback, String> {
+) -> Result<Playback, CommandError> {
     let midi = state.lock().await;
+    if midi.recorder.lock().unwrap().is_overdubbing() {
+        return Err("Cannot eject the MIDI file while overdubbing"
+            .to_string()
+            .into());
+    }
 
     let mut playback = midi.playback.lock().await;
-    playback.eject_track(index).await?;
+    playback.eject_file().await?;
 
     Ok((&*playback).into())
 }
@@ -271,8 +749,13 @@ pub async fn eject_midi_track<'a>(
 #[tauri::command]
 pub async fn pause_midi_playback<'a>(
     state: tauri::State<'a, MidiState>,
-) -> Result<Playback, String> {
+) -> Result<Playback, CommandError> {
     let midi = state.lock().await;
+    if midi.recorder.lock().unwrap().is_overdubbing() {
+        return Err("Cannot pause the playback while overdubbing"
+            .to_string()
+            .into());
+    }
     let mut playback = midi.playback.lock().await;
     playback.pause()?;
 
@@ -282,7 +765,7 @@ pub async fn pause_midi_playback<'a>(
 #[tauri::command]
 pub async fn resume_midi_playback<'a>(
     state: tauri::State<'a, MidiState>,
-) -> Result<Playback, String> {
+) -> Result<Playback, CommandError> {
     let midi = state.lock().await;
     let mut playback = midi.playback.lock().await;
     playback.resume()?;
@@ -293,10 +776,185 @@ pub async fn resume_midi_playback<'a>(
 #[tauri::command]
 pub async fn stop_midi_playback<'a>(
     state: tauri::State<'a, MidiState>,
-) -> Result<Playback, String> {
+) -> Result<Playback, CommandError> {
     let midi = state.lock().await;
+    if midi.recorder.lock().unwrap().is_overdubbing() {
+        return Err("Cannot stop the playback while overdubbing"
+            .to_string()
+            .into());
+    }
     let mut playback = midi.playback.lock().await;
     playback.stop().await?;
 
     Ok((&*playback).into())
 }
+
+#[tauri::command]
+pub async fn seek_midi_playback<'a>(
+    position_milliseconds: u32,
+    state: tauri::State<'a, MidiState>,
+) -> Result<Playback, CommandError> {
+    let midi = state.lock().await;
+    if midi.recorder.lock().unwrap().is_overdubbing() {
+        return Err("Cannot seek while overdubbing".to_string().into());
+    }
+    let mut playback = midi.playback.lock().await;
+    playback.seek(Duration::from_millis(position_milliseconds as u64))?;
+
+    Ok((&*playback).into())
+}
+
+#[tauri::command]
+pub async fn get_midi_playback_jitter<'a>(
+    state: tauri::State<'a, MidiState>,
+) -> Result<PlaybackJitter, CommandError> {
+    let midi = state.lock().await;
+    let playback = midi.playback.lock().await;
+
+    Ok((&playback.get_jitter_stats()).into())
+}
+
+#[tauri::command]
+pub async fn panic_midi_playback<'a>(
+    state: tauri::State<'a, MidiState>,
+) -> Result<Playback, CommandError> {
+    let midi = state.lock().await;
+    let mut playback = midi.playback.lock().await;
+    playback.panic().await?;
+
+    Ok((&*playback).into())
+}
+
+#[tauri::command]
+pub async fn set_midi_playback_loop<'a>(
+    start_milliseconds: u32,
+    end_milliseconds: u32,
+    state: tauri::State<'a, MidiState>,
+) -> Result<Playback, CommandError> {
+    let midi = state.lock().await;
+    if midi.recorder.lock().unwrap().is_overdubbing() {
+        return Err("Cannot change the loop while overdubbing"
+            .to_string()
+            .into());
+    }
+    let mut playback = midi.playback.lock().await;
+    playback.set_loop(Some(LoopRegion {
+        start: Duration::from_millis(start_milliseconds as u64),
+        end: Duration::from_millis(end_milliseconds as u64),
+    }))?;
+
+    Ok((&*playback).into())
+}
+
+#[tauri::command]
+pub async fn clear_midi_playback_loop<'a>(
+    state: tauri::State<'a, MidiState>,
+) -> Result<Playback, CommandError> {
+    let midi = state.lock().await;
+    if midi.recorder.lock().unwrap().is_overdubbing() {
+        return Err("Cannot change the loop while overdubbing"
+            .to_string()
+            .into());
+    }
+    let mut playback = midi.playback.lock().await;
+    playback.set_loop(None)?;
+
+    Ok((&*playback).into())
+}
+
+#[tauri::command]
+pub async fn set_midi_playback_rate<'a>(
+    rate: f64,
+    state: tauri::State<'a, MidiState>,
+) -> Result<Playback, CommandError> {
+    let midi = state.lock().await;
+    if midi.recorder.lock().unwrap().is_overdubbing() {
+        return Err("Cannot change the playback rate while overdubbing"
+            .to_string()
+            .into());
+    }
+    let mut playback = midi.playback.lock().await;
+    playback.set_rate(rate)?;
+
+    Ok((&*playback).into())
+}
+
+#[tauri::command]
+pub async fn set_midi_playback_transform<'a>(
+    transform: PlaybackTransform,
+    state: tauri::State<'a, MidiState>,
+) -> Result<Playback, CommandError> {
+    let midi = state.lock().await;
+    let mut playback = midi.playback.lock().await;
+    playback.set_transform(transform.into())?;
+
+    Ok((&*playback).into())
+}
+
+#[tauri::command]
+pub async fn set_midi_channel_muted<'a>(
+    channel: MidiChannel,
+    muted: bool,
+    state: tauri::State<'a, MidiState>,
+) -> Result<Playback, CommandError> {
+    let midi = state.lock().await;
+    let mut playback = midi.playback.lock().await;
+    playback.set_channel_muted(channel, muted);
+
+    Ok((&*playback).into())
+}
+
+#[tauri::command]
+pub async fn set_midi_channel_soloed<'a>(
+    channel: MidiChannel,
+    soloed: bool,
+    state: tauri::State<'a, MidiState>,
+) -> Result<Playback, CommandError> {
+    let midi = state.lock().await;
+    let mut playback = midi.playback.lock().await;
+    playback.set_channel_soloed(channel, soloed);
+
+    Ok((&*playback).into())
+}
+
+#[tauri::command]
+pub async fn set_midi_track_muted<'a>(
+    index: usize,
+    muted: bool,
+    state: tauri::State<'a, MidiState>,
+) -> Result<Playback, CommandError> {
+    let midi = state.lock().await;
+    let mut playback = midi.playback.lock().await;
+    playback.set_track_muted(index, muted)?;
+
+    Ok((&*playback).into())
+}
+
+#[tauri::command]
+pub async fn set_midi_track_soloed<'a>(
+    index: usize,
+    soloed: bool,
+    state: tauri::State<'a, MidiState>,
+) -> Result<Playback, CommandError> {
+    let midi = state.lock().await;
+    let mut playback = midi.playback.lock().await;
+    playback.set_track_soloed(index, soloed)?;
+
+    Ok((&*playback).into())
+}
+
+/// Writes an RMID file for paths with its extension, a Standard MIDI File otherwise.
+fn to_file_bytes(
+    file: &MidiFile,
+    path: &Path,
+    config: &MidiWriterConfig,
+) -> Result<Vec<u8>, MidiFileError> {
+    let is_rmid = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case(RMID_FILE_EXTENSION));
+    if is_rmid {
+        file.to_rmid_bytes(config)
+    } else {
+        file.to_bytes(config)
+    }
+}
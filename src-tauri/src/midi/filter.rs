@@ -0,0 +1,135 @@
+use crate::midi::message::{
+    ChannelMessage, ChannelVoiceMessage, MidiChannel, MidiMessage, SystemMessage,
+    SystemRealTimeMessage,
+};
+
+/// Classes of input messages that are dropped before they reach recordings,
+/// and optionally the monitor.
+#[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct InputFilter {
+    pub active_sensing: bool,
+    pub timing_clock: bool,
+    pub system_exclusive: bool,
+    /// Polyphonic key pressure and channel pressure.
+    pub aftertouch: bool,
+    /// Control change numbers to drop.
+    pub controllers: Vec<u8>,
+    /// Channels to drop entirely.
+    pub channels: Vec<MidiChannel>,
+    /// Whether the filter also applies to messages sent to the monitor.
+    pub apply_to_monitor: bool,
+}
+
+impl Default for InputFilter {
+    fn default() -> Self {
+        // keyboards send these all the time, but they are never worth recording
+        Self {
+            active_sensing: true,
+            timing_clock: true,
+            system_exclusive: false,
+            aftertouch: false,
+            controllers: Vec::new(),
+            channels: Vec::new(),
+            apply_to_monitor: false,
+        }
+    }
+}
+
+impl InputFilter {
+    pub fn accepts(&self, message: &MidiMessage) -> bool {
+        match message {
+            MidiMessage::Channel { channel, message } => {
+                if self.channels.contains(channel) {
+                    return false;
+                }
+
+                match message {
+                    ChannelMessage::Voice(
+                        ChannelVoiceMessage::PolyphonicKeyPressure { .. }
+                        | ChannelVoiceMessage::ChannelPressure(_),
+                    ) => !self.aftertouch,
+                    ChannelMessage::Voice(ChannelVoiceMessage::ControlChange {
+                        controller,
+                        ..
+                    }) => !self.controllers.contains(controller),
+                    _ => true,
+                }
+            }
+            MidiMessage::System(SystemMessage::RealTime(SystemRealTimeMessage::ActiveSensing)) => {
+                !self.active_sensing
+            }
+            MidiMessage::System(SystemMessage::RealTime(SystemRealTimeMessage::TimingClock)) => {
+                !self.timing_clock
+            }
+            MidiMessage::System(SystemMessage::Exclusive(_)) => !self.system_exclusive,
+            _ => true,
+        }
+    }
+
+    pub fn accepts_monitored(&self, message: &MidiMessage) -> bool {
+        !self.apply_to_monitor || self.accepts(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(data: &[u8]) -> MidiMessage {
+        MidiMessage::try_from(data).unwrap()
+    }
+
+    #[test]
+    fn default_drops_active_sensing_and_timing_clock_only() {
+        let filter = InputFilter::default();
+
+        assert!(!filter.accepts(&message(&[0xFE])));
+        assert!(!filter.accepts(&message(&[0xF8])));
+        assert!(filter.accepts(&message(&[0xFA])));
+        assert!(filter.accepts(&message(&[0x90, 60, 100])));
+        assert!(filter.accepts(&message(&[0xD0, 40])));
+        assert!(filter.accepts(&message(&[0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7])));
+    }
+
+    #[test]
+    fn drops_every_message_of_filtered_channels() {
+        let filter = InputFilter {
+            channels: vec![MidiChannel::Channel2],
+            ..Default::default()
+        };
+
+        assert!(filter.accepts(&message(&[0x90, 60, 100])));
+        assert!(!filter.accepts(&message(&[0x91, 60, 100])));
+        assert!(!filter.accepts(&message(&[0xB1, 7, 100])));
+        assert!(!filter.accepts(&message(&[0xC1, 5])));
+    }
+
+    #[test]
+    fn drops_filtered_message_types() {
+        let filter = InputFilter {
+            system_exclusive: true,
+            aftertouch: true,
+            controllers: vec![1, 64],
+            ..Default::default()
+        };
+
+        assert!(!filter.accepts(&message(&[0xA0, 60, 40])));
+        assert!(!filter.accepts(&message(&[0xD0, 40])));
+        assert!(!filter.accepts(&message(&[0xB0, 1, 10])));
+        assert!(!filter.accepts(&message(&[0xB0, 64, 127])));
+        assert!(filter.accepts(&message(&[0xB0, 7, 100])));
+        assert!(!filter.accepts(&message(&[0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7])));
+        assert!(filter.accepts(&message(&[0x90, 60, 100])));
+    }
+
+    #[test]
+    fn monitor_is_only_filtered_when_applied_to_it() {
+        let mut filter = InputFilter::default();
+        assert!(filter.accepts_monitored(&message(&[0xFE])));
+
+        filter.apply_to_monitor = true;
+        assert!(!filter.accepts_monitored(&message(&[0xFE])));
+        assert!(filter.accepts_monitored(&message(&[0x90, 60, 100])));
+    }
+}
@@ -0,0 +1,160 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::midi::message::TimeStampedMidiMessage;
+
+/// How often the journal is written to the disk; at most this much of a recording is lost.
+const SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Describes the recording a journal belongs to, stored in its first line.
+#[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct JournalHeader {
+    pub id: String,
+    /// Milliseconds since the Unix epoch.
+    pub created_milliseconds: u64,
+    pub input_device: Option<String>,
+}
+
+/// The messages of a recording that was not finished, e.g. because the app crashed.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct UnfinishedJournal {
+    pub header: JournalHeader,
+    pub messages: Vec<TimeStampedMidiMessage>,
+}
+
+/// Appends the messages of a running recording to a file, one JSON line per message.
+///
+/// Writing happens on a separate thread, so that the MIDI callback never waits for the disk.
+#[derive(Debug)]
+pub struct RecordingJournal {
+    path: PathBuf,
+    sender: Option<Sender<TimeStampedMidiMessage>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl RecordingJournal {
+    pub fn create(path: PathBuf, header: &JournalHeader) -> Result<RecordingJournal, String> {
+        let file = File::create(&path)
+            .map_err(|e| format!("Failed to create recording journal: {}", e))?;
+        let mut file = BufWriter::new(file);
+        let header =
+            serde_json::to_string(header).map_err(|e| format!("Failed to write journal: {}", e))?;
+        writeln!(file, "{}", header)
+            .and_then(|_| file.flush())
+            .map_err(|e| format!("Failed to write journal: {}", e))?;
+
+        let (sender, receiver) = mpsc::channel::<TimeStampedMidiMessage>();
+        let writer = thread::spawn(move || {
+            let mut last_sync = Instant::now();
+            let mut is_dirty = false;
+            loop {
+                match receiver.recv_timeout(SYNC_INTERVAL) {
+                    Ok(message) => {
+                        let result = serde_json::to_string(&message)
+                            .map_err(|e| e.to_string())
+                            .and_then(|line| writeln!(file, "{}", line).map_err(|e| e.to_string()));
+                        if let Err(e) = result {
+                            eprintln!("Failed to write recording journal: {}", e);
+                        }
+                        is_dirty = true;
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+
+                if is_dirty && last_sync.elapsed() >= SYNC_INTERVAL {
+                    Self::sync(&mut file);
+                    last_sync = Instant::now();
+                    is_dirty = false;
+                }
+            }
+            Self::sync(&mut file);
+        });
+
+        Ok(RecordingJournal {
+            path,
+            sender: Some(sender),
+            writer: Some(writer),
+        })
+    }
+
+    /// Queues a message for writing; never blocks.
+    pub fn append(&self, message: TimeStampedMidiMessage) {
+        if let Some(sender) = &self.sender {
+            // the writer only stops when the journal is closed
+            let _ = sender.send(message);
+        }
+    }
+
+    /// Writes all queued messages and closes the file, which is kept on the disk.
+    pub fn close(mut self) {
+        self.stop_writer();
+    }
+
+    /// Closes the journal and removes its file, once the recording is safely stored elsewhere.
+    pub fn discard(mut self) {
+        self.stop_writer();
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            eprintln!("Failed to remove recording journal: {}", e);
+        }
+    }
+
+    /// Reads a journal left behind; a line cut off by the crash is ignored.
+    pub fn read(path: &Path) -> Result<UnfinishedJournal, String> {
+        let file = File::open(path).map_err(|e| format!("Failed to open journal: {}", e))?;
+        let mut lines = BufReader::new(file).lines();
+
+        let header = lines
+            .next()
+            .ok_or_else(|| "Journal is empty".to_string())?
+            .map_err(|e| format!("Failed to read journal: {}", e))?;
+        let header: JournalHeader = serde_json::from_str(&header)
+            .map_err(|e| format!("Failed to parse journal header: {}", e))?;
+
+        let messages = lines
+            .map_while(Result::ok)
+            .map_while(|line| serde_json::from_str(&line).ok())
+            .collect();
+
+        Ok(UnfinishedJournal { header, messages })
+    }
+
+    fn stop_writer(&mut self) {
+        // dropping the sender ends the writer once the queue is empty
+        self.sender.take();
+        if let Some(writer) = self.writer.take()
+            && writer.join().is_err()
+        {
+            eprintln!("Recording journal writer panicked");
+        }
+    }
+
+    fn sync(file: &mut BufWriter<File>) {
+        let result = file.flush().and_then(|_| file.get_ref().sync_data());
+        if let Err(e) = result {
+            eprintln!("Failed to sync recording journal: {}", e);
+        }
+    }
+}
+
+/// Journals are the same if they write to the same file.
+impl PartialEq for RecordingJournal {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path
+    }
+}
+
+impl Eq for RecordingJournal {}
+
+impl Drop for RecordingJournal {
+    fn drop(&mut self) {
+        self.stop_writer();
+    }
+}
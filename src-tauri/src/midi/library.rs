@@ -0,0 +1,748 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use tauri::{AppHandle, Manager};
+
+use crate::midi::{
+    journal::{JournalHeader, RecordingJournal, UnfinishedJournal},
+    message::{
+        ChannelMessage, ChannelVoiceMessage, MidiChannel, MidiMessage, TimeStampedMidiMessage,
+    },
+    smf::{DEFAULT_TEMPO, MetaEvent, MidiDivision, MidiFile, MidiFormat, MidiHeader, MidiTrack},
+};
+
+const LIBRARY_DIRECTORY_NAME: &str = "recordings";
+const MIDI_FILE_EXTENSION: &str = "mid";
+const METADATA_FILE_EXTENSION: &str = "json";
+const JOURNAL_FILE_EXTENSION: &str = "journal";
+const TAKES_FILE_EXTENSION: &str = "takes.mid";
+/// Recordings are stored with a higher resolution than the default division,
+/// so that the timing of the performance is kept (about 0.5ms at 120 BPM).
+const RECORDING_DIVISION: MidiDivision = MidiDivision::TicksPerQuarterNote(960);
+
+/// Tempo and time signature a recording was played to, e.g. with the metronome.
+///
+/// The messages of such a recording start at the first beat of the first bar.
+#[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingGrid {
+    /// Microseconds per quarter note.
+    pub tempo: u32,
+    pub beats_per_bar: u8,
+    /// Note value of a beat: 2, 4, 8 or 16.
+    pub beat_unit: u8,
+}
+
+impl RecordingGrid {
+    pub fn get_beat_duration(&self) -> Duration {
+        Duration::from_micros(self.tempo as u64 * 4 / self.beat_unit as u64)
+    }
+
+    pub fn get_meta_events(&self) -> Vec<MetaEvent> {
+        vec![
+            MetaEvent::SetTempo(self.tempo),
+            MetaEvent::TimeSignature {
+                numerator: self.beats_per_bar,
+                // the denominator is stored as a power of two
+                denominator: self.beat_unit.trailing_zeros() as u8,
+                // MIDI clocks (24 per quarter note) per beat
+                clocks_per_click: 96 / self.beat_unit,
+                notated_32nd_notes_per_quarter_note: 8,
+            },
+        ]
+    }
+}
+
+/// What was played back while a recording was made, so that it can be merged with it.
+#[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, Clone)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum OverdubSource {
+    Recording { id: String },
+    MidiFile { path: String },
+}
+
+/// A range of a take that is used for a comp, in positions of the loop playback.
+#[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CompSegment {
+    pub take: usize,
+    pub start_milliseconds: u32,
+    pub end_milliseconds: u32,
+}
+
+/// What the messages of a loop recording are made of.
+#[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, Clone)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum TakeSelection {
+    Take { index: usize },
+    Comp { segments: Vec<CompSegment> },
+}
+
+/// Everything known about a recording besides its messages, stored next to it as JSON.
+#[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingMetadata {
+    /// Name of the files of the recording in the library.
+    pub id: String,
+    pub name: String,
+    /// Milliseconds since the Unix epoch.
+    pub created_milliseconds: u64,
+    pub duration_milliseconds: u32,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Name of the input port the recording was played on.
+    #[serde(default)]
+    pub input_device: Option<String>,
+    #[serde(default)]
+    pub grid: Option<RecordingGrid>,
+    /// Set for takes that were recorded along with a playback; their timestamps
+    /// are positions in that playback.
+    #[serde(default)]
+    pub overdub_of: Option<OverdubSource>,
+    /// Set for loop recordings, whose messages are taken from their takes.
+    #[serde(default)]
+    pub take_selection: Option<TakeSelection>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Recording {
+    pub metadata: RecordingMetadata,
+    pub messages: Vec<TimeStampedMidiMessage>,
+    /// Every pass of a loop recording, with positions in the loop playback.
+    pub takes: Vec<Vec<TimeStampedMidiMessage>>,
+}
+
+impl Recording {
+    pub fn new(
+        name: String,
+        messages: Vec<TimeStampedMidiMessage>,
+        input_device: Option<String>,
+    ) -> Recording {
+        let created_milliseconds = get_current_milliseconds();
+        let duration_milliseconds = get_duration_milliseconds(&messages);
+
+        Recording {
+            metadata: RecordingMetadata {
+                id: format!("recording-{}", created_milliseconds),
+                name,
+                created_milliseconds,
+                duration_milliseconds,
+                tags: Vec::new(),
+                input_device,
+                grid: None,
+                overdub_of: None,
+                take_selection: None,
+            },
+            messages,
+            takes: Vec::new(),
+        }
+    }
+
+    /// Creates a loop recording from the passes over the loop region, starting with the last one.
+    pub fn from_takes(
+        name: String,
+        takes: Vec<Vec<TimeStampedMidiMessage>>,
+        input_device: Option<String>,
+    ) -> Result<Recording, String> {
+        let mut recording = Recording::new(name, Vec::new(), input_device);
+        recording.takes = takes;
+        recording.pick_take(recording.takes.len().saturating_sub(1))?;
+
+        Ok(recording)
+    }
+
+    pub fn pick_take(&mut self, index: usize) -> Result<(), String> {
+        let take = self
+            .takes
+            .get(index)
+            .ok_or_else(|| "Take index out of bounds".to_string())?;
+
+        self.messages = take.clone();
+        self.metadata.duration_milliseconds = get_duration_milliseconds(&self.messages);
+        self.metadata.take_selection = Some(TakeSelection::Take { index });
+
+        Ok(())
+    }
+
+    /// Puts the messages together from ranges of different takes, which must not overlap.
+    ///
+    /// Notes are cut at the borders of the ranges.
+    pub fn comp_takes(&mut self, mut segments: Vec<CompSegment>) -> Result<(), String> {
+        if segments.is_empty() {
+            return Err("A comp needs at least one range".to_string());
+        }
+        segments.sort_by_key(|segment| segment.start_milliseconds);
+        for segment in &segments {
+            if segment.take >= self.takes.len() {
+                return Err("Take index out of bounds".to_string());
+            }
+            if segment.start_milliseconds >= segment.end_milliseconds {
+                return Err("The start of a comp range must be before its end".to_string());
+            }
+        }
+        if segments
+            .windows(2)
+            .any(|pair| pair[0].end_milliseconds > pair[1].start_milliseconds)
+        {
+            return Err("The ranges of a comp must not overlap".to_string());
+        }
+
+        let mut messages: Vec<TimeStampedMidiMessage> = segments
+            .iter()
+            .flat_map(|segment| {
+                get_messages_in_range(
+                    &self.takes[segment.take],
+                    segment.start_milliseconds as u64 * 1000,
+                    Some(segment.end_milliseconds as u64 * 1000),
+                )
+            })
+            .collect();
+        messages.sort_by_key(|message| message.timestamp_microseconds);
+        if messages.is_empty() {
+            return Err("Nothing was played in the ranges of the comp".to_string());
+        }
+
+        self.metadata.duration_milliseconds = get_duration_milliseconds(&messages);
+        self.metadata.take_selection = Some(TakeSelection::Comp { segments });
+        self.messages = messages;
+
+        Ok(())
+    }
+
+    /// Returns where the playback of the recording starts in its file.
+    ///
+    /// The playback starts with the first message, while the file of a recording on a grid
+    /// starts with the first beat and the file of an overdub with the start of its playback.
+    pub fn get_playback_offset(&self) -> u64 {
+        match self.messages.first() {
+            Some(first) if self.is_positioned() => first.timestamp_microseconds,
+            _ => 0,
+        }
+    }
+
+    /// Replaces the messages from start (inclusive) to end (exclusive) with a take, keeping
+    /// everything else.
+    ///
+    /// Notes of the recording that are held at the start are ended there, notes started in
+    /// the range are dropped with their end. The take is cut to the range in the same way and
+    /// its notes still held at the end are ended there.
+    pub fn replace_range(&mut self, start: u64, end: u64, take: &[TimeStampedMidiMessage]) {
+        let before = get_messages_in_range(&self.messages, 0, Some(start));
+        let replacement = get_messages_in_range(take, start, Some(end));
+        let after = get_messages_in_range(&self.messages, end, None);
+
+        let mut messages: Vec<TimeStampedMidiMessage> =
+            before.into_iter().chain(replacement).chain(after).collect();
+        // stable, so that notes ended at a boundary are ended before new ones start there
+        messages.sort_by_key(|message| message.timestamp_microseconds);
+
+        self.metadata.duration_milliseconds = get_duration_milliseconds(&messages);
+        self.messages = messages;
+    }
+
+    /// Creates a single track file, with the tempo and time signature of the grid if there is one.
+    pub fn to_midi_file(&self) -> MidiFile {
+        let track = match &self.metadata.grid {
+            Some(grid) => MidiTrack::from_time_stamped_messages_at(
+                self.messages.clone(),
+                0,
+                grid.get_meta_events(),
+                grid.tempo,
+                &RECORDING_DIVISION,
+            ),
+            None if self.is_positioned() => Self::to_positioned_track(&self.messages),
+            None => MidiTrack::from_time_stamped_messages(
+                self.messages.clone(),
+                DEFAULT_TEMPO,
+                &RECORDING_DIVISION,
+            ),
+        };
+
+        MidiFile::new(
+            MidiHeader::new(MidiFormat::SingleMultiChannelTrack, 1, RECORDING_DIVISION),
+            vec![track],
+        )
+    }
+
+    /// Creates a file with every take as an independent sequence, or `None` without takes.
+    pub fn to_takes_file(&self) -> Result<Option<MidiFile>, String> {
+        if self.takes.is_empty() {
+            return Ok(None);
+        }
+
+        let num_tracks = u16::try_from(self.takes.len())
+            .map_err(|_| "The recording has too many takes".to_string())?;
+        let tracks = self
+            .takes
+            .iter()
+            .map(|take| Self::to_positioned_track(take))
+            .collect();
+
+        Ok(Some(MidiFile::new(
+            MidiHeader::new(MidiFormat::MultiSequence, num_tracks, RECORDING_DIVISION),
+            tracks,
+        )))
+    }
+
+    /// Whether the timestamps are positions in a playback or on a grid, which must be kept
+    /// in the file, instead of starting it with the first message.
+    fn is_positioned(&self) -> bool {
+        self.metadata.grid.is_some() || self.metadata.overdub_of.is_some()
+    }
+
+    fn to_positioned_track(messages: &[TimeStampedMidiMessage]) -> MidiTrack {
+        MidiTrack::from_time_stamped_messages_at(
+            messages.to_vec(),
+            0,
+            vec![MetaEvent::SetTempo(DEFAULT_TEMPO)],
+            DEFAULT_TEMPO,
+            &RECORDING_DIVISION,
+        )
+    }
+}
+
+/// A directory in which every recording is kept as a Standard MIDI File
+/// with a metadata sidecar of the same name.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RecordingLibrary {
+    directory: PathBuf,
+}
+
+impl RecordingLibrary {
+    pub fn new(directory: PathBuf) -> RecordingLibrary {
+        RecordingLibrary { directory }
+    }
+
+    /// Loads all recordings of the library, oldest first.
+    ///
+    /// Recordings that cannot be read are skipped, so that a single broken file
+    /// does not hide all the others.
+    pub fn load(&self) -> Result<Vec<Recording>, String> {
+        let entries = match std::fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(format!("Failed to read recording library: {}", e)),
+        };
+
+        let mut recordings: Vec<Recording> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == METADATA_FILE_EXTENSION)
+            })
+            .filter_map(|path| {
+                Self::load_recording(&path)
+                    .inspect_err(|e| eprintln!("Skipping recording {}: {}", path.display(), e))
+                    .ok()
+            })
+            .collect();
+        recordings.sort_by_key(|recording| recording.metadata.created_milliseconds);
+
+        Ok(recordings)
+    }
+
+    pub fn save(&self, recording: &Recording) -> Result<(), String> {
+        std::fs::create_dir_all(&self.directory)
+            .map_err(|e| format!("Failed to create recording library: {}", e))?;
+
+        let midi_bytes: Vec<u8> = (&recording.to_midi_file())
+            .try_into()
+            .map_err(|e| format!("Failed to write MIDI file: {}", e))?;
+        std::fs::write(
+            self.get_path(&recording.metadata.id, MIDI_FILE_EXTENSION),
+            midi_bytes,
+        )
+        .map_err(|e| format!("Failed to write MIDI file: {}", e))?;
+
+        if let Some(takes_file) = recording.to_takes_file()? {
+            let takes_bytes: Vec<u8> = (&takes_file)
+                .try_into()
+                .map_err(|e| format!("Failed to write takes: {}", e))?;
+            std::fs::write(
+                self.get_path(&recording.metadata.id, TAKES_FILE_EXTENSION),
+                takes_bytes,
+            )
+            .map_err(|e| format!("Failed to write takes: {}", e))?;
+        }
+
+        self.save_metadata(&recording.metadata)
+    }
+
+    /// Whether files of a recording with the id are in the library, loaded or not.
+    pub fn contains(&self, id: &str) -> bool {
+        [METADATA_FILE_EXTENSION, MIDI_FILE_EXTENSION]
+            .iter()
+            .any(|extension| self.get_path(id, extension).exists())
+    }
+
+    pub fn save_metadata(&self, metadata: &RecordingMetadata) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(metadata)
+            .map_err(|e| format!("Failed to serialize recording metadata: {}", e))?;
+        std::fs::write(
+            self.get_path(&metadata.id, METADATA_FILE_EXTENSION),
+            content,
+        )
+        .map_err(|e| format!("Failed to write recording metadata: {}", e))
+    }
+
+    pub fn delete(&self, id: &str) -> Result<(), String> {
+        for extension in [
+            METADATA_FILE_EXTENSION,
+            MIDI_FILE_EXTENSION,
+            TAKES_FILE_EXTENSION,
+        ] {
+            match std::fs::remove_file(self.get_path(id, extension)) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    return Err(format!("Failed to delete recording: {}", e));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Starts a journal for a new recording, which is replaced by the recording when it is saved.
+    pub fn create_journal(&self, header: &JournalHeader) -> Result<RecordingJournal, String> {
+        std::fs::create_dir_all(&self.directory)
+            .map_err(|e| format!("Failed to create recording library: {}", e))?;
+        RecordingJournal::create(self.get_path(&header.id, JOURNAL_FILE_EXTENSION), header)
+    }
+
+    /// Loads the journals that were left behind by recordings that were never finished.
+    pub fn load_journals(&self) -> Vec<UnfinishedJournal> {
+        let Ok(entries) = std::fs::read_dir(&self.directory) else {
+            return Vec::new();
+        };
+
+        let mut journals: Vec<UnfinishedJournal> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == JOURNAL_FILE_EXTENSION)
+            })
+            .filter_map(|path| {
+                RecordingJournal::read(&path)
+                    .inspect_err(|e| eprintln!("Skipping journal {}: {}", path.display(), e))
+                    .ok()
+            })
+            .collect();
+        journals.sort_by_key(|journal| journal.header.created_milliseconds);
+
+        journals
+    }
+
+    pub fn delete_journal(&self, id: &str) -> Result<(), String> {
+        match std::fs::remove_file(self.get_path(id, JOURNAL_FILE_EXTENSION)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(format!("Failed to delete recording journal: {}", e))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn load_recording(metadata_path: &Path) -> Result<Recording, String> {
+        let content = std::fs::read_to_string(metadata_path)
+            .map_err(|e| format!("Failed to read metadata: {}", e))?;
+        let metadata: RecordingMetadata = serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse metadata: {}", e))?;
+
+        let midi_bytes = std::fs::read(metadata_path.with_extension(MIDI_FILE_EXTENSION))
+            .map_err(|e| format!("Failed to read MIDI file: {}", e))?;
+        let midi_file = MidiFile::try_from(midi_bytes.as_slice())
+            .map_err(|e| format!("Failed to parse MIDI file: {}", e))?;
+
+        let takes = match metadata.take_selection {
+            Some(_) => {
+                let takes_bytes = std::fs::read(metadata_path.with_extension(TAKES_FILE_EXTENSION))
+                    .map_err(|e| format!("Failed to read takes: {}", e))?;
+                let takes_file = MidiFile::try_from(takes_bytes.as_slice())
+                    .map_err(|e| format!("Failed to parse takes: {}", e))?;
+                takes_file
+                    .get_tracks()
+                    .iter()
+                    .enumerate()
+                    .map(|(index, track)| {
+                        track.to_time_stamped_messages(&takes_file.get_tempo_map(index))
+                    })
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+
+        Ok(Recording {
+            metadata,
+            messages: midi_file.to_time_stamped_messages(),
+            takes,
+        })
+    }
+
+    fn get_path(&self, id: &str, extension: &str) -> PathBuf {
+        self.directory.join(id).with_extension(extension)
+    }
+}
+
+fn get_duration_milliseconds(messages: &[TimeStampedMidiMessage]) -> u32 {
+    match (messages.first(), messages.last()) {
+        (Some(first), Some(last)) => {
+            ((last.timestamp_microseconds - first.timestamp_microseconds) / 1000) as u32
+        }
+        _ => 0,
+    }
+}
+
+/// Splits a recording made along with a looped playback into one take per pass over the loop,
+/// with the timestamps as positions in the loop. Passes without any message are left out.
+///
+/// The playback runs from its start to the end of the loop first, so that the first pass
+/// starts at the loop start as well; notes held across the end of a pass are ended there.
+pub fn split_loop_passes(
+    messages: &[TimeStampedMidiMessage],
+    loop_start: u64,
+    loop_end: u64,
+) -> Vec<Vec<TimeStampedMidiMessage>> {
+    let loop_length = loop_end.saturating_sub(loop_start);
+    let Some(last) = messages.last() else {
+        return Vec::new();
+    };
+    if loop_length == 0 {
+        return Vec::new();
+    }
+
+    let mut passes = Vec::new();
+    let mut pass_start = loop_start;
+    while pass_start <= last.timestamp_microseconds {
+        let pass: Vec<TimeStampedMidiMessage> =
+            get_messages_in_range(messages, pass_start, Some(pass_start + loop_length))
+                .into_iter()
+                .map(|mut message| {
+                    message.timestamp_microseconds =
+                        message.timestamp_microseconds - pass_start + loop_start;
+                    message
+                })
+                .collect();
+        if !pass.is_empty() {
+            passes.push(pass);
+        }
+        pass_start += loop_length;
+    }
+
+    passes
+}
+
+/// Returns whether a message starts (true) or ends (false) a note, with its channel and note.
+fn get_note_event(message: &MidiMessage) -> Option<((u8, u8), bool)> {
+    let MidiMessage::Channel {
+        channel,
+        message: ChannelMessage::Voice(message),
+    } = message
+    else {
+        return None;
+    };
+    let channel = u8::from(channel.clone());
+
+    match message {
+        ChannelVoiceMessage::NoteOn { note, velocity } => Some(((channel, *note), *velocity > 0)),
+        ChannelVoiceMessage::NoteOff { note, .. } => Some(((channel, *note), false)),
+        _ => None,
+    }
+}
+
+/// Returns the messages from start (inclusive) to end (exclusive) without any half notes:
+/// the ends of notes started earlier are left out and notes held at the end are ended there.
+fn get_messages_in_range(
+    messages: &[TimeStampedMidiMessage],
+    start: u64,
+    end: Option<u64>,
+) -> Vec<TimeStampedMidiMessage> {
+    let mut held_notes: BTreeMap<(u8, u8), usize> = BTreeMap::new();
+    let mut range = Vec::new();
+
+    for message in messages {
+        if message.timestamp_microseconds < start
+            || end.is_some_and(|end| message.timestamp_microseconds >= end)
+        {
+            continue;
+        }
+        match get_note_event(&message.message) {
+            Some((key, true)) => *held_notes.entry(key).or_default() += 1,
+            Some((key, false)) => match held_notes.get_mut(&key) {
+                Some(count) if *count > 1 => *count -= 1,
+                Some(_) => {
+                    held_notes.remove(&key);
+                }
+                None => continue,
+            },
+            None => {}
+        }
+        range.push(message.clone());
+    }
+
+    let Some(end) = end else {
+        return range;
+    };
+    for ((channel, note), count) in held_notes {
+        let Ok(message) = MidiChannel::try_from(channel)
+            .and_then(|channel| MidiMessage::note_off(channel, note, 0))
+        else {
+            continue;
+        };
+        for _ in 0..count {
+            range.push(TimeStampedMidiMessage {
+                timestamp_microseconds: end,
+                message: message.clone(),
+            });
+        }
+    }
+
+    range
+}
+
+/// Milliseconds since the Unix epoch.
+pub fn get_current_milliseconds() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+pub fn get_library_path(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join(LIBRARY_DIRECTORY_NAME))
+        .map_err(|e| format!("Failed to resolve recording library directory: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(timestamp_microseconds: u64, note: u8, velocity: u8) -> TimeStampedMidiMessage {
+        TimeStampedMidiMessage {
+            timestamp_microseconds,
+            message: if velocity > 0 {
+                MidiMessage::note_on(MidiChannel::Channel1, note, velocity).unwrap()
+            } else {
+                MidiMessage::note_off(MidiChannel::Channel1, note, 0).unwrap()
+            },
+        }
+    }
+
+    #[test]
+    fn replace_range_trims_notes_crossing_the_boundaries() {
+        let mut recording = Recording::new(
+            "Recording".to_string(),
+            vec![
+                note(0, 60, 100),
+                // crosses the start of the range
+                note(1_000, 62, 100),
+                note(3_000, 62, 0),
+                note(3_000, 60, 0),
+                // crosses the end of the range
+                note(4_000, 64, 100),
+                note(6_000, 64, 0),
+                note(7_000, 65, 100),
+                note(8_000, 65, 0),
+            ],
+            None,
+        );
+        let take = vec![
+            // played before the range
+            note(1_000, 70, 100),
+            note(2_500, 71, 100),
+            note(2_600, 70, 0),
+            note(2_700, 71, 0),
+            // held beyond the range
+            note(4_500, 72, 100),
+            note(7_500, 72, 0),
+        ];
+
+        recording.replace_range(2_000, 5_000, &take);
+
+        assert_eq!(
+            recording.messages,
+            vec![
+                note(0, 60, 100),
+                note(1_000, 62, 100),
+                note(2_000, 60, 0),
+                note(2_000, 62, 0),
+                note(2_500, 71, 100),
+                note(2_700, 71, 0),
+                note(4_500, 72, 100),
+                note(5_000, 72, 0),
+                note(7_000, 65, 100),
+                note(8_000, 65, 0),
+            ]
+        );
+        assert_eq!(recording.metadata.duration_milliseconds, 8);
+    }
+
+    #[test]
+    fn loop_passes_become_takes_that_can_be_comped() {
+        // a loop from 1ms to 3ms, played from the start
+        let messages = vec![
+            note(1_500, 60, 100),
+            note(2_500, 60, 0),
+            // held across the end of the first pass
+            note(2_800, 62, 100),
+            note(3_200, 62, 0),
+            note(4_000, 64, 100),
+            note(4_500, 64, 0),
+        ];
+
+        let takes = split_loop_passes(&messages, 1_000, 3_000);
+        assert_eq!(
+            takes,
+            vec![
+                vec![
+                    note(1_500, 60, 100),
+                    note(2_500, 60, 0),
+                    note(2_800, 62, 100),
+                    note(3_000, 62, 0),
+                ],
+                vec![note(2_000, 64, 100), note(2_500, 64, 0)],
+            ]
+        );
+
+        let mut recording = Recording::from_takes("Loop".to_string(), takes, None).unwrap();
+        assert_eq!(
+            recording.metadata.take_selection,
+            Some(TakeSelection::Take { index: 1 })
+        );
+
+        recording
+            .comp_takes(vec![
+                CompSegment {
+                    take: 1,
+                    start_milliseconds: 2,
+                    end_milliseconds: 3,
+                },
+                CompSegment {
+                    take: 0,
+                    start_milliseconds: 1,
+                    end_milliseconds: 2,
+                },
+            ])
+            .unwrap();
+        assert_eq!(
+            recording.messages,
+            vec![
+                note(1_500, 60, 100),
+                note(2_000, 60, 0),
+                note(2_000, 64, 100),
+                note(2_500, 64, 0),
+            ]
+        );
+    }
+}
@@ -45,7 +45,7 @@ impl From<MidiChannel> for u8 {
 }
 
 impl TryFrom<u8> for MidiChannel {
-    type Error = String;
+    type Error = MessageError;
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
         match value {
@@ -65,10 +65,7 @@ impl TryFrom<u8> for MidiChannel {
             13 => Ok(MidiChannel::Channel14),
             14 => Ok(MidiChannel::Channel15),
             15 => Ok(MidiChannel::Channel16),
-            _ => Err(format!(
-                "Invalid MIDI channel: {}. Must be between 0 and 15 for internal representation.",
-                value
-            )),
+            _ => Err(MessageError::InvalidChannel { channel: value }),
         }
     }
 }
@@ -109,7 +106,13 @@ pub enum ChannelMessage {
 #[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub enum SystemCommonMessage {
-    MidiTimeCodeQuarterFrame,
+    /// One of the eight pieces of a time code: the piece is the message type (0-7),
+    /// the values are its four bits of the frame, second, minute or hour.
+    #[serde(rename_all = "camelCase")]
+    MidiTimeCodeQuarterFrame {
+        message_type: u8,
+        values: u8,
+    },
     SongPositionPointer(u16),
     SongSelect(u8),
     TuneRequest,
@@ -161,23 +164,144 @@ pub enum MidiMessage {
     System(SystemMessage),
 }
 
-fn validate_midi_data_byte(value: u8) -> Result<u8, String> {
+/// Why bytes could not be read as a MIDI message.
+#[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, Clone)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum MessageError {
+    Empty,
+    InvalidChannel {
+        channel: u8,
+    },
+    InvalidDataByte {
+        value: u8,
+    },
+    InvalidStatusByte {
+        status: u8,
+    },
+    /// The message has a different number of data bytes than its status requires.
+    WrongDataLength {
+        status: u8,
+        expected: usize,
+        actual: usize,
+    },
+    /// The message has fewer data bytes than its status requires.
+    MissingDataBytes {
+        status: u8,
+        minimum: usize,
+        actual: usize,
+    },
+    InvalidLocalControl {
+        value: u8,
+    },
+}
+
+impl std::fmt::Display for MessageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MessageError::Empty => write!(f, "MIDI message cannot be empty"),
+            MessageError::InvalidChannel { channel } => write!(
+                f,
+                "Invalid MIDI channel: {}. Must be between 0 and 15 for internal representation.",
+                channel
+            ),
+            MessageError::InvalidDataByte { value } => write!(
+                f,
+                "Invalid MIDI data byte: {}. Must be between 0 and 127.",
+                value
+            ),
+            MessageError::InvalidStatusByte { status } => write!(
+                f,
+                "Invalid MIDI status byte: {}. Must be between 0x80 and 0xFF.",
+                status
+            ),
+            MessageError::WrongDataLength {
+                status,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{} message requires exactly {} data bytes but has {}",
+                get_status_name(*status),
+                expected,
+                actual
+            ),
+            MessageError::MissingDataBytes {
+                status,
+                minimum,
+                actual,
+            } => write!(
+                f,
+                "{} message requires at least {} data bytes but has {}",
+                get_status_name(*status),
+                minimum,
+                actual
+            ),
+            MessageError::InvalidLocalControl { value } => write!(
+                f,
+                "Invalid Local Control value: {}; must be 0 or 127",
+                value
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MessageError {}
+
+fn get_status_name(status: u8) -> &'static str {
+    match status {
+        0x80..=0xEF => match status & 0xF0 {
+            0x80 => "Note Off",
+            0x90 => "Note On",
+            0xA0 => "Polyphonic Key Pressure",
+            0xB0 => "Control/Mode Change",
+            0xC0 => "Program Change",
+            0xD0 => "Channel Pressure",
+            _ => "Pitch Bend Change",
+        },
+        0xF0 => "System Exclusive",
+        0xF1 => "MIDI Time Code Quarter Frame",
+        0xF2 => "Song Position Pointer",
+        0xF3 => "Song Select",
+        0xF6 => "Tune Request",
+        _ => "MIDI",
+    }
+}
+
+/// Checks that a message has exactly the number of data bytes its status requires.
+fn validate_data_length(
+    status: u8,
+    data_bytes: &[u8],
+    expected: usize,
+) -> Result<(), MessageError> {
+    if data_bytes.len() == expected {
+        Ok(())
+    } else {
+        Err(MessageError::WrongDataLength {
+            status,
+            expected,
+            actual: data_bytes.len(),
+        })
+    }
+}
+
+fn validate_midi_data_byte(value: u8) -> Result<u8, MessageError> {
     if value <= 127 {
         Ok(value)
     } else {
-        Err(format!(
-            "Invalid MIDI data byte: {}. Must be between 0 and 127.",
-            value
-        ))
+        Err(MessageError::InvalidDataByte { value })
     }
 }
 
 impl TryFrom<&[u8]> for MidiMessage {
-    type Error = String;
+    type Error = MessageError;
 
     fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
         if value.is_empty() {
-            return Err("MIDI message cannot be empty".to_string());
+            return Err(MessageError::Empty);
         }
 
         let status_byte = value[0];
@@ -192,11 +316,7 @@ impl TryFrom<&[u8]> for MidiMessage {
                     message: match status_byte & 0xF0 {
                         // Note Off
                         0x80 => {
-                            if data_bytes.len() != 2 {
-                                return Err(
-                                    "Note Off message requires exactly 2 data bytes".to_string()
-                                );
-                            }
+                            validate_data_length(status_byte, data_bytes, 2)?;
                             ChannelMessage::Voice(ChannelVoiceMessage::NoteOff {
                                 note: validate_midi_data_byte(data_bytes[0])?,
                                 velocity: validate_midi_data_byte(data_bytes[1])?,
@@ -204,11 +324,7 @@ impl TryFrom<&[u8]> for MidiMessage {
                         }
                         // Note On
                         0x90 => {
-                            if data_bytes.len() != 2 {
-                                return Err(
-                                    "Note On message requires exactly 2 data bytes".to_string()
-                                );
-                            }
+                            validate_data_length(status_byte, data_bytes, 2)?;
                             ChannelMessage::Voice(ChannelVoiceMessage::NoteOn {
                                 note: validate_midi_data_byte(data_bytes[0])?,
                                 velocity: validate_midi_data_byte(data_bytes[1])?,
@@ -216,12 +332,7 @@ impl TryFrom<&[u8]> for MidiMessage {
                         }
                         // Polyphonic Key Pressure
                         0xA0 => {
-                            if data_bytes.len() != 2 {
-                                return Err(
-                                    "Polyphonic Key Pressure message requires exactly 2 data bytes"
-                                        .to_string(),
-                                );
-                            }
+                            validate_data_length(status_byte, data_bytes, 2)?;
                             ChannelMessage::Voice(ChannelVoiceMessage::PolyphonicKeyPressure {
                                 note: validate_midi_data_byte(data_bytes[0])?,
                                 pressure: validate_midi_data_byte(data_bytes[1])?,
@@ -229,12 +340,7 @@ impl TryFrom<&[u8]> for MidiMessage {
                         }
                         // Control Change OR Mode Change
                         0xB0 => {
-                            if data_bytes.len() != 2 {
-                                return Err(
-                                    "Control/Mode Change message requires exactly 2 data bytes"
-                                        .to_string(),
-                                );
-                            }
+                            validate_data_length(status_byte, data_bytes, 2)?;
                             let data_byte_1 = validate_midi_data_byte(data_bytes[0])?;
                             let data_byte_2 = validate_midi_data_byte(data_bytes[1])?;
 
@@ -247,10 +353,9 @@ impl TryFrom<&[u8]> for MidiMessage {
                                         0x00 => ChannelModeMessage::LocalControlOff,
                                         0x7F => ChannelModeMessage::LocalControlOn,
                                         _ => {
-                                            return Err(format!(
-                                                "Invalid Local Control value: {}; must be 0 or 127",
-                                                data_byte_2
-                                            ));
+                                            return Err(MessageError::InvalidLocalControl {
+                                                value: data_byte_2,
+                                            });
                                         }
                                     },
                                     0x03 => ChannelModeMessage::AllNotesOff,
@@ -271,34 +376,21 @@ impl TryFrom<&[u8]> for MidiMessage {
                         }
                         // Program Change
                         0xC0 => {
-                            if data_bytes.len() != 1 {
-                                return Err("Program Change message requires exactly 1 data byte"
-                                    .to_string());
-                            }
+                            validate_data_length(status_byte, data_bytes, 1)?;
                             ChannelMessage::Voice(ChannelVoiceMessage::ProgramChange(
                                 validate_midi_data_byte(data_bytes[0])?,
                             ))
                         }
                         // Channel Pressure
                         0xD0 => {
-                            if data_bytes.len() != 1 {
-                                return Err(
-                                    "Channel Pressure message requires exactly 1 data byte"
-                                        .to_string(),
-                                );
-                            }
+                            validate_data_length(status_byte, data_bytes, 1)?;
                             ChannelMessage::Voice(ChannelVoiceMessage::ChannelPressure(
                                 validate_midi_data_byte(data_bytes[0])?,
                             ))
                         }
                         // Pitch Bend Change
                         0xE0 => {
-                            if data_bytes.len() != 2 {
-                                return Err(
-                                    "Pitch Bend Change message requires exactly 2 data bytes"
-                                        .to_string(),
-                                );
-                            }
+                            validate_data_length(status_byte, data_bytes, 2)?;
                             let lsb = validate_midi_data_byte(data_bytes[0])?;
                             let msb = validate_midi_data_byte(data_bytes[1])?;
                             let value = ((msb as u16) << 7) | (lsb as u16);
@@ -311,7 +403,11 @@ impl TryFrom<&[u8]> for MidiMessage {
             // System Exclusive Message
             0xF0 => {
                 if data_bytes.is_empty() {
-                    return Err("System Exclusive Message cannot be empty".to_string());
+                    return Err(MessageError::MissingDataBytes {
+                        status: status_byte,
+                        minimum: 1,
+                        actual: 0,
+                    });
                 }
 
                 let sub_id = validate_midi_data_byte(data_bytes[0])?;
@@ -325,10 +421,11 @@ impl TryFrom<&[u8]> for MidiMessage {
                         let mut manufacturer_id = data_bytes[0..1].to_vec();
                         if manufacturer_id[0] == 0x00 {
                             if data_bytes.len() < 3 {
-                                return Err(
-                                    "Manufacturer Identification System Exclusive message requires at least 3 data bytes"
-                                        .to_string(),
-                                );
+                                return Err(MessageError::MissingDataBytes {
+                                    status: status_byte,
+                                    minimum: 3,
+                                    actual: data_bytes.len(),
+                                });
                             }
                             manufacturer_id.extend_from_slice(&data_bytes[1..3]);
                         }
@@ -358,25 +455,17 @@ impl TryFrom<&[u8]> for MidiMessage {
             }
             // System Common Messages
             0xF1 => {
-                if data_bytes.len() != 1 {
-                    return Err(
-                        "System Common Message: MIDI Time Code Quarter Frame requires exactly 1 data byte"
-                            .to_string(),
-                    );
-                }
-                // TODO: add data byte to the structured message when needed
-                let _data_byte = validate_midi_data_byte(data_bytes[0])?;
+                validate_data_length(status_byte, data_bytes, 1)?;
+                let data_byte = validate_midi_data_byte(data_bytes[0])?;
                 MidiMessage::System(SystemMessage::Common(
-                    SystemCommonMessage::MidiTimeCodeQuarterFrame,
+                    SystemCommonMessage::MidiTimeCodeQuarterFrame {
+                        message_type: data_byte >> 4,
+                        values: data_byte & 0x0F,
+                    },
                 ))
             }
             0xF2 => {
-                if data_bytes.len() != 2 {
-                    return Err(
-                        "System Common Message: Song Position Pointer requires exactly 2 data bytes"
-                            .to_string(),
-                    );
-                }
+                validate_data_length(status_byte, data_bytes, 2)?;
                 let lsb = validate_midi_data_byte(data_bytes[0])?;
                 let msb = validate_midi_data_byte(data_bytes[1])?;
                 let position = ((msb as u16) << 7) | (lsb as u16);
@@ -385,24 +474,14 @@ impl TryFrom<&[u8]> for MidiMessage {
                 ))
             }
             0xF3 => {
-                if data_bytes.len() != 1 {
-                    return Err(
-                        "System Common Message: Song Select requires exactly 1 data byte"
-                            .to_string(),
-                    );
-                }
+                validate_data_length(status_byte, data_bytes, 1)?;
                 let song_number = validate_midi_data_byte(data_bytes[0])?;
                 MidiMessage::System(SystemMessage::Common(SystemCommonMessage::SongSelect(
                     song_number,
                 )))
             }
             0xF6 => {
-                if !data_bytes.is_empty() {
-                    return Err(
-                        "System Common Message: Tune Request does not require any data bytes"
-                            .to_string(),
-                    );
-                }
+                validate_data_length(status_byte, data_bytes, 0)?;
                 MidiMessage::System(SystemMessage::Common(SystemCommonMessage::TuneRequest))
             }
             // Stand-alone End of System Exclusive Message
@@ -425,10 +504,9 @@ impl TryFrom<&[u8]> for MidiMessage {
                 MidiMessage::System(SystemMessage::RealTime(SystemRealTimeMessage::SystemReset))
             }
             _ => {
-                return Err(format!(
-                    "Invalid MIDI status byte: {}. Must be between 0x80 and 0xFF.",
-                    status_byte
-                ));
+                return Err(MessageError::InvalidStatusByte {
+                    status: status_byte,
+                });
             }
         };
 
@@ -532,9 +610,12 @@ impl From<MidiMessage> for Vec<u8> {
             MidiMessage::System(system_msg) => match system_msg {
                 SystemMessage::Common(common_msg) => {
                     match common_msg {
-                        SystemCommonMessage::MidiTimeCodeQuarterFrame => {
-                            // FIXME: not implemented yet
-                            todo!("MIDI Time Code Quarter Frame not implemented yet");
+                        SystemCommonMessage::MidiTimeCodeQuarterFrame {
+                            message_type,
+                            values,
+                        } => {
+                            result.push(0xF1);
+                            result.push(((message_type & 0x07) << 4) | (values & 0x0F));
                         }
                         SystemCommonMessage::SongPositionPointer(position) => {
                             result.push(0xF2);
@@ -588,7 +669,7 @@ impl From<MidiMessage> for Vec<u8> {
 
 impl MidiMessage {
     // Channel Voice Messages
-    pub fn note_off(channel: MidiChannel, note: u8, velocity: u8) -> Result<Self, String> {
+    pub fn note_off(channel: MidiChannel, note: u8, velocity: u8) -> Result<Self, MessageError> {
         Ok(MidiMessage::Channel {
             channel,
             message: ChannelMessage::Voice(ChannelVoiceMessage::NoteOff {
@@ -598,7 +679,7 @@ impl MidiMessage {
         })
     }
 
-    pub fn note_on(channel: MidiChannel, note: u8, velocity: u8) -> Result<Self, String> {
+    pub fn note_on(channel: MidiChannel, note: u8, velocity: u8) -> Result<Self, MessageError> {
         Ok(MidiMessage::Channel {
             channel,
             message: ChannelMessage::Voice(ChannelVoiceMessage::NoteOn {
@@ -621,6 +702,13 @@ impl MidiMessage {
             message: ChannelMessage::Mode(ChannelModeMessage::AllSoundOff),
         }
     }
+
+    pub fn reset_all_controllers(channel: MidiChannel) -> Self {
+        MidiMessage::Channel {
+            channel,
+            message: ChannelMessage::Mode(ChannelModeMessage::ResetAllControllers),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, Clone)]
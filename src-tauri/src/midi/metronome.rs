@@ -0,0 +1,310 @@
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::midi::{
+    library::RecordingGrid,
+    message::{MidiChannel, MidiMessage},
+    playback::{MidiPlayerFn, scheduler::Scheduler},
+};
+
+pub const MIN_BEATS_PER_MINUTE: u16 = 20;
+pub const MAX_BEATS_PER_MINUTE: u16 = 300;
+pub const MAX_COUNT_IN_BARS: u8 = 4;
+/// How long a click note is held.
+const CLICK_DURATION: Duration = Duration::from_millis(30);
+
+/// Tempo, time signature and the sound of the clicks.
+///
+/// The tempo counts beats of the time signature's note value, e.g. eighths in 6/8.
+#[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MetronomeSettings {
+    /// Whether recordings are started with the metronome.
+    pub enabled: bool,
+    pub beats_per_minute: u16,
+    pub beats_per_bar: u8,
+    /// Note value of a beat: 2, 4, 8 or 16.
+    pub beat_unit: u8,
+    pub count_in_bars: u8,
+    pub channel: MidiChannel,
+    pub note: u8,
+    pub velocity: u8,
+    /// Note and velocity of the first beat of every bar.
+    pub accent_note: u8,
+    pub accent_velocity: u8,
+}
+
+impl Default for MetronomeSettings {
+    fn default() -> Self {
+        // General MIDI wood blocks on the percussion channel
+        Self {
+            enabled: false,
+            beats_per_minute: 120,
+            beats_per_bar: 4,
+            beat_unit: 4,
+            count_in_bars: 1,
+            channel: MidiChannel::Channel10,
+            note: 77,
+            velocity: 80,
+            accent_note: 76,
+            accent_velocity: 110,
+        }
+    }
+}
+
+impl MetronomeSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(MIN_BEATS_PER_MINUTE..=MAX_BEATS_PER_MINUTE).contains(&self.beats_per_minute) {
+            return Err(format!(
+                "Tempo must be between {} and {} BPM",
+                MIN_BEATS_PER_MINUTE, MAX_BEATS_PER_MINUTE
+            ));
+        }
+        if !(1..=32).contains(&self.beats_per_bar) {
+            return Err("A bar must have between 1 and 32 beats".to_string());
+        }
+        if ![2, 4, 8, 16].contains(&self.beat_unit) {
+            return Err("Beat unit must be 2, 4, 8 or 16".to_string());
+        }
+        if self.count_in_bars > MAX_COUNT_IN_BARS {
+            return Err(format!(
+                "Count-in must not be longer than {} bars",
+                MAX_COUNT_IN_BARS
+            ));
+        }
+        if [
+            self.note,
+            self.velocity,
+            self.accent_note,
+            self.accent_velocity,
+        ]
+        .iter()
+        .any(|value| *value > 127)
+        {
+            return Err("Click notes and velocities must be between 0 and 127".to_string());
+        }
+
+        Ok(())
+    }
+
+    pub fn get_grid(&self) -> RecordingGrid {
+        let beat_microseconds = 60_000_000 / self.beats_per_minute as u32;
+        RecordingGrid {
+            tempo: beat_microseconds * self.beat_unit as u32 / 4,
+            beats_per_bar: self.beats_per_bar,
+            beat_unit: self.beat_unit,
+        }
+    }
+
+    pub fn get_count_in_duration(&self) -> Duration {
+        self.get_grid().get_beat_duration() * self.beats_per_bar as u32 * self.count_in_bars as u32
+    }
+}
+
+/// Sends click notes to the output on every beat until it is stopped.
+#[derive(Default)]
+pub struct Metronome {
+    settings: MetronomeSettings,
+    player: Option<MidiPlayerFn>,
+    stop_signal: Arc<AtomicBool>,
+    clicker: Option<JoinHandle<()>>,
+}
+
+impl Metronome {
+    pub fn get_settings(&self) -> &MetronomeSettings {
+        &self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: MetronomeSettings) -> Result<(), String> {
+        if self.is_running() {
+            return Err("Cannot change the metronome while it is running".to_string());
+        }
+
+        settings.validate()?;
+        self.settings = settings;
+
+        Ok(())
+    }
+
+    pub fn set_player<F>(&mut self, player: F)
+    where
+        F: Fn(&[u8]) -> Result<(), String> + Sync + Send + 'static,
+    {
+        self.player = Some(Arc::new(player));
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.clicker.is_some()
+    }
+
+    /// Starts clicking with the first beat at the given instant, which is also the first beat
+    /// of a bar.
+    pub fn start(&mut self, first_beat: Instant) -> Result<(), String> {
+        if self.is_running() {
+            return Err("Metronome is already running".to_string());
+        }
+
+        let player = self
+            .player
+            .clone()
+            .ok_or_else(|| "No MIDI player set".to_string())?;
+        let note_on = |note, velocity| {
+            MidiMessage::note_on(self.settings.channel.clone(), note, velocity)
+                .map_err(|e| e.to_string())
+        };
+        let note_off = |note| {
+            MidiMessage::note_off(self.settings.channel.clone(), note, 0).map_err(|e| e.to_string())
+        };
+        let click = note_on(self.settings.note, self.settings.velocity)?;
+        let accent = note_on(self.settings.accent_note, self.settings.accent_velocity)?;
+        let release = [
+            note_off(self.settings.note)?,
+            note_off(self.settings.accent_note)?,
+        ];
+        let beat_duration = self.settings.get_grid().get_beat_duration();
+        let beats_per_bar = self.settings.beats_per_bar as u32;
+
+        self.stop_signal = Arc::new(AtomicBool::new(false));
+        let stop_signal = self.stop_signal.clone();
+        let clicker = thread::spawn(move || {
+            Scheduler::raise_priority();
+            // clicks are not part of the playback, so their timing is not counted
+            let scheduler = Scheduler::new(Arc::new(Mutex::new(Default::default())));
+            let send = |message: &MidiMessage| {
+                let data: Vec<u8> = message.clone().into();
+                if let Err(e) = player(&data) {
+                    eprintln!("Failed to send metronome click: {}", e);
+                }
+            };
+
+            let mut beat: u32 = 0;
+            while !stop_signal.load(Ordering::Relaxed) {
+                let deadline = first_beat + beat_duration * beat;
+                if !scheduler.wait_until(deadline) {
+                    continue;
+                }
+
+                let is_first_beat_of_bar = beat.is_multiple_of(beats_per_bar);
+                send(if is_first_beat_of_bar {
+                    &accent
+                } else {
+                    &click
+                });
+                thread::sleep(CLICK_DURATION.min(beat_duration / 2));
+                release.iter().for_each(send);
+                beat += 1;
+            }
+        });
+        self.clicker = Some(clicker);
+
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        self.stop_signal.store(true, Ordering::Relaxed);
+        if let Some(clicker) = self.clicker.take()
+            && clicker.join().is_err()
+        {
+            eprintln!("Metronome thread panicked");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(beats_per_minute: u16, beats_per_bar: u8, beat_unit: u8) -> MetronomeSettings {
+        MetronomeSettings {
+            beats_per_minute,
+            beats_per_bar,
+            beat_unit,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn accepts_common_and_odd_meters() {
+        assert_eq!(MetronomeSettings::default().validate(), Ok(()));
+        assert_eq!(settings(90, 6, 8).validate(), Ok(()));
+        assert_eq!(settings(140, 7, 4).validate(), Ok(()));
+        assert_eq!(settings(MIN_BEATS_PER_MINUTE, 1, 2).validate(), Ok(()));
+        assert_eq!(settings(MAX_BEATS_PER_MINUTE, 32, 16).validate(), Ok(()));
+    }
+
+    #[test]
+    fn rejects_settings_out_of_range() {
+        assert!(settings(MIN_BEATS_PER_MINUTE - 1, 4, 4).validate().is_err());
+        assert!(settings(MAX_BEATS_PER_MINUTE + 1, 4, 4).validate().is_err());
+        assert!(settings(120, 0, 4).validate().is_err());
+        assert!(settings(120, 33, 4).validate().is_err());
+        assert!(settings(120, 4, 3).validate().is_err());
+        assert!(settings(120, 4, 32).validate().is_err());
+
+        let too_long_count_in = MetronomeSettings {
+            count_in_bars: MAX_COUNT_IN_BARS + 1,
+            ..Default::default()
+        };
+        assert!(too_long_count_in.validate().is_err());
+
+        let invalid_velocity = MetronomeSettings {
+            accent_velocity: 128,
+            ..Default::default()
+        };
+        assert!(invalid_velocity.validate().is_err());
+    }
+
+    #[test]
+    fn grid_tempo_counts_quarter_notes() {
+        assert_eq!(
+            settings(120, 4, 4).get_grid(),
+            RecordingGrid {
+                tempo: 500_000,
+                beats_per_bar: 4,
+                beat_unit: 4,
+            }
+        );
+        // 120 eighths per minute are 60 quarters
+        assert_eq!(
+            settings(120, 6, 8).get_grid(),
+            RecordingGrid {
+                tempo: 1_000_000,
+                beats_per_bar: 6,
+                beat_unit: 8,
+            }
+        );
+        assert_eq!(
+            settings(140, 7, 4).get_grid(),
+            RecordingGrid {
+                tempo: 428_571,
+                beats_per_bar: 7,
+                beat_unit: 4,
+            }
+        );
+    }
+
+    #[test]
+    fn count_in_lasts_whole_bars() {
+        let mut six_eight = settings(120, 6, 8);
+        six_eight.count_in_bars = 2;
+        assert_eq!(six_eight.get_count_in_duration(), Duration::from_secs(6));
+
+        six_eight.count_in_bars = 0;
+        assert_eq!(six_eight.get_count_in_duration(), Duration::ZERO);
+
+        let seven_four = MetronomeSettings {
+            count_in_bars: 1,
+            ..settings(140, 7, 4)
+        };
+        assert_eq!(
+            seven_four.get_count_in_duration(),
+            Duration::from_micros(7 * 428_571)
+        );
+    }
+}
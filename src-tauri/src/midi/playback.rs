@@ -9,16 +9,35 @@ use std::{
     time::{Duration, Instant},
 };
 
-use tauri::async_runtime::JoinHandle;
+use tauri::{async_runtime::JoinHandle, ipc::Channel};
 
 use crate::midi::{
     message::{MidiChannel, MidiMessage, TimeStampedMidiMessage},
-    smf::{Event, MidiFile, calc_delta_time_microseconds},
+    playback::{
+        chase::ChaseState,
+        mute::{MuteFilter, MuteState},
+        output::PlaybackOutput,
+        scheduler::{JitterStats, MAX_SLEEP_DURATION, Scheduler},
+        transform::PlaybackTransform,
+    },
+    rmid::RmidInfo,
+    smf::{Event, MetaEvent, MidiFile, ParseWarning},
 };
 
-type MidiPlayerFn = Arc<dyn Fn(&[u8]) -> Result<(), String> + Sync + Send + 'static>;
+mod chase;
+pub mod mute;
+mod output;
+pub mod scheduler;
+pub mod transform;
+
+pub type MidiPlayerFn = Arc<dyn Fn(&[u8]) -> Result<(), String> + Sync + Send + 'static>;
+type PlaybackEndFn = Arc<dyn Fn() + Sync + Send + 'static>;
 
-const MAX_SLEEP_DURATION: Duration = Duration::from_millis(50);
+/// How often the playback thread reports the position to the frontend.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(50);
+
+pub const MIN_PLAYBACK_RATE: f64 = 0.25;
+pub const MAX_PLAYBACK_RATE: f64 = 2.0;
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Track(Vec<(u64, Vec<u8>)>);
@@ -63,10 +82,182 @@ impl<'a> IntoIterator for &'a mut Track {
     }
 }
 
+impl Track {
+    fn from_absolute_times(events: &[(u64, Vec<u8>)]) -> Track {
+        let mut previous_microseconds = 0;
+        Track(
+            events
+                .iter()
+                .map(|(microseconds, data)| {
+                    let delta = microseconds - previous_microseconds;
+                    previous_microseconds = *microseconds;
+                    (delta, data.clone())
+                })
+                .collect(),
+        )
+    }
+
+    pub fn get_duration(&self) -> Duration {
+        Duration::from_micros(self.iter().map(|(delta, _)| delta).sum::<u64>())
+    }
+
+    fn to_absolute_times(&self) -> Vec<(u64, Vec<u8>)> {
+        let mut microseconds = 0;
+        self.iter()
+            .map(|(delta, data)| {
+                microseconds += delta;
+                (microseconds, data.clone())
+            })
+            .collect()
+    }
+}
+
+/// A Standard MIDI File prepared for playback.
+///
+/// Besides the individual tracks, the file keeps a merged, time-ordered stream of
+/// all tracks so the whole piece can be played at once, along with the index of
+/// the track every merged event originates from.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct LoadedMidiFile {
+    path: String,
+    track_names: Vec<Option<String>>,
+    tracks: Vec<Track>,
+    merged_track: Track,
+    merged_track_indices: Vec<usize>,
+    info: RmidInfo,
+    /// Problems in the file that were worked around when parsing it.
+    warnings: Vec<ParseWarning>,
+}
+
+impl LoadedMidiFile {
+    pub fn new(file: &MidiFile, path: String) -> Result<LoadedMidiFile, String> {
+        if file.get_tracks().is_empty() {
+            return Err("Cannot load empty MIDI file".to_string());
+        }
+
+        let to_event = |message: &TimeStampedMidiMessage| -> (u64, Vec<u8>) {
+            (
+                message.timestamp_microseconds,
+                message.message.clone().into(),
+            )
+        };
+
+        let track_names = file
+            .get_tracks()
+            .iter()
+            .map(|track| {
+                track.iter().find_map(|event| match &event.event {
+                    Event::MetaEvent(
+                        MetaEvent::SequenceName(name) | MetaEvent::TrackName(name),
+                    ) => Some(name.clone()),
+                    _ => None,
+                })
+            })
+            .collect();
+
+        // a single track is played from its own start, also in a format 2 file
+        let track_messages = file.to_track_messages();
+        let tracks = track_messages
+            .iter()
+            .map(|(_, messages)| {
+                Track::from_absolute_times(&messages.iter().map(to_event).collect::<Vec<_>>())
+            })
+            .collect();
+
+        let (merged_events, merged_track_indices): (Vec<_>, Vec<_>) =
+            MidiFile::merge_track_messages(&track_messages)
+                .iter()
+                .map(|(index, message)| (to_event(message), *index))
+                .unzip();
+
+        Ok(LoadedMidiFile {
+            path,
+            track_names,
+            tracks,
+            merged_track: Track::from_absolute_times(&merged_events),
+            merged_track_indices,
+            info: file.get_info().clone(),
+            warnings: Vec::new(),
+        })
+    }
+
+    pub fn get_path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn get_track_names(&self) -> &[Option<String>] {
+        &self.track_names
+    }
+
+    pub fn get_tracks(&self) -> &[Track] {
+        &self.tracks
+    }
+
+    pub fn get_merged_track(&self) -> &Track {
+        &self.merged_track
+    }
+
+    pub fn get_info(&self) -> &RmidInfo {
+        &self.info
+    }
+
+    pub fn get_warnings(&self) -> &[ParseWarning] {
+        &self.warnings
+    }
+
+    pub fn get_merged_track_indices(&self) -> &[usize] {
+        &self.merged_track_indices
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum TrackInfo {
     Recording(usize),
-    StandardMidiFile(usize),
+    /// A track of the loaded MIDI file, or all tracks merged if no index is given.
+    StandardMidiFile(Option<usize>),
+}
+
+/// A section of the playback that is repeated until the loop is cleared.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct LoopRegion {
+    pub start: Duration,
+    pub end: Duration,
+}
+
+/// Pushed to the frontend, so it does not need to poll the playback state.
+///
+/// Only progress events carry the position; on every other event the frontend
+/// is expected to fetch the whole playback state.
+#[derive(Debug, PartialEq, Eq, Clone, serde::Serialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum PlaybackEvent {
+    Started,
+    Progress {
+        position_milliseconds: u32,
+    },
+    Paused,
+    Resumed,
+    /// Playback was stopped before reaching the end.
+    Stopped,
+    Finished,
+    /// Playback ended because a message could not be sent.
+    Error {
+        message: String,
+    },
+}
+
+type PlaybackChannel = Arc<Mutex<Option<Channel<PlaybackEvent>>>>;
+
+fn emit(channel: &PlaybackChannel, event: PlaybackEvent) {
+    if let Some(channel) = channel.lock().unwrap().as_ref()
+        && let Err(error) = channel.send(event)
+    {
+        eprintln!("Failed to send playback event: {error}");
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -79,13 +270,21 @@ pub enum PlaybackState {
 pub struct MidiPlaybackInner {
     state: PlaybackState,
     player: Option<MidiPlayerFn>,
-    tracks: Vec<Track>,
+    end_handler: Option<PlaybackEndFn>,
+    file: Option<LoadedMidiFile>,
     position_milliseconds: Arc<AtomicUsize>,
     duration_milliseconds: Option<Arc<AtomicUsize>>,
+    loop_region: Arc<Mutex<Option<LoopRegion>>>,
+    rate: Arc<Mutex<f64>>,
+    transform: Arc<Mutex<PlaybackTransform>>,
+    mute_state: Arc<Mutex<MuteState>>,
+    jitter_stats: Arc<Mutex<JitterStats>>,
+    frontend_channel: PlaybackChannel,
     // Thread management
     thread_handle: Option<JoinHandle<()>>,
     signal_stop: Option<Arc<AtomicBool>>,
     signal_pause: Option<Arc<AtomicBool>>,
+    signal_seek: Option<Arc<Mutex<Option<Duration>>>>,
 }
 
 pub struct MidiPlayback {
@@ -98,12 +297,20 @@ impl Default for MidiPlayback {
             inner: Arc::new(Mutex::new(MidiPlaybackInner {
                 state: PlaybackState::Stopped,
                 player: None,
-                tracks: Vec::new(),
+                end_handler: None,
+                file: None,
                 position_milliseconds: Arc::new(AtomicUsize::new(0)),
                 duration_milliseconds: None,
+                loop_region: Arc::new(Mutex::new(None)),
+                rate: Arc::new(Mutex::new(1.0)),
+                transform: Arc::new(Mutex::new(PlaybackTransform::default())),
+                mute_state: Arc::new(Mutex::new(MuteState::default())),
+                jitter_stats: Arc::new(Mutex::new(JitterStats::default())),
+                frontend_channel: Arc::new(Mutex::new(None)),
                 thread_handle: None,
                 signal_stop: None,
                 signal_pause: None,
+                signal_seek: None,
             })),
         }
     }
@@ -128,9 +335,128 @@ impl MidiPlayback {
         Duration::from_millis(inner.position_milliseconds.load(Ordering::SeqCst) as u64)
     }
 
-    pub fn get_tracks(&self) -> Vec<Track> {
+    pub fn get_loop(&self) -> Option<LoopRegion> {
+        let inner = self.inner.lock().unwrap();
+        inner.loop_region.lock().unwrap().clone()
+    }
+
+    /// Sets or clears the loop region, which also takes effect while playing.
+    pub fn set_loop(&mut self, loop_region: Option<LoopRegion>) -> Result<(), String> {
+        if let Some(ref region) = loop_region
+            && region.start >= region.end
+        {
+            return Err("Loop start must be before loop end".to_string());
+        }
+
+        let inner = self.inner.lock().unwrap();
+        *inner.loop_region.lock().unwrap() = loop_region;
+
+        Ok(())
+    }
+
+    pub fn get_rate(&self) -> f64 {
+        let inner = self.inner.lock().unwrap();
+        *inner.rate.lock().unwrap()
+    }
+
+    /// Sets the playback speed factor (1.0 is the original tempo), which also takes effect while playing.
+    pub fn set_rate(&mut self, rate: f64) -> Result<(), String> {
+        if !(MIN_PLAYBACK_RATE..=MAX_PLAYBACK_RATE).contains(&rate) {
+            return Err(format!(
+                "Playback rate must be between {} and {}",
+                MIN_PLAYBACK_RATE, MAX_PLAYBACK_RATE
+            ));
+        }
+
+        let inner = self.inner.lock().unwrap();
+        *inner.rate.lock().unwrap() = rate;
+
+        Ok(())
+    }
+
+    pub fn get_transform(&self) -> PlaybackTransform {
+        let inner = self.inner.lock().unwrap();
+        inner.transform.lock().unwrap().clone()
+    }
+
+    /// Sets transpose, channel remapping and velocity scaling, which also take effect while playing.
+    ///
+    /// Notes that are already sounding are released as they were started, so changing
+    /// the transform never leaves notes hanging.
+    pub fn set_transform(&mut self, transform: PlaybackTransform) -> Result<(), String> {
+        transform.validate()?;
+
+        let inner = self.inner.lock().unwrap();
+        *inner.transform.lock().unwrap() = transform;
+
+        Ok(())
+    }
+
+    pub fn get_mute_state(&self) -> MuteState {
+        let inner = self.inner.lock().unwrap();
+        inner.mute_state.lock().unwrap().clone()
+    }
+
+    /// Mutes or unmutes a channel, which also takes effect while playing.
+    ///
+    /// Notes held on a channel or track that becomes inaudible are released right away.
+    pub fn set_channel_muted(&mut self, channel: MidiChannel, muted: bool) {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .mute_state
+            .lock()
+            .unwrap()
+            .set_channel_muted(channel, muted);
+    }
+
+    pub fn set_channel_soloed(&mut self, channel: MidiChannel, soloed: bool) {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .mute_state
+            .lock()
+            .unwrap()
+            .set_channel_soloed(channel, soloed);
+    }
+
+    /// Mutes or unmutes a track of the loaded file, which also takes effect while playing.
+    pub fn set_track_muted(&mut self, index: usize, muted: bool) -> Result<(), String> {
+        let inner = self.inner.lock().unwrap();
+        Self::check_track_index(&inner, index)?;
+        inner
+            .mute_state
+            .lock()
+            .unwrap()
+            .set_track_muted(index, muted);
+
+        Ok(())
+    }
+
+    pub fn set_track_soloed(&mut self, index: usize, soloed: bool) -> Result<(), String> {
+        let inner = self.inner.lock().unwrap();
+        Self::check_track_index(&inner, index)?;
+        inner
+            .mute_state
+            .lock()
+            .unwrap()
+            .set_track_soloed(index, soloed);
+
+        Ok(())
+    }
+
+    /// Timing accuracy of the current (or last) playback, for diagnostics.
+    pub fn get_jitter_stats(&self) -> JitterStats {
+        let inner = self.inner.lock().unwrap();
+        inner.jitter_stats.lock().unwrap().clone()
+    }
+
+    pub fn set_frontend_channel(&mut self, channel: Channel<PlaybackEvent>) {
+        let inner = self.inner.lock().unwrap();
+        *inner.frontend_channel.lock().unwrap() = Some(channel);
+    }
+
+    pub fn get_file(&self) -> Option<LoadedMidiFile> {
         let inner = self.inner.lock().unwrap();
-        inner.tracks.clone()
+        inner.file.clone()
     }
 
     pub fn set_player<F>(&mut self, player: F) -> Result<(), String>
@@ -148,63 +474,91 @@ impl MidiPlayback {
         Ok(())
     }
 
+    /// Sets what runs whenever the playback thread ends, however it was stopped.
+    pub fn set_end_handler<F>(&mut self, handler: F)
+    where
+        F: Fn() + Sync + Send + 'static,
+    {
+        let mut inner = self.inner.lock().unwrap();
+        inner.end_handler = Some(Arc::new(handler));
+    }
+
     pub async fn play(
         &mut self,
         data: &Vec<TimeStampedMidiMessage>,
         track_info: TrackInfo,
+    ) -> Result<(), String> {
+        self.play_at(data, track_info, Instant::now()).await
+    }
+
+    /// Plays the data with its start at the given instant, e.g. to share it with a recording.
+    pub async fn play_at(
+        &mut self,
+        data: &Vec<TimeStampedMidiMessage>,
+        track_info: TrackInfo,
+        start: Instant,
     ) -> Result<(), String> {
         let buffer = self.load_timestamped_data(data)?;
-        self._play(buffer, track_info).await
+        let track_indices = vec![None; buffer.len()];
+        self._play(buffer, track_indices, track_info, start).await
     }
 
-    pub fn load_track(&mut self, file: MidiFile) -> Result<(), String> {
+    pub fn load_file(
+        &mut self,
+        file: MidiFile,
+        path: String,
+        warnings: Vec<ParseWarning>,
+    ) -> Result<(), String> {
+        let mut loaded_file = LoadedMidiFile::new(&file, path)?;
+        loaded_file.warnings = warnings;
         let mut inner = self.inner.lock().unwrap();
-
-        if file.get_tracks().is_empty() {
-            return Err("Cannot load empty MIDI file".to_string());
-        }
-
-        for track in file.get_tracks() {
-            inner.tracks.push(Track(
-                track
-                    .iter()
-                    .filter_map(|msg| match msg.event {
-                        Event::MidiEvent(ref midi_message) => {
-                            // FIXME: handle tempo changes and other meta events
-                            Some((
-                                calc_delta_time_microseconds(
-                                    msg.delta_time,
-                                    500_000,
-                                    file.get_header().get_division(),
-                                ),
-                                midi_message.clone().into(),
-                            ))
-                        }
-                        _ => None,
-                    })
-                    .collect(),
-            ));
-        }
+        inner.file = Some(loaded_file);
+        // track settings belong to the previous file
+        inner.mute_state.lock().unwrap().clear_tracks();
 
         Ok(())
     }
 
-    pub async fn eject_track(&mut self, index: usize) -> Result<(), String> {
+    pub async fn eject_file(&mut self) -> Result<(), String> {
+        let is_playing_file = matches!(
+            self.get_state(),
+            PlaybackState::Playing(TrackInfo::StandardMidiFile(_))
+                | PlaybackState::Paused(TrackInfo::StandardMidiFile(_))
+        );
+        if is_playing_file {
+            self.stop().await?;
+        }
+
         let mut inner = self.inner.lock().unwrap();
-        if index < inner.tracks.len() {
-            inner.tracks.remove(index);
-            // FIXME: reset state?
-            Ok(())
-        } else {
-            Err("Track index out of bounds".to_string())
+        inner.mute_state.lock().unwrap().clear_tracks();
+        match inner.file.take() {
+            Some(_) => Ok(()),
+            None => Err("No MIDI file loaded".to_string()),
         }
     }
 
-    pub async fn play_track(&mut self, index: usize) -> Result<(), String> {
-        let buffer = self._load_track(index)?;
-        self._play(buffer, TrackInfo::StandardMidiFile(index)).await
+    /// Plays a single track of the loaded file, or all tracks merged if `index` is `None`.
+    pub async fn play_file(&mut self, index: Option<usize>) -> Result<(), String> {
+        self.play_file_at(index, Instant::now()).await
+    }
+
+    /// Plays the loaded file with its start at the given instant.
+    pub async fn play_file_at(
+        &mut self,
+        index: Option<usize>,
+        start: Instant,
+    ) -> Result<(), String> {
+        let (buffer, track_indices) = self._load_file_track(index)?;
+        self._play(
+            buffer,
+            track_indices,
+            TrackInfo::StandardMidiFile(index),
+            start,
+        )
+        .await
     }
 
+    /// Pauses playback; the playback thread releases sounding notes and the sustain pedal.
     pub fn pause(&mut self) -> Result<(), String> {
         let mut inner = self.inner.lock().unwrap();
 
@@ -216,10 +570,12 @@ impl MidiPlayback {
         if let Some(signal) = &inner.signal_pause {
             signal.store(true, Ordering::SeqCst);
         }
+        emit(&inner.frontend_channel, PlaybackEvent::Paused);
 
         Ok(())
     }
 
+    /// Resumes playback; notes that are held at the paused position are struck again.
     pub fn resume(&mut self) -> Result<(), String> {
         let mut inner = self.inner.lock().unwrap();
 
@@ -231,6 +587,44 @@ impl MidiPlayback {
         if let Some(signal) = &inner.signal_pause {
             signal.store(false, Ordering::SeqCst);
         }
+        emit(&inner.frontend_channel, PlaybackEvent::Resumed);
+
+        Ok(())
+    }
+
+    /// Jumps to the given position while playing or paused.
+    ///
+    /// The playback thread silences sounding notes and chases program changes,
+    /// controller values and pitch bend up to the new position.
+    pub fn seek(&mut self, position: Duration) -> Result<(), String> {
+        let inner = self.inner.lock().unwrap();
+
+        if !matches!(
+            inner.state,
+            PlaybackState::Playing(_) | PlaybackState::Paused(_)
+        ) {
+            return Err("Playback is not active, cannot seek".to_string());
+        }
+
+        let duration_milliseconds = inner
+            .duration_milliseconds
+            .as_ref()
+            .map(|duration| duration.load(Ordering::SeqCst))
+            .unwrap_or_default();
+        let position = position.min(Duration::from_millis(duration_milliseconds as u64));
+
+        if let Some(signal) = &inner.signal_seek {
+            *signal.lock().unwrap() = Some(position);
+        }
+        inner
+            .position_milliseconds
+            .store(position.as_millis() as usize, Ordering::SeqCst);
+        emit(
+            &inner.frontend_channel,
+            PlaybackEvent::Progress {
+                position_milliseconds: position.as_millis() as u32,
+            },
+        );
 
         Ok(())
     }
@@ -260,17 +654,66 @@ impl MidiPlayback {
         Ok(())
     }
 
-    fn _load_track(&mut self, index: usize) -> Result<Track, String> {
-        let mut inner = self.inner.lock().unwrap();
-        if index < inner.tracks.len() {
-            let track = inner.tracks[index].clone();
-            inner.duration_milliseconds = Some(Arc::new(AtomicUsize::new(
-                (track.iter().map(|(delta, _)| delta).sum::<u64>() / 1000) as usize,
-            )));
-            Ok(track)
-        } else {
-            Err("Track index out of bounds".to_string())
+    /// Stops playback and resets all 16 channels of the MIDI output,
+    /// including the sustain pedal, to get rid of any stuck notes.
+    pub async fn panic(&mut self) -> Result<(), String> {
+        self.stop().await?;
+
+        let player = {
+            let inner = self.inner.lock().unwrap();
+            inner
+                .player
+                .clone()
+                .ok_or("No MIDI player set".to_string())?
+        };
+
+        PlaybackOutput::new(player).panic()
+    }
+
+    fn check_track_index(inner: &MidiPlaybackInner, index: usize) -> Result<(), String> {
+        let file = inner
+            .file
+            .as_ref()
+            .ok_or("No MIDI file loaded".to_string())?;
+        if index >= file.get_tracks().len() {
+            return Err("Track index out of bounds".to_string());
         }
+
+        Ok(())
+    }
+
+    fn _load_file_track(
+        &mut self,
+        index: Option<usize>,
+    ) -> Result<(Track, Vec<Option<usize>>), String> {
+        let mut inner = self.inner.lock().unwrap();
+        let file = inner
+            .file
+            .as_ref()
+            .ok_or("No MIDI file loaded".to_string())?;
+        let (track, track_indices) = match index {
+            Some(index) => {
+                let track = file
+                    .get_tracks()
+                    .get(index)
+                    .ok_or("Track index out of bounds".to_string())?
+                    .clone();
+                let track_indices = vec![Some(index); track.len()];
+                (track, track_indices)
+            }
+            None => (
+                file.get_merged_track().clone(),
+                file.get_merged_track_indices()
+                    .iter()
+                    .map(|index| Some(*index))
+                    .collect(),
+            ),
+        };
+
+        inner.duration_milliseconds = Some(Arc::new(AtomicUsize::new(
+            track.get_duration().as_millis() as usize,
+        )));
+        Ok((track, track_indices))
     }
 
     fn load_timestamped_data(
@@ -319,91 +762,351 @@ impl MidiPlayback {
         Ok(Track(normalized_data))
     }
 
-    async fn _play(&mut self, buffer: Track, track_info: TrackInfo) -> Result<(), String> {
+    /// Starts the playback thread; `track_indices` holds the source track of every event.
+    ///
+    /// The start may lie in the future, the thread then waits for it.
+    async fn _play(
+        &mut self,
+        buffer: Track,
+        track_indices: Vec<Option<usize>>,
+        track_info: TrackInfo,
+        start: Instant,
+    ) -> Result<(), String> {
         self.stop().await?;
 
         let signal_stop = Arc::new(AtomicBool::new(false));
         let signal_pause = Arc::new(AtomicBool::new(false));
+        let signal_seek = Arc::new(Mutex::new(None));
 
         let mut inner = self.inner.lock().unwrap();
-        inner.state = PlaybackState::Playing(track_info);
-        inner.position_milliseconds.store(0, Ordering::SeqCst);
-        inner.signal_stop = Some(signal_stop.clone());
-        inner.signal_pause = Some(signal_pause.clone());
-
-        let position = inner.position_milliseconds.clone();
         let player = if let Some(player) = inner.player.as_ref() {
             player.clone()
         } else {
             return Err("No MIDI player set".to_string());
         };
-        // FIXME: set priority to high for real-time playback
-        // use accurate sleeping (e.g. https://crates.io/crates/spin_sleep)
+
+        inner.state = PlaybackState::Playing(track_info);
+        inner.position_milliseconds.store(0, Ordering::SeqCst);
+        inner.signal_stop = Some(signal_stop.clone());
+        inner.signal_pause = Some(signal_pause.clone());
+        inner.signal_seek = Some(signal_seek.clone());
+
+        let position_milliseconds = inner.position_milliseconds.clone();
+        let loop_region = inner.loop_region.clone();
+        let rate = inner.rate.clone();
+        let transform = inner.transform.clone();
+        let mute_state = inner.mute_state.clone();
+        *inner.jitter_stats.lock().unwrap() = JitterStats::default();
+        let scheduler = Scheduler::new(inner.jitter_stats.clone());
+        let frontend_channel = inner.frontend_channel.clone();
+        emit(&frontend_channel, PlaybackEvent::Started);
         let playback_thread = thread::spawn(move || {
-            let play_stop = || {
-                // FIXME: Make channel configurable
-                let msg_all_notes_off: Vec<u8> =
-                    MidiMessage::all_notes_off(MidiChannel::Channel1).into();
-                let msg_all_sound_off: Vec<u8> =
-                    MidiMessage::all_sound_off(MidiChannel::Channel1).into();
-                let _ = player(msg_all_notes_off.as_slice()).map_err(|e| e.to_string());
-                let _ = player(&msg_all_sound_off.as_slice()).map_err(|e| e.to_string());
+            Scheduler::raise_priority();
+
+            let events = buffer.to_absolute_times();
+            let chase = |cursor: usize| {
+                ChaseState::from_events(events[..cursor].iter().map(|(_, data)| data.as_slice()))
             };
-            let mut start = Instant::now();
-            let mut time = Duration::ZERO;
-            for msg in buffer.iter() {
-                time += Duration::from_micros(msg.0);
-                let mut elapsed = start.elapsed();
-                position.store(elapsed.as_millis() as usize, Ordering::SeqCst);
-                while elapsed < time {
-                    if signal_pause.load(Ordering::SeqCst) {
-                        let elapsed = start.elapsed();
-                        while signal_pause.load(Ordering::SeqCst) {
-                            if signal_stop.load(Ordering::SeqCst) {
-                                play_stop();
-                                return;
-                            }
-                            thread::sleep(MAX_SLEEP_DURATION);
+            let mut output = PlaybackOutput::new(player);
+            let mut mute_filter = MuteFilter::default();
+
+            let mut cursor = 0;
+            // the position is measured from the last (re)start, seek, resume, loop jump
+            // or rate change, as elapsed wall-clock time scaled by the current rate
+            let mut anchor_position = Duration::ZERO;
+            let mut anchor_instant = start;
+            let mut current_rate = *rate.lock().unwrap();
+            let mut paused_position: Option<Duration> = None;
+            // chase messages for the loop start are only computed once per loop region
+            let mut loop_chase: Option<(usize, Vec<MidiMessage>)> = None;
+            let mut last_progress: Option<Instant> = None;
+            let end_event = loop {
+                if signal_stop.load(Ordering::SeqCst) {
+                    break PlaybackEvent::Stopped;
+                }
+
+                let mut position = paused_position.unwrap_or_else(|| {
+                    anchor_position + anchor_instant.elapsed().mul_f64(current_rate)
+                });
+
+                let new_rate = *rate.lock().unwrap();
+                if new_rate != current_rate {
+                    anchor_position = position;
+                    anchor_instant = Instant::now();
+                    current_rate = new_rate;
+                }
+
+                output.set_transform(transform.lock().unwrap().clone());
+                for note_off in mute_filter.set_state(mute_state.lock().unwrap().clone()) {
+                    output.send_message(note_off);
+                }
+
+                let seek_target = signal_seek.lock().unwrap().take();
+                if let Some(target) = seek_target {
+                    output.silence();
+                    mute_filter.reset();
+
+                    let target_microseconds = target.as_micros() as u64;
+                    cursor = events.partition_point(|(time, _)| *time < target_microseconds);
+                    for message in chase(cursor).get_messages() {
+                        output.send_message(message);
+                    }
+
+                    position = target;
+                    anchor_position = target;
+                    anchor_instant = Instant::now();
+                    if paused_position.is_some() {
+                        paused_position = Some(target);
+                    }
+                }
+
+                if signal_pause.load(Ordering::SeqCst) {
+                    if paused_position.is_none() {
+                        output.silence();
+                        mute_filter.reset();
+                        paused_position = Some(position);
+                    }
+                    thread::sleep(MAX_SLEEP_DURATION);
+                    continue;
+                } else if let Some(resume_position) = paused_position.take() {
+                    // the controllers were reset on pause, and notes that are still held
+                    // at this position are struck again with their original velocity
+                    let chase_state = chase(cursor);
+                    for message in chase_state.get_messages() {
+                        output.send_message(message);
+                    }
+                    for index in chase_state.get_held_notes() {
+                        let data = events[index].1.as_slice();
+                        if mute_filter.filter(data, track_indices[index])
+                            && let Err(error) = output.send(data)
+                        {
+                            eprintln!("{error}");
                         }
-                        start = Instant::now() - elapsed;
                     }
+                    anchor_position = resume_position;
+                    anchor_instant = Instant::now();
+                }
 
-                    if signal_stop.load(Ordering::SeqCst) {
-                        play_stop();
-                        break;
+                let loop_region = loop_region.lock().unwrap().clone();
+                if let Some(ref region) = loop_region
+                    && position >= region.end
+                {
+                    output.release_notes();
+                    mute_filter.reset();
+
+                    let start_microseconds = region.start.as_micros() as u64;
+                    cursor = events.partition_point(|(time, _)| *time < start_microseconds);
+                    let messages = match loop_chase.take() {
+                        Some((chase_cursor, messages)) if chase_cursor == cursor => messages,
+                        _ => chase(cursor).get_messages(),
+                    };
+                    for message in messages.iter() {
+                        output.send_message(message.clone());
                     }
+                    loop_chase = Some((cursor, messages));
 
-                    let duration = time - elapsed;
-                    // sleep no more than 50ms to be able to handle pause/stop signals quickly
-                    let sleep_duration = MAX_SLEEP_DURATION.min(duration);
-                    thread::sleep(sleep_duration);
-                    elapsed = start.elapsed();
+                    // keep the overshoot, so the loop does not drift over many repetitions
+                    let overshoot = position - region.end;
+                    position = region.start + overshoot;
+                    anchor_position = region.start;
+                    anchor_instant = Instant::now() - overshoot.div_f64(current_rate);
                 }
 
-                if let Err(error) = player(msg.1.as_slice()) {
-                    eprintln!("{error}");
-                    break;
+                position_milliseconds.store(position.as_millis() as usize, Ordering::SeqCst);
+                if last_progress.is_none_or(|instant| instant.elapsed() >= PROGRESS_INTERVAL) {
+                    emit(
+                        &frontend_channel,
+                        PlaybackEvent::Progress {
+                            position_milliseconds: position.as_millis() as u32,
+                        },
+                    );
+                    last_progress = Some(Instant::now());
                 }
-            }
+
+                let next_event = events
+                    .get(cursor)
+                    .map(|(time, data)| (Duration::from_micros(*time), data))
+                    .filter(|(time, _)| {
+                        loop_region.as_ref().is_none_or(|region| *time < region.end)
+                    });
+                // when looping, wait for the loop end even if there are no more events in the loop
+                let wake_time = match (&next_event, &loop_region) {
+                    (Some((time, _)), _) => *time,
+                    (None, Some(region)) => region.end,
+                    (None, None) => break PlaybackEvent::Finished,
+                };
+                let deadline = Scheduler::get_deadline(
+                    anchor_instant,
+                    anchor_position,
+                    wake_time,
+                    current_rate,
+                );
+                if !scheduler.wait_until(deadline) {
+                    continue;
+                }
+
+                if let Some((_, data)) = next_event {
+                    if mute_filter.filter(data, track_indices[cursor]) {
+                        if let Err(error) = output.send(data.as_slice()) {
+                            eprintln!("{error}");
+                            break PlaybackEvent::Error { message: error };
+                        }
+                        scheduler.record_lateness(deadline);
+                    }
+                    cursor += 1;
+                }
+            };
+
+            // whether stopped or finished, nothing may keep sounding afterwards
+            output.silence();
+
+            end_event
         });
 
         let inner_clone = self.inner.clone();
         let handle = tauri::async_runtime::spawn_blocking(move || {
-            let result = playback_thread.join();
-
-            if let Err(e) = result {
+            let end_event = playback_thread.join().unwrap_or_else(|e| {
                 eprintln!("{e:?}");
-            }
+                PlaybackEvent::Error {
+                    message: "Playback thread panicked".to_string(),
+                }
+            });
 
-            let mut inner_clone = inner_clone.lock().unwrap();
-            inner_clone.state = PlaybackState::Stopped;
-            inner_clone.position_milliseconds.store(0, Ordering::SeqCst);
-            inner_clone.signal_pause = None;
-            inner_clone.signal_stop = None;
-            inner_clone.thread_handle = None;
+            let (frontend_channel, end_handler) = {
+                let mut inner_clone = inner_clone.lock().unwrap();
+                inner_clone.state = PlaybackState::Stopped;
+                inner_clone.position_milliseconds.store(0, Ordering::SeqCst);
+                inner_clone.signal_pause = None;
+                inner_clone.signal_stop = None;
+                inner_clone.signal_seek = None;
+                inner_clone.thread_handle = None;
+                (
+                    inner_clone.frontend_channel.clone(),
+                    inner_clone.end_handler.clone(),
+                )
+            };
+            if let Some(end_handler) = end_handler {
+                end_handler();
+            }
+            // the state is consistent by now, the frontend may fetch it right away
+            emit(&frontend_channel, end_event);
         });
         inner.thread_handle = Some(handle);
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIDI_FORMAT_2_EXAMPLE: &[u8] = &[
+        0x4D, 0x54, 0x68, 0x64, // MThd
+        0x00, 0x00, 0x00, 0x06, // chunk length
+        0x00, 0x02, // format 2
+        0x00, 0x02, // two sequences
+        0x00, 0x60, // division: 96 ticks per quarter note
+        0x4D, 0x54, 0x72, 0x6B, // MTrk
+        0x00, 0x00, 0x00, 0x11, // chunk length
+        0x00, 0xFF, 0x03, 0x05, 0x46, 0x69, 0x72, 0x73, 0x74, // track name: "First"
+        0x30, 0x90, 0x3C, 0x64, // note on after an eighth: channel 0, note 60, velocity 100
+        0x60, 0xFF, 0x2F, 0x00, // end of track a quarter later
+        0x4D, 0x54, 0x72, 0x6B, // MTrk
+        0x00, 0x00, 0x00, 0x08, // chunk length
+        0x18, 0x90, 0x3E, 0x64, // note on after a sixteenth: channel 0, note 62, velocity 100
+        0x00, 0xFF, 0x2F, 0x00, // end of track
+    ];
+
+    #[test]
+    fn merged_track_matches_the_messages_of_the_file() {
+        let file = MidiFile::try_from(MIDI_FORMAT_2_EXAMPLE).unwrap();
+        let loaded = LoadedMidiFile::new(&file, "sequences.mid".to_string()).unwrap();
+        let expected: Vec<(u64, Vec<u8>)> = file
+            .to_time_stamped_messages()
+            .into_iter()
+            .map(|message| (message.timestamp_microseconds, message.message.into()))
+            .collect();
+        // the second sequence starts at the end of track of the first one
+        assert_eq!(expected[1].0, 750_000 + 125_000);
+        assert_eq!(loaded.get_merged_track().to_absolute_times(), expected);
+        assert_eq!(loaded.get_merged_track_indices(), &[0, 1]);
+        assert_eq!(loaded.get_track_names(), &[Some("First".to_string()), None]);
+        // a single track plays from its own start
+        assert_eq!(loaded.get_tracks()[1].to_absolute_times()[0].0, 125_000);
+    }
+
+    const NUM_TIMED_MESSAGES: u64 = 20;
+    const TIMED_INTERVAL_MICROSECONDS: u64 = 10_000;
+
+    fn play_timed_messages() -> (Vec<TimeStampedMidiMessage>, Vec<Vec<u8>>, JitterStats) {
+        let received = Arc::new(Mutex::new(Vec::<Vec<u8>>::new()));
+        let sink = received.clone();
+        let mut playback = MidiPlayback::default();
+        playback
+            .set_player(move |data| {
+                sink.lock().unwrap().push(data.to_vec());
+                Ok(())
+            })
+            .unwrap();
+
+        let data: Vec<TimeStampedMidiMessage> = (0..NUM_TIMED_MESSAGES)
+            .map(|index| TimeStampedMidiMessage {
+                timestamp_microseconds: index * TIMED_INTERVAL_MICROSECONDS,
+                message: if index % 2 == 0 {
+                    MidiMessage::note_on(MidiChannel::Channel1, 60, 100).unwrap()
+                } else {
+                    MidiMessage::note_off(MidiChannel::Channel1, 60, 0).unwrap()
+                },
+            })
+            .collect();
+
+        tauri::async_runtime::block_on(async {
+            playback.play(&data, TrackInfo::Recording(0)).await.unwrap();
+            while playback.get_state() != PlaybackState::Stopped {
+                thread::sleep(Duration::from_millis(10));
+            }
+        });
+
+        let received = received.lock().unwrap().clone();
+        (data, received, playback.get_jitter_stats())
+    }
+
+    #[test]
+    fn playback_sends_every_message() {
+        let (data, received, stats) = play_timed_messages();
+        assert_eq!(stats.get_message_count(), NUM_TIMED_MESSAGES);
+        assert!(stats.get_mean_lateness() <= stats.get_max_lateness());
+
+        // the playback ends with resetting the used channel
+        assert!(received.len() > NUM_TIMED_MESSAGES as usize);
+
+        for (index, bytes) in received
+            .iter()
+            .take(NUM_TIMED_MESSAGES as usize)
+            .enumerate()
+        {
+            let expected: Vec<u8> = data[index].message.clone().into();
+            assert_eq!(*bytes, expected);
+        }
+    }
+
+    #[test]
+    #[ignore = "timing depends on the load of the machine, run it with --ignored on its own"]
+    fn playback_sends_messages_in_time() {
+        const MAX_MEAN_LATENESS: Duration = Duration::from_micros(500);
+        const MAX_LATENESS: Duration = Duration::from_millis(2);
+
+        let (_, _, stats) = play_timed_messages();
+        assert!(
+            stats.get_mean_lateness() < MAX_MEAN_LATENESS,
+            "messages were sent {:?} late on average",
+            stats.get_mean_lateness()
+        );
+        assert!(
+            stats.get_max_lateness() < MAX_LATENESS,
+            "a message was sent {:?} late",
+            stats.get_max_lateness()
+        );
+    }
+}
@@ -0,0 +1,360 @@
+use std::collections::HashMap;
+
+use crate::midi::message::{
+    ChannelMessage, ChannelModeMessage, ChannelVoiceMessage, MidiChannel, MidiMessage,
+};
+
+const NUM_CHANNELS: usize = 16;
+// Controller numbers 120-127 are reserved for channel mode messages
+const NUM_CONTROLLERS: usize = 120;
+
+const BANK_SELECT_MSB: u8 = 0x00;
+const BANK_SELECT_LSB: u8 = 0x20;
+const DATA_ENTRY_MSB: u8 = 0x06;
+const DATA_ENTRY_LSB: u8 = 0x26;
+const SUSTAIN_PEDAL: u8 = 0x40;
+const DATA_INCREMENT: u8 = 0x60;
+const DATA_DECREMENT: u8 = 0x61;
+const NRPN_LSB: u8 = 0x62;
+const NRPN_MSB: u8 = 0x63;
+const RPN_LSB: u8 = 0x64;
+const RPN_MSB: u8 = 0x65;
+const PITCH_BEND_CENTER: u16 = 0x2000;
+
+#[derive(Debug, Clone)]
+struct ChannelState {
+    used: bool,
+    program: Option<u8>,
+    controllers: [Option<u8>; NUM_CONTROLLERS],
+    // whether data entry applies to the NRPN rather than the RPN selected last
+    is_nrpn_selected: bool,
+    pitch_bend: Option<u16>,
+}
+
+impl Default for ChannelState {
+    fn default() -> Self {
+        Self {
+            used: false,
+            program: None,
+            controllers: [None; NUM_CONTROLLERS],
+            is_nrpn_selected: false,
+            pitch_bend: None,
+        }
+    }
+}
+
+/// The last program change, controller values and pitch bend per channel
+/// that a sequence has set up to a certain position, as well as the notes held there.
+///
+/// Sending these messages before continuing playback at an arbitrary position
+/// puts the instrument into the same state as if it had played from the start.
+#[derive(Debug, Clone)]
+pub struct ChaseState {
+    channels: [ChannelState; NUM_CHANNELS],
+    // (channel, note) -> index of the Note On event that is still held
+    held_notes: HashMap<(u8, u8), usize>,
+}
+
+impl ChaseState {
+    pub fn from_events<'a>(events: impl IntoIterator<Item = &'a [u8]>) -> ChaseState {
+        let mut channels: [ChannelState; NUM_CHANNELS] = Default::default();
+        let mut held_notes = HashMap::new();
+
+        for (index, data) in events.into_iter().enumerate() {
+            let Ok(MidiMessage::Channel { channel, message }) = MidiMessage::try_from(data) else {
+                continue;
+            };
+
+            let channel_index = u8::from(channel);
+            let state = &mut channels[channel_index as usize];
+            state.used = true;
+
+            if let ChannelMessage::Mode(
+                ChannelModeMessage::AllNotesOff | ChannelModeMessage::AllSoundOff,
+            ) = message
+            {
+                held_notes.retain(|(channel, _), _| *channel != channel_index);
+            }
+
+            if let ChannelMessage::Mode(ChannelModeMessage::ResetAllControllers) = message {
+                // the program is not a controller, it stays as it is
+                state.controllers = [None; NUM_CONTROLLERS];
+                state.is_nrpn_selected = false;
+                state.pitch_bend = None;
+            }
+
+            if let ChannelMessage::Voice(voice_message) = message {
+                match voice_message {
+                    ChannelVoiceMessage::NoteOn { note, velocity } if velocity > 0 => {
+                        held_notes.insert((channel_index, note), index);
+                    }
+                    ChannelVoiceMessage::NoteOn { note, .. }
+                    | ChannelVoiceMessage::NoteOff { note, .. } => {
+                        held_notes.remove(&(channel_index, note));
+                    }
+                    ChannelVoiceMessage::ProgramChange(program) => state.program = Some(program),
+                    ChannelVoiceMessage::ControlChange { controller, value } => {
+                        match controller {
+                            NRPN_LSB | NRPN_MSB => state.is_nrpn_selected = true,
+                            RPN_LSB | RPN_MSB => state.is_nrpn_selected = false,
+                            _ => {}
+                        }
+                        state.controllers[controller as usize] = Some(value);
+                    }
+                    ChannelVoiceMessage::PitchBendChange(value) => state.pitch_bend = Some(value),
+                    _ => {}
+                }
+            }
+        }
+
+        ChaseState {
+            channels,
+            held_notes,
+        }
+    }
+
+    /// Returns the messages that restore the chased state on every used channel.
+    ///
+    /// Bank select is sent right before the program change it belongs to, and data entry
+    /// right after the RPN or NRPN selected last, as it only means something for that
+    /// parameter. Data increment and decrement are relative, so they are not repeated.
+    /// The sustain pedal and pitch bend fall back to their defaults, so that a pedal
+    /// held down or a bend applied at the previous position does not leak into the new one.
+    pub fn get_messages(&self) -> Vec<MidiMessage> {
+        let mut messages = Vec::new();
+
+        for (index, state) in self.channels.iter().enumerate() {
+            if !state.used {
+                continue;
+            }
+
+            let channel = MidiChannel::try_from(index as u8).unwrap();
+            let control_change = |controller: u8, value: u8| MidiMessage::Channel {
+                channel: channel.clone(),
+                message: ChannelMessage::Voice(ChannelVoiceMessage::ControlChange {
+                    controller,
+                    value,
+                }),
+            };
+
+            if let Some(program) = state.program {
+                for controller in [BANK_SELECT_MSB, BANK_SELECT_LSB] {
+                    if let Some(value) = state.controllers[controller as usize] {
+                        messages.push(control_change(controller, value));
+                    }
+                }
+                messages.push(MidiMessage::Channel {
+                    channel: channel.clone(),
+                    message: ChannelMessage::Voice(ChannelVoiceMessage::ProgramChange(program)),
+                });
+            }
+
+            for (controller, value) in state.controllers.iter().enumerate() {
+                let controller = controller as u8;
+                let is_bank_select = controller == BANK_SELECT_MSB || controller == BANK_SELECT_LSB;
+                if is_bank_select && state.program.is_some() {
+                    continue;
+                }
+                if matches!(
+                    controller,
+                    DATA_ENTRY_MSB | DATA_ENTRY_LSB | DATA_INCREMENT | DATA_DECREMENT | NRPN_LSB
+                        ..=RPN_MSB
+                ) {
+                    continue;
+                }
+
+                match value {
+                    Some(value) => messages.push(control_change(controller, *value)),
+                    None if controller == SUSTAIN_PEDAL => {
+                        messages.push(control_change(controller, 0))
+                    }
+                    None => {}
+                }
+            }
+
+            let (unselected, selected) = if state.is_nrpn_selected {
+                ([RPN_MSB, RPN_LSB], [NRPN_MSB, NRPN_LSB])
+            } else {
+                ([NRPN_MSB, NRPN_LSB], [RPN_MSB, RPN_LSB])
+            };
+            for controller in unselected
+                .into_iter()
+                .chain(selected)
+                .chain([DATA_ENTRY_MSB, DATA_ENTRY_LSB])
+            {
+                if let Some(value) = state.controllers[controller as usize] {
+                    messages.push(control_change(controller, value));
+                }
+            }
+
+            messages.push(MidiMessage::Channel {
+                channel: channel.clone(),
+                message: ChannelMessage::Voice(ChannelVoiceMessage::PitchBendChange(
+                    state.pitch_bend.unwrap_or(PITCH_BEND_CENTER),
+                )),
+            });
+        }
+
+        messages
+    }
+
+    /// Returns the indices of the Note On events that are still held at the end of
+    /// the chased events, in the order they were played.
+    pub fn get_held_notes(&self) -> Vec<usize> {
+        let mut indices: Vec<usize> = self.held_notes.values().copied().collect();
+        indices.sort();
+        indices
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn control_change(channel: MidiChannel, controller: u8, value: u8) -> MidiMessage {
+        MidiMessage::Channel {
+            channel,
+            message: ChannelMessage::Voice(ChannelVoiceMessage::ControlChange {
+                controller,
+                value,
+            }),
+        }
+    }
+
+    fn chase(messages: Vec<MidiMessage>) -> ChaseState {
+        let events: Vec<Vec<u8>> = messages.into_iter().map(Vec::from).collect();
+        ChaseState::from_events(events.iter().map(Vec::as_slice))
+    }
+
+    #[test]
+    fn restores_program_controllers_and_defaults_of_used_channels() {
+        let channel = MidiChannel::Channel1;
+        let state = chase(vec![
+            control_change(channel.clone(), 7, 100),
+            control_change(channel.clone(), BANK_SELECT_MSB, 1),
+            MidiMessage::Channel {
+                channel: channel.clone(),
+                message: ChannelMessage::Voice(ChannelVoiceMessage::ProgramChange(5)),
+            },
+            control_change(channel.clone(), 7, 80),
+        ]);
+
+        assert_eq!(
+            state.get_messages(),
+            vec![
+                control_change(channel.clone(), BANK_SELECT_MSB, 1),
+                MidiMessage::Channel {
+                    channel: channel.clone(),
+                    message: ChannelMessage::Voice(ChannelVoiceMessage::ProgramChange(5)),
+                },
+                control_change(channel.clone(), 7, 80),
+                control_change(channel.clone(), SUSTAIN_PEDAL, 0),
+                MidiMessage::Channel {
+                    channel,
+                    message: ChannelMessage::Voice(ChannelVoiceMessage::PitchBendChange(
+                        PITCH_BEND_CENTER,
+                    )),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn held_notes_are_the_note_ons_not_yet_released() {
+        let state = chase(vec![
+            MidiMessage::note_on(MidiChannel::Channel1, 60, 100).unwrap(),
+            MidiMessage::note_on(MidiChannel::Channel1, 62, 100).unwrap(),
+            MidiMessage::note_on(MidiChannel::Channel2, 64, 100).unwrap(),
+            MidiMessage::note_off(MidiChannel::Channel1, 60, 0).unwrap(),
+            // a retriggered note counts from its last Note On
+            MidiMessage::note_on(MidiChannel::Channel2, 64, 90).unwrap(),
+        ]);
+
+        assert_eq!(state.get_held_notes(), vec![1, 4]);
+    }
+
+    #[test]
+    fn all_notes_off_releases_the_held_notes_of_its_channel() {
+        let state = chase(vec![
+            MidiMessage::note_on(MidiChannel::Channel1, 60, 100).unwrap(),
+            MidiMessage::note_on(MidiChannel::Channel2, 62, 100).unwrap(),
+            MidiMessage::all_notes_off(MidiChannel::Channel1),
+        ]);
+
+        assert_eq!(state.get_held_notes(), vec![1]);
+    }
+
+    #[test]
+    fn reset_all_controllers_keeps_only_the_program() {
+        let channel = MidiChannel::Channel1;
+        let state = chase(vec![
+            MidiMessage::Channel {
+                channel: channel.clone(),
+                message: ChannelMessage::Voice(ChannelVoiceMessage::ProgramChange(5)),
+            },
+            control_change(channel.clone(), 7, 80),
+            control_change(channel.clone(), SUSTAIN_PEDAL, 127),
+            MidiMessage::Channel {
+                channel: channel.clone(),
+                message: ChannelMessage::Voice(ChannelVoiceMessage::PitchBendChange(0x3000)),
+            },
+            MidiMessage::Channel {
+                channel: channel.clone(),
+                message: ChannelMessage::Mode(ChannelModeMessage::ResetAllControllers),
+            },
+        ]);
+
+        assert_eq!(
+            state.get_messages(),
+            vec![
+                MidiMessage::Channel {
+                    channel: channel.clone(),
+                    message: ChannelMessage::Voice(ChannelVoiceMessage::ProgramChange(5)),
+                },
+                control_change(channel.clone(), SUSTAIN_PEDAL, 0),
+                MidiMessage::Channel {
+                    channel,
+                    message: ChannelMessage::Voice(ChannelVoiceMessage::PitchBendChange(
+                        PITCH_BEND_CENTER,
+                    )),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn data_entry_follows_the_parameter_selected_last() {
+        let channel = MidiChannel::Channel1;
+        let state = chase(vec![
+            // pitch bend sensitivity, then an NRPN, then back to the RPN
+            control_change(channel.clone(), RPN_MSB, 0),
+            control_change(channel.clone(), RPN_LSB, 0),
+            control_change(channel.clone(), DATA_ENTRY_MSB, 12),
+            control_change(channel.clone(), NRPN_MSB, 1),
+            control_change(channel.clone(), NRPN_LSB, 8),
+            control_change(channel.clone(), DATA_INCREMENT, 0),
+            control_change(channel.clone(), RPN_LSB, 1),
+            control_change(channel.clone(), DATA_ENTRY_MSB, 64),
+            control_change(channel.clone(), DATA_ENTRY_LSB, 0),
+        ]);
+
+        assert_eq!(
+            state.get_messages(),
+            vec![
+                control_change(channel.clone(), SUSTAIN_PEDAL, 0),
+                control_change(channel.clone(), NRPN_MSB, 1),
+                control_change(channel.clone(), NRPN_LSB, 8),
+                control_change(channel.clone(), RPN_MSB, 0),
+                control_change(channel.clone(), RPN_LSB, 1),
+                control_change(channel.clone(), DATA_ENTRY_MSB, 64),
+                control_change(channel.clone(), DATA_ENTRY_LSB, 0),
+                MidiMessage::Channel {
+                    channel,
+                    message: ChannelMessage::Voice(ChannelVoiceMessage::PitchBendChange(
+                        PITCH_BEND_CENTER,
+                    )),
+                },
+            ]
+        );
+    }
+}
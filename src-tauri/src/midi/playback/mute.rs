@@ -0,0 +1,240 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::midi::message::{
+    ChannelMessage, ChannelModeMessage, ChannelVoiceMessage, MidiChannel, MidiMessage,
+};
+
+const NUM_CHANNELS: usize = 16;
+
+/// Mute and solo settings per channel and per track of the loaded file.
+///
+/// A channel (or track) is audible if it is not muted and either nothing is soloed
+/// or it is soloed itself. An event is played if both its channel and its track are audible.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MuteState {
+    muted_channels: [bool; NUM_CHANNELS],
+    soloed_channels: [bool; NUM_CHANNELS],
+    muted_tracks: BTreeSet<usize>,
+    soloed_tracks: BTreeSet<usize>,
+}
+
+impl MuteState {
+    pub fn set_channel_muted(&mut self, channel: MidiChannel, muted: bool) {
+        self.muted_channels[u8::from(channel) as usize] = muted;
+    }
+
+    pub fn set_channel_soloed(&mut self, channel: MidiChannel, soloed: bool) {
+        self.soloed_channels[u8::from(channel) as usize] = soloed;
+    }
+
+    pub fn set_track_muted(&mut self, index: usize, muted: bool) {
+        if muted {
+            self.muted_tracks.insert(index);
+        } else {
+            self.muted_tracks.remove(&index);
+        }
+    }
+
+    pub fn set_track_soloed(&mut self, index: usize, soloed: bool) {
+        if soloed {
+            self.soloed_tracks.insert(index);
+        } else {
+            self.soloed_tracks.remove(&index);
+        }
+    }
+
+    pub fn clear_tracks(&mut self) {
+        self.muted_tracks.clear();
+        self.soloed_tracks.clear();
+    }
+
+    pub fn get_muted_channels(&self) -> Vec<MidiChannel> {
+        Self::channels(&self.muted_channels)
+    }
+
+    pub fn get_soloed_channels(&self) -> Vec<MidiChannel> {
+        Self::channels(&self.soloed_channels)
+    }
+
+    pub fn get_muted_tracks(&self) -> Vec<usize> {
+        self.muted_tracks.iter().copied().collect()
+    }
+
+    pub fn get_soloed_tracks(&self) -> Vec<usize> {
+        self.soloed_tracks.iter().copied().collect()
+    }
+
+    /// Tracks only apply to files, events of recordings have no track.
+    pub fn is_audible(&self, channel: u8, track: Option<usize>) -> bool {
+        let channel = channel as usize;
+        let is_channel_audible = !self.muted_channels[channel]
+            && (self.soloed_channels[channel] || !self.soloed_channels.contains(&true));
+        let is_track_audible = track.is_none_or(|track| {
+            !self.muted_tracks.contains(&track)
+                && (self.soloed_tracks.is_empty() || self.soloed_tracks.contains(&track))
+        });
+
+        is_channel_audible && is_track_audible
+    }
+
+    fn channels(flags: &[bool; NUM_CHANNELS]) -> Vec<MidiChannel> {
+        flags
+            .iter()
+            .enumerate()
+            .filter(|(_, flag)| **flag)
+            .map(|(index, _)| MidiChannel::try_from(index as u8).unwrap())
+            .collect()
+    }
+}
+
+/// Drops the Note Ons of muted channels and tracks and remembers which track started
+/// every sounding note, so the notes can be released when their source gets muted.
+///
+/// Everything but Note Ons is still played on muted channels, which keeps programs
+/// and controllers up to date for the moment the channel is unmuted again.
+#[derive(Debug, Default)]
+pub struct MuteFilter {
+    state: MuteState,
+    // (channel, note) -> track that turned the note on
+    sounding_notes: HashMap<(u8, u8), Option<usize>>,
+}
+
+impl MuteFilter {
+    /// Applies new mute settings and returns Note Offs for the notes that became inaudible.
+    pub fn set_state(&mut self, state: MuteState) -> Vec<MidiMessage> {
+        if state == self.state {
+            return vec![];
+        }
+        self.state = state;
+
+        let mut note_offs = Vec::new();
+        self.sounding_notes.retain(|(channel, note), track| {
+            if self.state.is_audible(*channel, *track) {
+                return true;
+            }
+
+            note_offs.push(MidiMessage::Channel {
+                channel: MidiChannel::try_from(*channel).unwrap(),
+                message: ChannelMessage::Voice(ChannelVoiceMessage::NoteOff {
+                    note: *note,
+                    velocity: 0,
+                }),
+            });
+            false
+        });
+
+        note_offs
+    }
+
+    /// Forgets all sounding notes, e.g. after they were released by other means.
+    pub fn reset(&mut self) {
+        self.sounding_notes.clear();
+    }
+
+    /// Returns whether the event should be played.
+    pub fn filter(&mut self, data: &[u8], track: Option<usize>) -> bool {
+        let Ok(MidiMessage::Channel { channel, message }) = MidiMessage::try_from(data) else {
+            return true;
+        };
+        let channel = u8::from(channel);
+
+        match message {
+            ChannelMessage::Voice(ChannelVoiceMessage::NoteOn { note, velocity })
+                if velocity > 0 =>
+            {
+                if !self.state.is_audible(channel, track) {
+                    return false;
+                }
+                self.sounding_notes.insert((channel, note), track);
+            }
+            ChannelMessage::Voice(
+                ChannelVoiceMessage::NoteOn { note, .. }
+                | ChannelVoiceMessage::NoteOff { note, .. },
+            ) => {
+                self.sounding_notes.remove(&(channel, note));
+            }
+            ChannelMessage::Mode(
+                ChannelModeMessage::AllNotesOff | ChannelModeMessage::AllSoundOff,
+            ) => {
+                self.sounding_notes
+                    .retain(|(sounding_channel, _), _| *sounding_channel != channel);
+            }
+            _ => {}
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(message: MidiMessage) -> Vec<u8> {
+        message.into()
+    }
+
+    fn note_on(channel: MidiChannel, note: u8) -> Vec<u8> {
+        data(MidiMessage::note_on(channel, note, 100).unwrap())
+    }
+
+    #[test]
+    fn muting_a_channel_releases_its_notes_and_drops_new_ones() {
+        let mut filter = MuteFilter::default();
+        assert!(filter.filter(&note_on(MidiChannel::Channel1, 60), None));
+        assert!(filter.filter(&note_on(MidiChannel::Channel2, 62), None));
+
+        let mut state = MuteState::default();
+        state.set_channel_muted(MidiChannel::Channel1, true);
+        assert_eq!(
+            filter.set_state(state),
+            vec![MidiMessage::note_off(MidiChannel::Channel1, 60, 0).unwrap()]
+        );
+
+        assert!(!filter.filter(&note_on(MidiChannel::Channel1, 64), None));
+        // controllers keep the muted channel up to date
+        let volume = MidiMessage::Channel {
+            channel: MidiChannel::Channel1,
+            message: ChannelMessage::Voice(ChannelVoiceMessage::ControlChange {
+                controller: 7,
+                value: 90,
+            }),
+        };
+        assert!(filter.filter(&data(volume), None));
+    }
+
+    #[test]
+    fn soloing_a_track_releases_the_notes_of_other_tracks() {
+        let mut filter = MuteFilter::default();
+        filter.filter(&note_on(MidiChannel::Channel1, 60), Some(0));
+        filter.filter(&note_on(MidiChannel::Channel1, 62), Some(1));
+
+        let mut state = MuteState::default();
+        state.set_track_soloed(1, true);
+        assert_eq!(
+            filter.set_state(state),
+            vec![MidiMessage::note_off(MidiChannel::Channel1, 60, 0).unwrap()]
+        );
+        assert!(!filter.filter(&note_on(MidiChannel::Channel1, 60), Some(0)));
+        assert!(filter.filter(&note_on(MidiChannel::Channel1, 60), Some(1)));
+    }
+
+    #[test]
+    fn all_notes_off_forgets_the_sounding_notes_of_its_channel() {
+        let mut filter = MuteFilter::default();
+        filter.filter(&note_on(MidiChannel::Channel1, 60), None);
+        filter.filter(&note_on(MidiChannel::Channel2, 62), None);
+        assert!(filter.filter(
+            &data(MidiMessage::all_notes_off(MidiChannel::Channel1)),
+            None
+        ));
+
+        let mut state = MuteState::default();
+        state.set_channel_muted(MidiChannel::Channel1, true);
+        state.set_channel_muted(MidiChannel::Channel2, true);
+        assert_eq!(
+            filter.set_state(state),
+            vec![MidiMessage::note_off(MidiChannel::Channel2, 62, 0).unwrap()]
+        );
+    }
+}
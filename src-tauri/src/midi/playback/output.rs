@@ -0,0 +1,266 @@
+use crate::midi::{
+    message::{ChannelMessage, ChannelModeMessage, ChannelVoiceMessage, MidiChannel, MidiMessage},
+    playback::{
+        MidiPlayerFn,
+        transform::{PlaybackTransform, TransformStage},
+    },
+};
+
+const NUM_CHANNELS: usize = 16;
+const NUM_NOTES: usize = 128;
+
+const SUSTAIN_PEDAL: u8 = 0x40;
+
+/// Sends messages through the transform stage to the MIDI player and keeps track of
+/// the notes it turned on and the channels it used, so they can be released at any time.
+pub struct PlaybackOutput {
+    player: MidiPlayerFn,
+    transform_stage: TransformStage,
+    // velocity of every sounding note per channel, 0 if the note is off
+    active_notes: [[u8; NUM_NOTES]; NUM_CHANNELS],
+    used_channels: [bool; NUM_CHANNELS],
+}
+
+impl PlaybackOutput {
+    pub fn new(player: MidiPlayerFn) -> PlaybackOutput {
+        PlaybackOutput {
+            player,
+            transform_stage: TransformStage::default(),
+            active_notes: [[0; NUM_NOTES]; NUM_CHANNELS],
+            used_channels: [false; NUM_CHANNELS],
+        }
+    }
+
+    pub fn set_transform(&mut self, transform: PlaybackTransform) {
+        self.transform_stage.set_transform(transform);
+    }
+
+    pub fn send(&mut self, data: &[u8]) -> Result<(), String> {
+        // only channel messages are transformed, everything else is passed through as is
+        let message = match MidiMessage::try_from(data) {
+            Ok(message @ MidiMessage::Channel { .. }) => message,
+            _ => return (self.player)(data),
+        };
+
+        for message in self.transform_stage.apply(message) {
+            self.send_to_player(message)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn send_message(&mut self, message: MidiMessage) {
+        for message in self.transform_stage.apply(message) {
+            if let Err(error) = self.send_to_player(message) {
+                eprintln!("{error}");
+            }
+        }
+    }
+
+    /// Sends a Note Off for every note that is still sounding.
+    pub fn release_notes(&mut self) {
+        for channel_index in 0..NUM_CHANNELS {
+            for note in 0..NUM_NOTES {
+                if self.active_notes[channel_index][note] == 0 {
+                    continue;
+                }
+
+                let channel = MidiChannel::try_from(channel_index as u8).unwrap();
+                let note_off = MidiMessage::Channel {
+                    channel,
+                    message: ChannelMessage::Voice(ChannelVoiceMessage::NoteOff {
+                        note: note as u8,
+                        velocity: 0,
+                    }),
+                };
+                if let Err(error) = self.send_to_player(note_off) {
+                    eprintln!("{error}");
+                }
+            }
+        }
+
+        self.transform_stage.reset();
+    }
+
+    /// Releases all sounding notes and the sustain pedal and resets every channel that
+    /// was used so far, e.g. when playback stops, pauses or jumps to another position.
+    pub fn silence(&mut self) {
+        self.release_notes();
+
+        for channel_index in 0..NUM_CHANNELS {
+            if !self.used_channels[channel_index] {
+                continue;
+            }
+
+            let channel = MidiChannel::try_from(channel_index as u8).unwrap();
+            for message in [
+                Self::sustain_off(channel.clone()),
+                MidiMessage::all_notes_off(channel.clone()),
+                MidiMessage::reset_all_controllers(channel),
+            ] {
+                if let Err(error) = self.send_to_player(message) {
+                    eprintln!("{error}");
+                }
+            }
+        }
+    }
+
+    /// Releases all sounding notes and resets all 16 channels, including the sustain
+    /// pedal, whether they were used or not.
+    pub fn panic(&mut self) -> Result<(), String> {
+        self.release_notes();
+
+        for channel_index in 0..NUM_CHANNELS {
+            let channel = MidiChannel::try_from(channel_index as u8).unwrap();
+            for message in [
+                Self::sustain_off(channel.clone()),
+                MidiMessage::all_sound_off(channel.clone()),
+                MidiMessage::all_notes_off(channel.clone()),
+                MidiMessage::reset_all_controllers(channel),
+            ] {
+                self.send_to_player(message)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn sustain_off(channel: MidiChannel) -> MidiMessage {
+        MidiMessage::Channel {
+            channel,
+            message: ChannelMessage::Voice(ChannelVoiceMessage::ControlChange {
+                controller: SUSTAIN_PEDAL,
+                value: 0,
+            }),
+        }
+    }
+
+    fn send_to_player(&mut self, message: MidiMessage) -> Result<(), String> {
+        let data: Vec<u8> = message.clone().into();
+        (self.player)(data.as_slice())?;
+        self.update_active_notes(&message);
+
+        Ok(())
+    }
+
+    fn update_active_notes(&mut self, message: &MidiMessage) {
+        let MidiMessage::Channel { channel, message } = message else {
+            return;
+        };
+
+        let channel_index = u8::from(channel.clone()) as usize;
+        self.used_channels[channel_index] = true;
+
+        let notes = &mut self.active_notes[channel_index];
+        match message {
+            ChannelMessage::Voice(ChannelVoiceMessage::NoteOn { note, velocity }) => {
+                notes[*note as usize] = *velocity;
+            }
+            ChannelMessage::Voice(ChannelVoiceMessage::NoteOff { note, .. }) => {
+                notes[*note as usize] = 0;
+            }
+            ChannelMessage::Mode(
+                ChannelModeMessage::AllNotesOff | ChannelModeMessage::AllSoundOff,
+            ) => {
+                *notes = [0; NUM_NOTES];
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    /// Returns an output together with everything it sends to the player.
+    fn output() -> (PlaybackOutput, Arc<Mutex<Vec<MidiMessage>>>) {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let sink = sent.clone();
+        let output = PlaybackOutput::new(Arc::new(move |data: &[u8]| {
+            sink.lock()
+                .unwrap()
+                .push(MidiMessage::try_from(data).unwrap());
+            Ok(())
+        }));
+        (output, sent)
+    }
+
+    fn remap(target: MidiChannel) -> PlaybackTransform {
+        PlaybackTransform {
+            channel_map: vec![(MidiChannel::Channel1, target)],
+            ..Default::default()
+        }
+    }
+
+    fn note_on(channel: MidiChannel, note: u8) -> MidiMessage {
+        MidiMessage::note_on(channel, note, 100).unwrap()
+    }
+
+    fn note_off(channel: MidiChannel, note: u8) -> MidiMessage {
+        MidiMessage::note_off(channel, note, 0).unwrap()
+    }
+
+    #[test]
+    fn release_notes_turns_off_the_notes_where_they_were_sent() {
+        let (mut output, sent) = output();
+        output.set_transform(remap(MidiChannel::Channel2));
+        let data: Vec<u8> = note_on(MidiChannel::Channel1, 60).into();
+        output.send(&data).unwrap();
+        output.set_transform(PlaybackTransform::default());
+        sent.lock().unwrap().clear();
+
+        output.release_notes();
+        assert_eq!(
+            *sent.lock().unwrap(),
+            vec![note_off(MidiChannel::Channel2, 60)]
+        );
+
+        // nothing is left to release
+        sent.lock().unwrap().clear();
+        output.release_notes();
+        assert_eq!(*sent.lock().unwrap(), vec![]);
+    }
+
+    #[test]
+    fn all_notes_off_leaves_no_remapped_note_hanging() {
+        let (mut output, sent) = output();
+        output.set_transform(remap(MidiChannel::Channel2));
+        output.send_message(note_on(MidiChannel::Channel1, 60));
+        output.set_transform(remap(MidiChannel::Channel3));
+        sent.lock().unwrap().clear();
+
+        output.send_message(MidiMessage::all_notes_off(MidiChannel::Channel1));
+        assert_eq!(
+            *sent.lock().unwrap(),
+            vec![
+                note_off(MidiChannel::Channel2, 60),
+                MidiMessage::all_notes_off(MidiChannel::Channel3),
+            ]
+        );
+
+        sent.lock().unwrap().clear();
+        output.release_notes();
+        assert_eq!(*sent.lock().unwrap(), vec![]);
+    }
+
+    #[test]
+    fn silence_resets_only_the_used_channels() {
+        let (mut output, sent) = output();
+        output.send_message(note_on(MidiChannel::Channel3, 60));
+        sent.lock().unwrap().clear();
+
+        output.silence();
+        assert_eq!(
+            *sent.lock().unwrap(),
+            vec![
+                note_off(MidiChannel::Channel3, 60),
+                PlaybackOutput::sustain_off(MidiChannel::Channel3),
+                MidiMessage::all_notes_off(MidiChannel::Channel3),
+                MidiMessage::reset_all_controllers(MidiChannel::Channel3),
+            ]
+        );
+    }
+}
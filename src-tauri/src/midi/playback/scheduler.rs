@@ -0,0 +1,125 @@
+use std::{
+    sync::{Arc, Mutex, Once},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Sleeping is only accurate to about a millisecond (often worse), so the last part
+/// of every wait is spent spinning.
+const SPIN_THRESHOLD: Duration = Duration::from_millis(2);
+/// Sleep no more than 50ms to be able to handle pause/stop/seek signals quickly.
+pub const MAX_SLEEP_DURATION: Duration = Duration::from_millis(50);
+
+/// The failure to raise the priority is the same on every playback, so it is reported once.
+static PRIORITY_WARNING: Once = Once::new();
+
+/// How late the scheduled messages were sent compared to their due time.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JitterStats {
+    message_count: u64,
+    total_lateness: Duration,
+    max_lateness: Duration,
+}
+
+impl JitterStats {
+    pub fn record(&mut self, lateness: Duration) {
+        self.message_count += 1;
+        self.total_lateness += lateness;
+        self.max_lateness = self.max_lateness.max(lateness);
+    }
+
+    pub fn get_message_count(&self) -> u64 {
+        self.message_count
+    }
+
+    pub fn get_mean_lateness(&self) -> Duration {
+        match self.message_count {
+            0 => Duration::ZERO,
+            count => self.total_lateness.div_f64(count as f64),
+        }
+    }
+
+    pub fn get_max_lateness(&self) -> Duration {
+        self.max_lateness
+    }
+}
+
+/// Waits for the due time of the next message with a hybrid sleep/spin strategy
+/// and records how late every message was sent.
+pub struct Scheduler {
+    jitter_stats: Arc<Mutex<JitterStats>>,
+}
+
+impl Scheduler {
+    pub fn new(jitter_stats: Arc<Mutex<JitterStats>>) -> Scheduler {
+        Scheduler { jitter_stats }
+    }
+
+    /// Returns the instant at which the given playback position is reached, counted from
+    /// the position that was reached at the anchor instant.
+    pub fn get_deadline(
+        anchor_instant: Instant,
+        anchor_position: Duration,
+        position: Duration,
+        rate: f64,
+    ) -> Instant {
+        if position >= anchor_position {
+            anchor_instant + (position - anchor_position).div_f64(rate)
+        } else {
+            anchor_instant - (anchor_position - position).div_f64(rate)
+        }
+    }
+
+    /// Tries to give the calling thread real-time priority, or at least a higher nice value.
+    ///
+    /// Both usually require extra privileges (e.g. `rtprio` in `limits.conf`),
+    /// so failing is not an error, the playback just stays less accurate under load.
+    #[cfg(target_os = "linux")]
+    pub fn raise_priority() {
+        // SAFETY: both calls only change the scheduling of the calling thread
+        unsafe {
+            let param = libc::sched_param { sched_priority: 10 };
+            if libc::sched_setscheduler(0, libc::SCHED_FIFO, &param) == 0 {
+                return;
+            }
+
+            let thread_id = libc::gettid() as libc::id_t;
+            if libc::setpriority(libc::PRIO_PROCESS, thread_id, -10) != 0 {
+                PRIORITY_WARNING.call_once(|| {
+                    eprintln!("Could not raise the priority of the playback thread");
+                });
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn raise_priority() {}
+
+    /// Waits until the deadline, but sleeps no longer than a short slice.
+    ///
+    /// Returns `true` once the deadline is reached, or `false` after sleeping a slice,
+    /// so that the caller can handle signals and check the deadline again.
+    pub fn wait_until(&self, deadline: Instant) -> bool {
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+
+        let remaining = deadline - now;
+        if remaining > SPIN_THRESHOLD {
+            thread::sleep(MAX_SLEEP_DURATION.min(remaining - SPIN_THRESHOLD));
+            return false;
+        }
+
+        while Instant::now() < deadline {
+            std::hint::spin_loop();
+        }
+
+        true
+    }
+
+    pub fn record_lateness(&self, deadline: Instant) {
+        let lateness = Instant::now().saturating_duration_since(deadline);
+        self.jitter_stats.lock().unwrap().record(lateness);
+    }
+}
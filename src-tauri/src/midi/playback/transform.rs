@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+
+use crate::midi::message::{
+    ChannelMessage, ChannelModeMessage, ChannelVoiceMessage, MidiChannel, MidiMessage,
+};
+
+pub const MAX_TRANSPOSE: i8 = 48;
+pub const MAX_VELOCITY_SCALE: f64 = 2.0;
+
+/// Changes applied to every channel message on its way from the track to the MIDI player.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlaybackTransform {
+    /// Semitones added to every note number.
+    pub transpose: i8,
+    /// Source and target channel pairs; unmapped channels are left unchanged.
+    pub channel_map: Vec<(MidiChannel, MidiChannel)>,
+    /// Factor applied to Note On velocities.
+    pub velocity_scale: f64,
+}
+
+impl Default for PlaybackTransform {
+    fn default() -> Self {
+        Self {
+            transpose: 0,
+            channel_map: Vec::new(),
+            velocity_scale: 1.0,
+        }
+    }
+}
+
+impl PlaybackTransform {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.transpose.unsigned_abs() > MAX_TRANSPOSE as u8 {
+            return Err(format!(
+                "Transpose must be between -{} and {} semitones",
+                MAX_TRANSPOSE, MAX_TRANSPOSE
+            ));
+        }
+
+        if !(self.velocity_scale > 0.0 && self.velocity_scale <= MAX_VELOCITY_SCALE) {
+            return Err(format!(
+                "Velocity scale must be greater than 0 and at most {}",
+                MAX_VELOCITY_SCALE
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn map_channel(&self, channel: &MidiChannel) -> MidiChannel {
+        self.channel_map
+            .iter()
+            .find(|(source, _)| source == channel)
+            .map(|(_, target)| target.clone())
+            .unwrap_or_else(|| channel.clone())
+    }
+
+    fn map_note(&self, note: u8) -> Option<u8> {
+        let note = note as i16 + self.transpose as i16;
+        (0..=127).contains(&note).then_some(note as u8)
+    }
+
+    fn scale_velocity(&self, velocity: u8) -> u8 {
+        // keep the velocity above 0, otherwise the Note On would turn into a Note Off
+        (velocity as f64 * self.velocity_scale)
+            .round()
+            .clamp(1.0, 127.0) as u8
+    }
+}
+
+/// Applies the current transform and remembers where every sounding note was sent to,
+/// so its Note Off reaches the same output note even if the transform changed in between.
+#[derive(Debug, Default)]
+pub struct TransformStage {
+    transform: PlaybackTransform,
+    // (source channel, source note) -> (target channel, target note)
+    sounding_notes: HashMap<(u8, u8), (MidiChannel, u8)>,
+}
+
+impl TransformStage {
+    pub fn set_transform(&mut self, transform: PlaybackTransform) {
+        self.transform = transform;
+    }
+
+    /// Forgets all sounding notes, e.g. after they were released by other means.
+    pub fn reset(&mut self) {
+        self.sounding_notes.clear();
+    }
+
+    pub fn apply(&mut self, message: MidiMessage) -> Vec<MidiMessage> {
+        let MidiMessage::Channel { channel, message } = message else {
+            return vec![message];
+        };
+
+        let source_channel = u8::from(channel.clone());
+        let target_channel = self.transform.map_channel(&channel);
+        let voice = |channel: MidiChannel, message: ChannelVoiceMessage| MidiMessage::Channel {
+            channel,
+            message: ChannelMessage::Voice(message),
+        };
+
+        match message {
+            ChannelMessage::Voice(ChannelVoiceMessage::NoteOn { note, velocity })
+                if velocity > 0 =>
+            {
+                let mut messages = Vec::new();
+
+                // a retriggered note must not leave its previous output note hanging
+                if let Some((channel, note)) = self.sounding_notes.remove(&(source_channel, note)) {
+                    messages.push(voice(
+                        channel,
+                        ChannelVoiceMessage::NoteOff { note, velocity: 0 },
+                    ));
+                }
+
+                if let Some(target_note) = self.transform.map_note(note) {
+                    self.sounding_notes.insert(
+                        (source_channel, note),
+                        (target_channel.clone(), target_note),
+                    );
+                    messages.push(voice(
+                        target_channel,
+                        ChannelVoiceMessage::NoteOn {
+                            note: target_note,
+                            velocity: self.transform.scale_velocity(velocity),
+                        },
+                    ));
+                }
+
+                messages
+            }
+            ChannelMessage::Voice(
+                ChannelVoiceMessage::NoteOn { note, velocity }
+                | ChannelVoiceMessage::NoteOff { note, velocity },
+            ) => {
+                // notes that were never turned on by the player are not released either
+                match self.sounding_notes.remove(&(source_channel, note)) {
+                    Some((channel, note)) => {
+                        vec![voice(
+                            channel,
+                            ChannelVoiceMessage::NoteOff { note, velocity },
+                        )]
+                    }
+                    None => vec![],
+                }
+            }
+            ChannelMessage::Voice(ChannelVoiceMessage::PolyphonicKeyPressure {
+                note,
+                pressure,
+            }) => {
+                let target = match self.sounding_notes.get(&(source_channel, note)) {
+                    Some((channel, note)) => Some((channel.clone(), *note)),
+                    None => self
+                        .transform
+                        .map_note(note)
+                        .map(|note| (target_channel, note)),
+                };
+
+                target
+                    .map(|(channel, note)| {
+                        voice(
+                            channel,
+                            ChannelVoiceMessage::PolyphonicKeyPressure { note, pressure },
+                        )
+                    })
+                    .into_iter()
+                    .collect()
+            }
+            ChannelMessage::Mode(
+                ChannelModeMessage::AllNotesOff | ChannelModeMessage::AllSoundOff,
+            ) => {
+                // the notes may have been sent to other channels before the mapping changed
+                let mut released: Vec<(u8, (MidiChannel, u8))> = Vec::new();
+                self.sounding_notes.retain(|(channel, note), target| {
+                    if *channel != source_channel {
+                        return true;
+                    }
+                    released.push((*note, target.clone()));
+                    false
+                });
+                released.sort_by_key(|(note, _)| *note);
+
+                let mut messages: Vec<MidiMessage> = released
+                    .into_iter()
+                    .map(|(_, (channel, note))| {
+                        voice(channel, ChannelVoiceMessage::NoteOff { note, velocity: 0 })
+                    })
+                    .collect();
+                messages.push(MidiMessage::Channel {
+                    channel: target_channel,
+                    message,
+                });
+                messages
+            }
+            message => vec![MidiMessage::Channel {
+                channel: target_channel,
+                message,
+            }],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note_on(channel: MidiChannel, note: u8) -> MidiMessage {
+        MidiMessage::note_on(channel, note, 100).unwrap()
+    }
+
+    fn note_off(channel: MidiChannel, note: u8) -> MidiMessage {
+        MidiMessage::note_off(channel, note, 0).unwrap()
+    }
+
+    fn remap(target: MidiChannel) -> PlaybackTransform {
+        PlaybackTransform {
+            channel_map: vec![(MidiChannel::Channel1, target)],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn retriggered_note_releases_its_previous_output_note() {
+        let mut stage = TransformStage::default();
+        stage.set_transform(PlaybackTransform {
+            transpose: 2,
+            ..Default::default()
+        });
+        assert_eq!(
+            stage.apply(note_on(MidiChannel::Channel1, 60)),
+            vec![note_on(MidiChannel::Channel1, 62)]
+        );
+
+        stage.set_transform(PlaybackTransform::default());
+        assert_eq!(
+            stage.apply(note_on(MidiChannel::Channel1, 60)),
+            vec![
+                note_off(MidiChannel::Channel1, 62),
+                note_on(MidiChannel::Channel1, 60),
+            ]
+        );
+    }
+
+    #[test]
+    fn note_off_follows_its_note_on_to_the_remapped_channel() {
+        let mut stage = TransformStage::default();
+        stage.set_transform(remap(MidiChannel::Channel2));
+        assert_eq!(
+            stage.apply(note_on(MidiChannel::Channel1, 60)),
+            vec![note_on(MidiChannel::Channel2, 60)]
+        );
+
+        stage.set_transform(PlaybackTransform::default());
+        assert_eq!(
+            stage.apply(note_off(MidiChannel::Channel1, 60)),
+            vec![note_off(MidiChannel::Channel2, 60)]
+        );
+        // released already
+        assert_eq!(stage.apply(note_off(MidiChannel::Channel1, 60)), vec![]);
+    }
+
+    #[test]
+    fn all_notes_off_releases_notes_sent_to_other_channels() {
+        let mut stage = TransformStage::default();
+        stage.set_transform(remap(MidiChannel::Channel2));
+        stage.apply(note_on(MidiChannel::Channel1, 64));
+        stage.apply(note_on(MidiChannel::Channel1, 60));
+        stage.apply(note_on(MidiChannel::Channel3, 67));
+
+        stage.set_transform(remap(MidiChannel::Channel4));
+        assert_eq!(
+            stage.apply(MidiMessage::all_notes_off(MidiChannel::Channel1)),
+            vec![
+                note_off(MidiChannel::Channel2, 60),
+                note_off(MidiChannel::Channel2, 64),
+                MidiMessage::all_notes_off(MidiChannel::Channel4),
+            ]
+        );
+        // notes of other source channels keep sounding
+        assert_eq!(
+            stage.apply(note_off(MidiChannel::Channel3, 67)),
+            vec![note_off(MidiChannel::Channel3, 67)]
+        );
+    }
+}
@@ -1,16 +1,99 @@
-use crate::midi::message::{MidiMessage, TimeStampedMidiMessage};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use crate::midi::{
+    filter::InputFilter,
+    journal::{JournalHeader, RecordingJournal, UnfinishedJournal},
+    library::{
+        CompSegment, OverdubSource, Recording, RecordingGrid, RecordingLibrary, RecordingMetadata,
+        get_current_milliseconds, split_loop_passes,
+    },
+    message::{
+        ChannelMessage, ChannelVoiceMessage, MidiChannel, MidiMessage, SystemMessage,
+        TimeStampedMidiMessage,
+    },
+    playback::LoopRegion,
+};
+
+pub const DEFAULT_CAPTURE_MINUTES: u32 = 5;
+pub const MAX_CAPTURE_MINUTES: u32 = 60;
+/// Silence of at least this length separates two phrases in the capture buffer.
+const PHRASE_IDLE_GAP: Duration = Duration::from_secs(3);
+/// An overdub starts the playback a little later, so that both sides are ready in time.
+pub const OVERDUB_LEAD: Duration = Duration::from_millis(200);
+
+/// The part of the capture buffer to turn into a recording.
+#[derive(Debug, PartialEq, Eq, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum CaptureSelection {
+    LastSeconds {
+        seconds: u32,
+    },
+    /// Everything since the last idle gap.
+    LastPhrase,
+}
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum RecorderState {
     Stopped,
     Recording,
+    Paused,
+}
+
+/// Positions in a playback between which a take replaces what was recorded before.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PunchRange {
+    pub start: Duration,
+    pub end: Duration,
 }
 
+/// Ties a recording to a clock outside of the input, e.g. the metronome or a playback.
 #[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RecordingAlignment {
+    /// The instant that becomes the start of the recording.
+    pub origin: Instant,
+    /// Tempo and time signature, when the origin is the first beat of a bar.
+    pub grid: Option<RecordingGrid>,
+    /// What is played back from the origin on.
+    pub overdub_of: Option<OverdubSource>,
+    /// Set when the take replaces a range of the recording that is played back,
+    /// instead of becoming a new recording.
+    pub punch: Option<PunchRange>,
+    /// Set when the playback repeats a region; every pass over it becomes a take.
+    pub loop_region: Option<LoopRegion>,
+}
+
+// not Clone, as the journal of a running recording is written by a thread of its own
+#[derive(Debug, PartialEq, Eq)]
 pub struct MidiRecorder {
     state: RecorderState,
     buffer: Vec<TimeStampedMidiMessage>,
-    recordings: Vec<Vec<TimeStampedMidiMessage>>,
+    alignment: Option<RecordingAlignment>,
+    // timestamp of the alignment origin in the time of the buffer, known with the first message
+    origin_timestamp: Option<u64>,
+    // copy of the buffer on the disk, to recover the recording after a crash
+    journal: Option<RecordingJournal>,
+    // journals of recordings that were not finished in an earlier session
+    unfinished_journals: Vec<UnfinishedJournal>,
+    recordings: Vec<Recording>,
+    // where new recordings are saved, if opened
+    library: Option<RecordingLibrary>,
+    // name of the connected input port, kept with new recordings
+    input_device: Option<String>,
+    // notes currently held on the instrument: (channel, note) -> velocity
+    held_notes: BTreeMap<(u8, u8), u8>,
+    // input timestamp of the last message and when it arrived, to tell the current input time
+    last_input: Option<(u64, Instant)>,
+    // input timestamp at which the recording was paused
+    paused_at: Option<u64>,
+    // sum of all pauses, which is removed from the timestamps of the recording
+    paused_microseconds: u64,
+    // everything received from the input during the last minutes, whether recording or not
+    capture_buffer: VecDeque<TimeStampedMidiMessage>,
+    capture_minutes: u32,
+    input_filter: InputFilter,
 }
 
 impl Default for MidiRecorder {
@@ -18,7 +101,20 @@ impl Default for MidiRecorder {
         Self {
             state: RecorderState::Stopped,
             buffer: Vec::new(),
+            alignment: None,
+            origin_timestamp: None,
+            journal: None,
+            unfinished_journals: Vec::new(),
             recordings: Vec::new(),
+            library: None,
+            input_device: None,
+            held_notes: BTreeMap::new(),
+            last_input: None,
+            paused_at: None,
+            paused_microseconds: 0,
+            capture_buffer: VecDeque::new(),
+            capture_minutes: DEFAULT_CAPTURE_MINUTES,
+            input_filter: InputFilter::default(),
         }
     }
 }
@@ -31,6 +127,12 @@ impl MidiRecorder {
     pub fn start_recording(&mut self) -> Result<(), String> {
         if self.state == RecorderState::Stopped {
             self.buffer.clear();
+            self.held_notes.clear();
+            self.paused_at = None;
+            self.paused_microseconds = 0;
+            self.alignment = None;
+            self.origin_timestamp = None;
+            self.journal = self.create_journal();
             self.state = RecorderState::Recording;
             Ok(())
         } else {
@@ -38,6 +140,26 @@ impl MidiRecorder {
         }
     }
 
+    /// Starts a recording whose timestamps count from the origin of the alignment.
+    ///
+    /// With a grid, everything played before the first beat is part of the count-in and
+    /// left out, apart from notes that are played slightly early.
+    pub fn start_aligned_recording(&mut self, alignment: RecordingAlignment) -> Result<(), String> {
+        self.start_recording()?;
+        self.alignment = Some(alignment);
+
+        Ok(())
+    }
+
+    /// Whether the running recording is made along with a playback.
+    pub fn is_overdubbing(&self) -> bool {
+        self.state != RecorderState::Stopped
+            && self
+                .alignment
+                .as_ref()
+                .is_some_and(|alignment| alignment.overdub_of.is_some())
+    }
+
     pub fn stop_recording(&mut self) -> Result<(), String> {
         if self.state == RecorderState::Stopped {
             return Err("Recorder is not currently recording".to_string());
@@ -45,25 +167,311 @@ impl MidiRecorder {
 
         self.state = RecorderState::Stopped;
 
-        if !self.buffer.is_empty() {
-            self.recordings.push(self.buffer.clone());
-            self.buffer.clear();
+        let is_saved = if self.buffer.is_empty() {
+            true
+        } else {
+            let mut messages = std::mem::take(&mut self.buffer);
+            let alignment = self.alignment.take();
+            if alignment.is_some()
+                && let Some(origin) = self.origin_timestamp
+            {
+                messages.iter_mut().for_each(|message| {
+                    message.timestamp_microseconds =
+                        message.timestamp_microseconds.saturating_sub(origin);
+                });
+            }
+            match alignment {
+                Some(RecordingAlignment {
+                    overdub_of: Some(OverdubSource::Recording { id }),
+                    punch: Some(punch),
+                    ..
+                }) => self.punch_recording(&id, &punch, &messages),
+                Some(alignment) if alignment.loop_region.is_some() => {
+                    self.add_loop_recording(messages, alignment)
+                }
+                alignment => self.add_recording(messages, alignment),
+            }
+        };
+
+        // keep the journal unless the recording made it into the library
+        if let Some(journal) = self.journal.take() {
+            if is_saved {
+                journal.discard();
+            } else {
+                journal.close();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether the running recording can be paused.
+    ///
+    /// A recording tied to the metronome or a playback cannot, as its clock keeps running.
+    pub fn can_pause(&self) -> bool {
+        self.state == RecorderState::Recording && self.alignment.is_none()
+    }
+
+    /// Pauses the recording; notes held at this moment are closed.
+    pub fn pause_recording(&mut self) -> Result<(), String> {
+        if self.state != RecorderState::Recording {
+            return Err("Recorder is not currently recording".to_string());
+        }
+        if self.alignment.is_some() {
+            return Err(
+                "Cannot pause a recording made along with the metronome or a playback".to_string(),
+            );
+        }
+
+        self.state = RecorderState::Paused;
+        self.paused_at = self.get_input_timestamp();
+
+        if let Some(paused_at) = self.paused_at {
+            let timestamp_microseconds = paused_at.saturating_sub(self.paused_microseconds);
+            let held_notes: Vec<(u8, u8)> = self.held_notes.keys().copied().collect();
+            for (channel, note) in held_notes {
+                let message = MidiChannel::try_from(channel)
+                    .and_then(|channel| MidiMessage::note_off(channel, note, 0))
+                    .map_err(|e| e.to_string())?;
+                self.record(TimeStampedMidiMessage {
+                    timestamp_microseconds,
+                    message,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Resumes the recording right where it was paused, the time in between is left out.
+    ///
+    /// Notes that are (still or newly) held when resuming are opened again.
+    pub fn resume_recording(&mut self) -> Result<(), String> {
+        if self.state != RecorderState::Paused {
+            return Err("Recorder is not paused".to_string());
+        }
+
+        self.state = RecorderState::Recording;
+
+        let resumed_at = self.get_input_timestamp();
+        if let (Some(paused_at), Some(resumed_at)) = (self.paused_at.take(), resumed_at) {
+            self.paused_microseconds += resumed_at.saturating_sub(paused_at);
+        }
+
+        if let Some(resumed_at) = resumed_at {
+            let timestamp_microseconds = resumed_at.saturating_sub(self.paused_microseconds);
+            let held_notes: Vec<((u8, u8), u8)> = self
+                .held_notes
+                .iter()
+                .map(|(key, velocity)| (*key, *velocity))
+                .collect();
+            for ((channel, note), velocity) in held_notes {
+                let message = MidiChannel::try_from(channel)
+                    .and_then(|channel| MidiMessage::note_on(channel, note, velocity))
+                    .map_err(|e| e.to_string())?;
+                self.record(TimeStampedMidiMessage {
+                    timestamp_microseconds,
+                    message,
+                });
+            }
         }
 
         Ok(())
     }
 
-    pub fn get_recordings(&self) -> &[Vec<TimeStampedMidiMessage>] {
+    pub fn get_capture_minutes(&self) -> u32 {
+        self.capture_minutes
+    }
+
+    pub fn set_capture_minutes(&mut self, minutes: u32) -> Result<(), String> {
+        if !(1..=MAX_CAPTURE_MINUTES).contains(&minutes) {
+            return Err(format!(
+                "Capture buffer length must be between 1 and {} minutes",
+                MAX_CAPTURE_MINUTES
+            ));
+        }
+
+        self.capture_minutes = minutes;
+        if let Some(timestamp_microseconds) = self
+            .capture_buffer
+            .back()
+            .map(|message| message.timestamp_microseconds)
+        {
+            self.trim_capture_buffer(timestamp_microseconds);
+        }
+
+        Ok(())
+    }
+
+    pub fn get_input_filter(&self) -> &InputFilter {
+        &self.input_filter
+    }
+
+    pub fn set_input_filter(&mut self, input_filter: InputFilter) {
+        self.input_filter = input_filter;
+    }
+
+    /// Keeps every input message in the capture buffer, independent of the recording state.
+    pub fn capture_message(&mut self, message: MidiMessage, timestamp_microseconds: u64) {
+        self.last_input = Some((timestamp_microseconds, Instant::now()));
+        if !self.input_filter.accepts(&message) {
+            return;
+        }
+        self.capture_buffer.push_back(TimeStampedMidiMessage {
+            timestamp_microseconds,
+            message,
+        });
+        self.trim_capture_buffer(timestamp_microseconds);
+    }
+
+    /// Empties the capture buffer, e.g. when the input changes and with it the timestamps.
+    pub fn clear_capture_buffer(&mut self) {
+        self.capture_buffer.clear();
+        self.last_input = None;
+    }
+
+    /// Turns a part of the capture buffer into a new recording.
+    pub fn save_capture(&mut self, selection: CaptureSelection) -> Result<(), String> {
+        let is_played = |message: &TimeStampedMidiMessage| {
+            !matches!(
+                message.message,
+                MidiMessage::System(SystemMessage::RealTime(_))
+            )
+        };
+
+        let start_index = match selection {
+            CaptureSelection::LastSeconds { seconds } => {
+                let now = self.get_input_timestamp().unwrap_or_default();
+                let start = now.saturating_sub(seconds as u64 * 1_000_000);
+                self.capture_buffer
+                    .partition_point(|message| message.timestamp_microseconds < start)
+            }
+            CaptureSelection::LastPhrase => {
+                let gap = PHRASE_IDLE_GAP.as_micros() as u64;
+                let mut start_index = self.capture_buffer.len();
+                let mut next_timestamp: Option<u64> = None;
+                for (index, message) in self.capture_buffer.iter().enumerate().rev() {
+                    if !is_played(message) {
+                        continue;
+                    }
+                    if next_timestamp
+                        .is_some_and(|next| next - message.timestamp_microseconds >= gap)
+                    {
+                        break;
+                    }
+                    next_timestamp = Some(message.timestamp_microseconds);
+                    start_index = index;
+                }
+                start_index
+            }
+        };
+
+        let recording: Vec<TimeStampedMidiMessage> =
+            self.capture_buffer.range(start_index..).cloned().collect();
+        if !recording.iter().any(is_played) {
+            return Err("Nothing was played in the selected time".to_string());
+        }
+
+        self.add_recording(recording, None);
+
+        Ok(())
+    }
+
+    pub fn get_unfinished_journals(&self) -> &[UnfinishedJournal] {
+        self.unfinished_journals.as_slice()
+    }
+
+    /// Turns the journal of a recording that was never finished into a recording.
+    pub fn recover_recording(&mut self, index: usize) -> Result<(), String> {
+        let journal = self
+            .unfinished_journals
+            .get(index)
+            .ok_or_else(|| "Unfinished recording index out of bounds".to_string())?;
+        if journal.messages.is_empty() {
+            return Err("The unfinished recording contains no messages".to_string());
+        }
+
+        let mut recording = Recording::new(
+            "Recovered recording".to_string(),
+            journal.messages.clone(),
+            journal.header.input_device.clone(),
+        );
+        recording.metadata.id = journal.header.id.clone();
+        recording.metadata.created_milliseconds = journal.header.created_milliseconds;
+        if let Some(library) = &self.library {
+            library.save(&recording)?;
+        }
+
+        self.discard_unfinished_journal(index)?;
+        self.insert_recording(recording);
+
+        Ok(())
+    }
+
+    pub fn discard_unfinished_journal(&mut self, index: usize) -> Result<(), String> {
+        let journal = self
+            .unfinished_journals
+            .get(index)
+            .ok_or_else(|| "Unfinished recording index out of bounds".to_string())?;
+        if let Some(library) = &self.library {
+            library.delete_journal(&journal.header.id)?;
+        }
+        self.unfinished_journals.remove(index);
+
+        Ok(())
+    }
+
+    /// Loads the recordings of the library and saves all new recordings to it.
+    pub fn open_library(&mut self, library: RecordingLibrary) -> Result<(), String> {
+        let mut recordings = library.load()?;
+        recordings.append(&mut self.recordings);
+        self.recordings = recordings;
+        self.unfinished_journals = library.load_journals();
+        self.library = Some(library);
+
+        Ok(())
+    }
+
+    pub fn set_input_device(&mut self, input_device: Option<String>) {
+        self.input_device = input_device;
+    }
+
+    pub fn get_recordings(&self) -> &[Recording] {
         self.recordings.as_slice()
     }
 
+    pub fn rename_recording(&mut self, index: usize, name: String) -> Result<(), String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("Recording name must not be empty".to_string());
+        }
+
+        let mut metadata = self.get_recording(index)?.metadata.clone();
+        metadata.name = name.to_string();
+        self.update_metadata(index, metadata)
+    }
+
+    /// Replaces the tags of a recording; blank and duplicate tags are dropped.
+    pub fn set_recording_tags(&mut self, index: usize, tags: Vec<String>) -> Result<(), String> {
+        let mut metadata = self.get_recording(index)?.metadata.clone();
+        metadata.tags.clear();
+        for tag in tags {
+            let tag = tag.trim();
+            if !tag.is_empty() && !metadata.tags.iter().any(|existing| existing == tag) {
+                metadata.tags.push(tag.to_string());
+            }
+        }
+        self.update_metadata(index, metadata)
+    }
+
     pub fn remove_recording(&mut self, index: usize) -> Result<(), String> {
-        if index < self.recordings.len() {
-            self.recordings.remove(index);
-            Ok(())
-        } else {
-            Err("Recording index out of bounds".to_string())
+        let recording = self.get_recording(index)?;
+        if let Some(library) = &self.library {
+            library.delete(&recording.metadata.id)?;
         }
+        self.recordings.remove(index);
+
+        Ok(())
     }
 
     pub fn add_message(
@@ -71,15 +479,557 @@ impl MidiRecorder {
         message: MidiMessage,
         timestamp_microseconds: u64,
     ) -> Result<(), String> {
-        // FIXME: We may want to filter messages here (e.g. skip active sensing messages)
-        if self.state == RecorderState::Recording {
-            self.buffer.push(TimeStampedMidiMessage {
-                timestamp_microseconds,
-                message,
-            });
-            Ok(())
+        if self.state != RecorderState::Stopped && !self.input_filter.accepts(&message) {
+            return Ok(());
+        }
+
+        match self.state {
+            RecorderState::Recording => {
+                let timestamp_microseconds =
+                    timestamp_microseconds.saturating_sub(self.paused_microseconds);
+                if let Some(alignment) = &self.alignment
+                    && self.origin_timestamp.is_none()
+                {
+                    let now = Instant::now();
+                    let origin = alignment.origin;
+                    if let Some(grid) = &alignment.grid
+                        && now + grid.get_beat_duration() / 2 < origin
+                    {
+                        return Ok(());
+                    }
+                    self.origin_timestamp = Some(if now >= origin {
+                        timestamp_microseconds.saturating_sub((now - origin).as_micros() as u64)
+                    } else {
+                        timestamp_microseconds + (origin - now).as_micros() as u64
+                    });
+                }
+
+                self.track_input(&message);
+                self.record(TimeStampedMidiMessage {
+                    timestamp_microseconds,
+                    message,
+                });
+                Ok(())
+            }
+            // keep track of the held notes, so they can be reopened on resume
+            RecorderState::Paused => {
+                self.track_input(&message);
+                Ok(())
+            }
+            RecorderState::Stopped => Err("Recorder is not currently recording".to_string()),
+        }
+    }
+
+    /// Makes a take of a loop recording its messages.
+    pub fn pick_take(&mut self, index: usize, take: usize) -> Result<(), String> {
+        let mut recording = self.get_recording(index)?.clone();
+        recording.pick_take(take)?;
+        self.update_recording(index, recording)
+    }
+
+    /// Puts the messages of a loop recording together from ranges of its takes.
+    pub fn comp_takes(&mut self, index: usize, segments: Vec<CompSegment>) -> Result<(), String> {
+        let mut recording = self.get_recording(index)?.clone();
+        recording.comp_takes(segments)?;
+        self.update_recording(index, recording)
+    }
+
+    fn get_recording(&self, index: usize) -> Result<&Recording, String> {
+        self.recordings
+            .get(index)
+            .ok_or_else(|| "Recording index out of bounds".to_string())
+    }
+
+    fn update_recording(&mut self, index: usize, recording: Recording) -> Result<(), String> {
+        if let Some(library) = &self.library {
+            library.save(&recording)?;
+        }
+        self.recordings[index] = recording;
+
+        Ok(())
+    }
+
+    fn update_metadata(&mut self, index: usize, metadata: RecordingMetadata) -> Result<(), String> {
+        if let Some(library) = &self.library {
+            library.save_metadata(&metadata)?;
+        }
+        self.recordings[index].metadata = metadata;
+
+        Ok(())
+    }
+
+    /// Replaces the punch range of a recording with a take, which starts with the playback
+    /// of the recording.
+    ///
+    /// Returns whether the changed recording was saved; when the recording is gone meanwhile,
+    /// the take is kept as a new recording instead.
+    fn punch_recording(
+        &mut self,
+        id: &str,
+        punch: &PunchRange,
+        take: &[TimeStampedMidiMessage],
+    ) -> bool {
+        let Some(index) = self
+            .recordings
+            .iter()
+            .position(|recording| recording.metadata.id == id)
+        else {
+            return self.add_recording(take.to_vec(), None);
+        };
+
+        let recording = &mut self.recordings[index];
+        // the playback starts with the first message of the recording
+        let offset = recording
+            .messages
+            .first()
+            .map_or(0, |message| message.timestamp_microseconds);
+        let take: Vec<TimeStampedMidiMessage> = take
+            .iter()
+            .map(|message| TimeStampedMidiMessage {
+                timestamp_microseconds: message.timestamp_microseconds + offset,
+                message: message.message.clone(),
+            })
+            .collect();
+        recording.replace_range(
+            offset + punch.start.as_micros() as u64,
+            offset + punch.end.as_micros() as u64,
+            &take,
+        );
+
+        match &self.library {
+            Some(library) => library
+                .save(recording)
+                .inspect_err(|e| eprintln!("Failed to save recording to the library: {}", e))
+                .is_ok(),
+            None => false,
+        }
+    }
+
+    /// Keeps a new recording and saves it to the library, if one is open.
+    ///
+    /// Returns whether the recording was saved.
+    fn add_recording(
+        &mut self,
+        messages: Vec<TimeStampedMidiMessage>,
+        alignment: Option<RecordingAlignment>,
+    ) -> bool {
+        let mut recording = Recording::new(
+            format!("Recording {}", self.recordings.len() + 1),
+            messages,
+            self.input_device.clone(),
+        );
+        if let Some(alignment) = alignment {
+            recording.metadata.grid = alignment.grid;
+            recording.metadata.overdub_of = alignment.overdub_of;
+        }
+        self.keep_recording(recording)
+    }
+
+    /// Keeps a recording made over a looped playback with one take per pass.
+    ///
+    /// Returns whether the recording was saved.
+    fn add_loop_recording(
+        &mut self,
+        messages: Vec<TimeStampedMidiMessage>,
+        alignment: RecordingAlignment,
+    ) -> bool {
+        let takes = match &alignment.loop_region {
+            Some(loop_region) => split_loop_passes(
+                &messages,
+                loop_region.start.as_micros() as u64,
+                loop_region.end.as_micros() as u64,
+            ),
+            None => Vec::new(),
+        };
+        let recording = Recording::from_takes(
+            format!("Recording {}", self.recordings.len() + 1),
+            takes,
+            self.input_device.clone(),
+        );
+        let Ok(mut recording) = recording else {
+            // nothing was played inside the loop
+            return self.add_recording(messages, Some(alignment));
+        };
+        recording.metadata.overdub_of = alignment.overdub_of;
+        self.keep_recording(recording)
+    }
+
+    /// Adds a recording to the list and saves it to the library, if one is open.
+    ///
+    /// Returns whether the recording was saved.
+    fn keep_recording(&mut self, mut recording: Recording) -> bool {
+        // the id is made from the creation time, which is not unique when saving quickly
+        let id = recording.metadata.id.clone();
+        let mut number = self.recordings.len() + 1;
+        while self.is_id_taken(&recording.metadata.id) {
+            recording.metadata.id = format!("{}-{}", id, number);
+            number += 1;
+        }
+        let is_saved = match &self.library {
+            Some(library) => library
+                .save(&recording)
+                .inspect_err(|e| eprintln!("Failed to save recording to the library: {}", e))
+                .is_ok(),
+            None => false,
+        };
+        self.recordings.push(recording);
+
+        is_saved
+    }
+
+    /// Whether a recording with the id exists, so that saving with it would overwrite another.
+    fn is_id_taken(&self, id: &str) -> bool {
+        self.recordings
+            .iter()
+            .any(|existing| existing.metadata.id == id)
+            || self
+                .library
+                .as_ref()
+                .is_some_and(|library| library.contains(id))
+    }
+
+    /// Keeps the recordings ordered by creation time, recovered ones are older than the rest.
+    fn insert_recording(&mut self, recording: Recording) {
+        let index = self.recordings.partition_point(|existing| {
+            existing.metadata.created_milliseconds <= recording.metadata.created_milliseconds
+        });
+        self.recordings.insert(index, recording);
+    }
+
+    /// Adds a message to the recording and its journal.
+    fn record(&mut self, message: TimeStampedMidiMessage) {
+        if let Some(journal) = &self.journal {
+            journal.append(message.clone());
+        }
+        self.buffer.push(message);
+    }
+
+    fn create_journal(&self) -> Option<RecordingJournal> {
+        let library = self.library.as_ref()?;
+        let created_milliseconds = get_current_milliseconds();
+        let header = JournalHeader {
+            id: format!("recording-{}", created_milliseconds),
+            created_milliseconds,
+            input_device: self.input_device.clone(),
+        };
+
+        library
+            .create_journal(&header)
+            .inspect_err(|e| eprintln!("Failed to create recording journal: {}", e))
+            .ok()
+    }
+
+    fn track_input(&mut self, message: &MidiMessage) {
+        let MidiMessage::Channel {
+            channel,
+            message: ChannelMessage::Voice(message),
+        } = message
+        else {
+            return;
+        };
+        let channel = u8::from(channel.clone());
+
+        match message {
+            ChannelVoiceMessage::NoteOn { note, velocity } if *velocity > 0 => {
+                self.held_notes.insert((channel, *note), *velocity);
+            }
+            ChannelVoiceMessage::NoteOn { note, .. }
+            | ChannelVoiceMessage::NoteOff { note, .. } => {
+                self.held_notes.remove(&(channel, *note));
+            }
+            _ => {}
+        }
+    }
+
+    fn trim_capture_buffer(&mut self, timestamp_microseconds: u64) {
+        let window = self.capture_minutes as u64 * 60 * 1_000_000;
+        while self
+            .capture_buffer
+            .front()
+            .is_some_and(|message| message.timestamp_microseconds + window < timestamp_microseconds)
+        {
+            self.capture_buffer.pop_front();
+        }
+    }
+
+    /// The input timestamp of this very moment, extrapolated from the last message.
+    fn get_input_timestamp(&self) -> Option<u64> {
+        self.last_input.map(|(timestamp_microseconds, instant)| {
+            timestamp_microseconds + instant.elapsed().as_micros() as u64
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::midi::message::SystemRealTimeMessage;
+
+    fn note(note: u8, velocity: u8) -> MidiMessage {
+        if velocity > 0 {
+            MidiMessage::note_on(MidiChannel::Channel1, note, velocity).unwrap()
         } else {
-            return Err("Recorder is not currently recording".to_string());
+            MidiMessage::note_off(MidiChannel::Channel1, note, 0).unwrap()
+        }
+    }
+
+    /// Passes a message to the recorder the way the input connection does.
+    fn receive(recorder: &mut MidiRecorder, timestamp_microseconds: u64, message: MidiMessage) {
+        recorder.capture_message(message.clone(), timestamp_microseconds);
+        if recorder.get_state() != RecorderState::Stopped {
+            recorder
+                .add_message(message, timestamp_microseconds)
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn pause_leaves_out_the_time_in_between() {
+        let mut recorder = MidiRecorder::default();
+        recorder.start_recording().unwrap();
+        receive(&mut recorder, 1_000_000, note(60, 100));
+        receive(&mut recorder, 2_000_000, note(60, 0));
+        recorder.pause_recording().unwrap();
+        // played while paused, not recorded
+        receive(&mut recorder, 5_000_000, note(62, 100));
+        receive(&mut recorder, 6_000_000, note(62, 0));
+        receive(&mut recorder, 10_000_000, note(64, 100));
+        recorder.resume_recording().unwrap();
+        receive(&mut recorder, 10_500_000, note(64, 0));
+        recorder.stop_recording().unwrap();
+
+        let messages = &recorder.get_recordings()[0].messages;
+        let recorded: Vec<&MidiMessage> = messages.iter().map(|message| &message.message).collect();
+        assert_eq!(
+            recorded,
+            vec![&note(60, 100), &note(60, 0), &note(64, 100), &note(64, 0)]
+        );
+        // the note held on resume starts where the recording was paused
+        let pause_timestamp = messages[2].timestamp_microseconds;
+        assert!((2_000_000..2_001_000).contains(&pause_timestamp));
+        let gap = messages[3].timestamp_microseconds - pause_timestamp;
+        assert!((499_000..=500_000).contains(&gap));
+    }
+
+    #[test]
+    fn aligned_recordings_cannot_be_paused() {
+        let mut recorder = MidiRecorder::default();
+        recorder
+            .start_aligned_recording(RecordingAlignment {
+                origin: Instant::now(),
+                grid: None,
+                overdub_of: Some(OverdubSource::Recording {
+                    id: "recording-1".to_string(),
+                }),
+                punch: None,
+                loop_region: None,
+            })
+            .unwrap();
+
+        assert!(!recorder.can_pause());
+        assert!(recorder.pause_recording().is_err());
+        assert_eq!(recorder.get_state(), RecorderState::Recording);
+    }
+
+    #[test]
+    fn notes_held_across_a_pause_are_closed_and_reopened() {
+        let mut recorder = MidiRecorder::default();
+        recorder.start_recording().unwrap();
+        receive(&mut recorder, 1_000_000, note(60, 100));
+        receive(&mut recorder, 2_000_000, note(64, 90));
+        recorder.pause_recording().unwrap();
+        receive(&mut recorder, 4_000_000, note(64, 0));
+        recorder.resume_recording().unwrap();
+        receive(&mut recorder, 4_250_000, note(60, 0));
+        recorder.stop_recording().unwrap();
+
+        let messages = &recorder.get_recordings()[0].messages;
+        let recorded: Vec<&MidiMessage> = messages.iter().map(|message| &message.message).collect();
+        assert_eq!(
+            recorded,
+            vec![
+                &note(60, 100),
+                &note(64, 90),
+                &note(60, 0),
+                &note(64, 0),
+                &note(60, 100),
+                &note(60, 0),
+            ]
+        );
+        let pause_timestamp = messages[2].timestamp_microseconds;
+        assert_eq!(messages[3].timestamp_microseconds, pause_timestamp);
+        assert_eq!(messages[4].timestamp_microseconds, pause_timestamp);
+        let gap = messages[5].timestamp_microseconds - pause_timestamp;
+        assert!((249_000..=250_000).contains(&gap));
+    }
+
+    /// An empty library in a directory of its own, removed again when the test ends.
+    struct TemporaryLibrary(std::path::PathBuf);
+
+    impl TemporaryLibrary {
+        fn new(name: &str) -> TemporaryLibrary {
+            let directory =
+                std::env::temp_dir().join(format!("midirc-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&directory);
+            TemporaryLibrary(directory)
+        }
+
+        fn open(&self) -> RecordingLibrary {
+            RecordingLibrary::new(self.0.clone())
         }
     }
+
+    impl Drop for TemporaryLibrary {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn recording(id: &str) -> Recording {
+        let mut recording = Recording::new(
+            "Recording".to_string(),
+            vec![TimeStampedMidiMessage {
+                timestamp_microseconds: 0,
+                message: note(60, 100),
+            }],
+            None,
+        );
+        recording.metadata.id = id.to_string();
+        recording
+    }
+
+    #[test]
+    fn kept_recordings_never_reuse_an_id() {
+        let directory = TemporaryLibrary::new("unique-ids");
+        let mut recorder = MidiRecorder::default();
+        recorder.open_library(directory.open()).unwrap();
+        // saved meanwhile, but not loaded
+        directory.open().save(&recording("recording-1")).unwrap();
+        directory.open().save(&recording("recording-1-2")).unwrap();
+
+        assert!(recorder.keep_recording(recording("recording-1")));
+        assert!(recorder.keep_recording(recording("recording-1")));
+        assert!(recorder.keep_recording(recording("recording-1")));
+
+        let ids: Vec<&str> = recorder
+            .get_recordings()
+            .iter()
+            .map(|recording| recording.metadata.id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["recording-1-1", "recording-1-3", "recording-1-4"]);
+        assert_eq!(directory.open().load().unwrap().len(), 5);
+    }
+
+    #[test]
+    fn unfinished_recording_is_recovered_from_its_journal() {
+        let directory = TemporaryLibrary::new("journal");
+        let mut recorder = MidiRecorder::default();
+        recorder.open_library(directory.open()).unwrap();
+        recorder.start_recording().unwrap();
+        receive(&mut recorder, 1_000_000, note(60, 100));
+        receive(&mut recorder, 1_500_000, note(60, 0));
+        // the app ends without stopping the recording
+        drop(recorder);
+
+        let mut recorder = MidiRecorder::default();
+        recorder.open_library(directory.open()).unwrap();
+        let journals = recorder.get_unfinished_journals();
+        assert_eq!(journals.len(), 1);
+        let id = journals[0].header.id.clone();
+        assert_eq!(
+            journals[0].messages,
+            vec![
+                TimeStampedMidiMessage {
+                    timestamp_microseconds: 1_000_000,
+                    message: note(60, 100),
+                },
+                TimeStampedMidiMessage {
+                    timestamp_microseconds: 1_500_000,
+                    message: note(60, 0),
+                },
+            ]
+        );
+
+        recorder.recover_recording(0).unwrap();
+        assert!(recorder.get_unfinished_journals().is_empty());
+        assert_eq!(recorder.get_recordings()[0].metadata.id, id);
+
+        // the recovered recording replaces the journal in the library
+        let mut recorder = MidiRecorder::default();
+        recorder.open_library(directory.open()).unwrap();
+        assert!(recorder.get_unfinished_journals().is_empty());
+        assert_eq!(
+            recorder.get_recordings()[0].messages.len(),
+            2,
+            "recovered recording was not saved"
+        );
+    }
+
+    fn captured(recorder: &MidiRecorder) -> Vec<u64> {
+        let messages = &recorder.get_recordings().last().unwrap().messages;
+        messages
+            .iter()
+            .map(|message| message.timestamp_microseconds)
+            .collect()
+    }
+
+    #[test]
+    fn capture_buffer_keeps_only_the_last_minutes() {
+        let mut recorder = MidiRecorder::default();
+        recorder.set_capture_minutes(1).unwrap();
+        receive(&mut recorder, 0, note(60, 100));
+        receive(&mut recorder, 30_000_000, note(60, 0));
+        receive(&mut recorder, 61_000_000, note(62, 100));
+
+        recorder
+            .save_capture(CaptureSelection::LastSeconds { seconds: 120 })
+            .unwrap();
+        assert_eq!(captured(&recorder), vec![30_000_000, 61_000_000]);
+    }
+
+    #[test]
+    fn last_seconds_selects_the_end_of_the_capture_buffer() {
+        let mut recorder = MidiRecorder::default();
+        receive(&mut recorder, 1_000_000, note(60, 100));
+        receive(&mut recorder, 5_000_000, note(60, 0));
+        receive(&mut recorder, 8_000_000, note(62, 100));
+        receive(&mut recorder, 9_500_000, note(62, 0));
+
+        recorder
+            .save_capture(CaptureSelection::LastSeconds { seconds: 2 })
+            .unwrap();
+        assert_eq!(captured(&recorder), vec![8_000_000, 9_500_000]);
+    }
+
+    #[test]
+    fn last_phrase_starts_after_the_last_idle_gap() {
+        let mut recorder = MidiRecorder::default();
+        recorder.set_input_filter(InputFilter {
+            active_sensing: false,
+            ..Default::default()
+        });
+        let active_sensing = || {
+            MidiMessage::System(SystemMessage::RealTime(
+                SystemRealTimeMessage::ActiveSensing,
+            ))
+        };
+        receive(&mut recorder, 1_000_000, note(60, 100));
+        receive(&mut recorder, 2_000_000, note(60, 0));
+        // real-time messages do not fill the gap
+        receive(&mut recorder, 3_500_000, active_sensing());
+        receive(&mut recorder, 5_000_000, note(62, 100));
+        // just short of the gap
+        receive(&mut recorder, 7_999_999, note(62, 0));
+        receive(&mut recorder, 8_500_000, active_sensing());
+
+        recorder.save_capture(CaptureSelection::LastPhrase).unwrap();
+        assert_eq!(captured(&recorder), vec![5_000_000, 7_999_999, 8_500_000]);
+    }
+
+    #[test]
+    fn last_phrase_needs_a_played_message() {
+        let mut recorder = MidiRecorder::default();
+        assert!(recorder.save_capture(CaptureSelection::LastPhrase).is_err());
+        assert!(recorder.get_recordings().is_empty());
+    }
 }
@@ -0,0 +1,324 @@
+// RIFF MIDI (RMID) files wrap a Standard MIDI File in a RIFF container, e.g. `.rmi` files.
+// https://www.loc.gov/preservation/digital/formats/fdd/fdd000120.shtml
+
+use std::ops::Range;
+
+use super::smf::{MidiFileError, MidiFileErrorKind};
+
+pub const RMID_FILE_EXTENSION: &str = "rmi";
+
+const RIFF_CHUNK_ID: &[u8; 4] = b"RIFF";
+const RMID_FORM_TYPE: &[u8; 4] = b"RMID";
+const DATA_CHUNK_ID: &[u8; 4] = b"data";
+const LIST_CHUNK_ID: &[u8; 4] = b"LIST";
+const INFO_LIST_TYPE: &[u8; 4] = b"INFO";
+const TITLE_CHUNK_ID: &[u8; 4] = b"INAM";
+const ARTIST_CHUNK_ID: &[u8; 4] = b"IART";
+const COPYRIGHT_CHUNK_ID: &[u8; 4] = b"ICOP";
+
+/// Metadata from the INFO list of an RMID file.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RmidInfo {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub copyright: Option<String>,
+}
+
+impl RmidInfo {
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.artist.is_none() && self.copyright.is_none()
+    }
+}
+
+/// Where the Standard MIDI File is in an RMID file, and what else the container holds.
+#[derive(Debug)]
+pub struct RmidContents {
+    pub smf_range: Range<usize>,
+    pub info: RmidInfo,
+    /// Problems with the container that could be worked around, with their byte offsets.
+    pub problems: Vec<(MidiFileErrorKind, usize)>,
+}
+
+pub fn is_rmid(data: &[u8]) -> bool {
+    data.len() >= 12 && data.starts_with(RIFF_CHUNK_ID) && &data[8..12] == RMID_FORM_TYPE
+}
+
+/// Finds the Standard MIDI File and the INFO metadata in an RMID file.
+///
+/// Other chunks, e.g. an embedded DLS collection, are skipped.
+pub fn read_rmid(data: &[u8]) -> Result<RmidContents, MidiFileError> {
+    if !is_rmid(data) {
+        return Err(MidiFileError::new(
+            MidiFileErrorKind::NotAMidiFile,
+            Some(0),
+            None,
+        ));
+    }
+
+    let mut problems = Vec::new();
+    let riff_size = read_u32_le(data, 4) as usize;
+    let available = data.len() - 8;
+    if riff_size != available {
+        problems.push((
+            MidiFileErrorKind::InvalidRiffSize {
+                size: riff_size,
+                available,
+            },
+            4,
+        ));
+    }
+    let end = (8 + riff_size).min(data.len());
+
+    let mut smf_range = None;
+    let mut info = RmidInfo::default();
+    for (id, range) in read_chunks(data, 12, end, &mut problems) {
+        if &id == DATA_CHUNK_ID && smf_range.is_none() {
+            smf_range = Some(range);
+        } else if &id == LIST_CHUNK_ID && data[range.clone()].starts_with(INFO_LIST_TYPE) {
+            for (id, range) in read_chunks(data, range.start + 4, range.end, &mut problems) {
+                let value = read_info_string(&data[range]);
+                match &id {
+                    TITLE_CHUNK_ID => info.title = value,
+                    ARTIST_CHUNK_ID => info.artist = value,
+                    COPYRIGHT_CHUNK_ID => info.copyright = value,
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    let smf_range = smf_range
+        .ok_or_else(|| MidiFileError::new(MidiFileErrorKind::MissingRmidData, Some(12), None))?;
+
+    Ok(RmidContents {
+        smf_range,
+        info,
+        problems,
+    })
+}
+
+/// Wraps a Standard MIDI File in an RMID file, with the metadata as INFO list.
+pub fn write_rmid(smf: &[u8], info: &RmidInfo) -> Vec<u8> {
+    let mut chunks = Vec::new();
+    write_chunk(&mut chunks, DATA_CHUNK_ID, smf);
+
+    if !info.is_empty() {
+        let mut list = INFO_LIST_TYPE.to_vec();
+        for (id, value) in [
+            (TITLE_CHUNK_ID, &info.title),
+            (ARTIST_CHUNK_ID, &info.artist),
+            (COPYRIGHT_CHUNK_ID, &info.copyright),
+        ] {
+            if let Some(value) = value {
+                // INFO strings end with a NUL byte
+                let mut text = value.as_bytes().to_vec();
+                text.push(0x00);
+                write_chunk(&mut list, id, &text);
+            }
+        }
+        write_chunk(&mut chunks, LIST_CHUNK_ID, &list);
+    }
+
+    let mut data = RIFF_CHUNK_ID.to_vec();
+    data.extend_from_slice(&((RMID_FORM_TYPE.len() + chunks.len()) as u32).to_le_bytes());
+    data.extend_from_slice(RMID_FORM_TYPE);
+    data.extend(chunks);
+    data
+}
+
+fn read_u32_le(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+/// Returns the ids and data ranges of the chunks between the offsets.
+fn read_chunks(
+    data: &[u8],
+    start: usize,
+    end: usize,
+    problems: &mut Vec<(MidiFileErrorKind, usize)>,
+) -> Vec<([u8; 4], Range<usize>)> {
+    let mut chunks = Vec::new();
+    let mut offset = start;
+    while offset + 8 <= end {
+        let id: [u8; 4] = data[offset..offset + 4].try_into().unwrap();
+        let size = read_u32_le(data, offset + 4) as usize;
+        let data_start = offset + 8;
+        if data_start + size > end {
+            problems.push((
+                MidiFileErrorKind::InvalidRiffSize {
+                    size,
+                    available: end - data_start,
+                },
+                offset + 4,
+            ));
+        }
+        let data_end = (data_start + size).min(end);
+        chunks.push((id, data_start..data_end));
+
+        // chunks are padded to an even size
+        offset = data_end + (size & 1);
+    }
+
+    chunks
+}
+
+fn read_info_string(data: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(data);
+    let text = text.trim_end_matches('\0').trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+fn write_chunk(data: &mut Vec<u8>, id: &[u8; 4], chunk_data: &[u8]) {
+    data.extend_from_slice(id);
+    data.extend_from_slice(&(chunk_data.len() as u32).to_le_bytes());
+    data.extend_from_slice(chunk_data);
+    if chunk_data.len() % 2 == 1 {
+        data.push(0x00);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::midi::smf::{MidiFile, MidiWriterConfig, ParseMode};
+
+    const SMF_EXAMPLE: &[u8] = &[
+        0x4D, 0x54, 0x68, 0x64, // MThd
+        0x00, 0x00, 0x00, 0x06, // chunk length
+        0x00, 0x00, // format 0
+        0x00, 0x01, // one track
+        0x00, 0x60, // division: 96 ticks per quarter note
+        0x4D, 0x54, 0x72, 0x6B, // MTrk
+        0x00, 0x00, 0x00, 0x0C, // chunk length
+        0x00, 0x90, 0x3C, 0x40, // note on: channel 0, note 60, velocity 64
+        0x60, 0x80, 0x3C, 0x40, // note off: channel 0, note 60, velocity 64
+        0x00, 0xFF, 0x2F, 0x00, // end of track
+    ];
+
+    /// An RMID file with the SMF, an embedded DLS collection and an INFO list.
+    fn rmid_with_other_chunks(smf: &[u8]) -> Vec<u8> {
+        let mut data = b"RIFF\x00\x00\x00\x00RMIDdata".to_vec();
+        data.extend_from_slice(&(smf.len() as u32).to_le_bytes());
+        data.extend_from_slice(smf);
+        data.extend_from_slice(b"RIFF\x04\x00\x00\x00DLS ");
+        data.extend_from_slice(b"LIST\x12\x00\x00\x00INFOINAM\x05\x00\x00\x00Song\x00\x00");
+        let riff_size = data.len() as u32 - 8;
+        data[4..8].copy_from_slice(&riff_size.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn write_then_read_keeps_smf_and_info() {
+        let info = RmidInfo {
+            title: Some("Example".to_string()),
+            artist: Some("Band".to_string()),
+            copyright: None,
+        };
+
+        let data = write_rmid(SMF_EXAMPLE, &info);
+        assert!(is_rmid(&data));
+        assert_eq!(&data[8..16], b"RMIDdata");
+
+        let contents = read_rmid(&data).unwrap();
+        assert_eq!(&data[contents.smf_range], SMF_EXAMPLE);
+        assert_eq!(contents.info, info);
+        assert_eq!(contents.problems, vec![]);
+    }
+
+    #[test]
+    fn midi_file_round_trip_keeps_info() {
+        let mut midi_file = MidiFile::try_from(SMF_EXAMPLE).unwrap();
+        midi_file.set_info(RmidInfo {
+            title: Some("Example".to_string()),
+            artist: None,
+            copyright: Some("2024".to_string()),
+        });
+
+        let data = midi_file
+            .to_rmid_bytes(&MidiWriterConfig::default())
+            .unwrap();
+        assert_eq!(MidiFile::try_from(data.as_slice()).unwrap(), midi_file);
+    }
+
+    #[test]
+    fn read_skips_other_chunks() {
+        let data = rmid_with_other_chunks(SMF_EXAMPLE);
+
+        let contents = read_rmid(&data).unwrap();
+        assert_eq!(&data[contents.smf_range], SMF_EXAMPLE);
+        assert_eq!(contents.info.title.as_deref(), Some("Song"));
+        assert_eq!(contents.problems, vec![]);
+    }
+
+    #[test]
+    fn read_requires_a_data_chunk() {
+        let data = write_rmid(SMF_EXAMPLE, &RmidInfo::default());
+        let mut data = data[..12].to_vec();
+        data[4..8].copy_from_slice(&4u32.to_le_bytes());
+
+        let error = read_rmid(&data).unwrap_err();
+        assert_eq!(error.kind, MidiFileErrorKind::MissingRmidData);
+    }
+
+    #[test]
+    fn read_chunks_skips_the_padding_of_odd_sizes() {
+        let data = b"abcd\x03\x00\x00\x00xyz\x00efgh\x01\x00\x00\x00z\x00";
+        let mut problems = Vec::new();
+
+        let chunks = read_chunks(data, 0, data.len(), &mut problems);
+        assert_eq!(chunks, vec![(*b"abcd", 8..11), (*b"efgh", 20..21)]);
+        assert_eq!(problems, vec![]);
+    }
+
+    #[test]
+    fn read_chunks_cuts_oversized_chunks_at_the_end() {
+        let data = b"abcd\x02\x00\x00\x00xyefgh\x64\x00\x00\x00wxyz";
+        let mut problems = Vec::new();
+
+        let chunks = read_chunks(data, 0, data.len(), &mut problems);
+        assert_eq!(chunks, vec![(*b"abcd", 8..10), (*b"efgh", 18..22)]);
+        assert_eq!(
+            problems,
+            vec![(
+                MidiFileErrorKind::InvalidRiffSize {
+                    size: 100,
+                    available: 4,
+                },
+                14,
+            )]
+        );
+    }
+
+    #[test]
+    fn lenient_parse_reports_problems_at_offsets_in_the_rmid_file() {
+        let mut smf = SMF_EXAMPLE.to_vec();
+        // the track chunk length is 4 bytes too long
+        smf[21] = 0x10;
+        let mut data = rmid_with_other_chunks(&smf);
+        // the RIFF size is 2 bytes too large
+        let riff_size = data.len() as u32 - 6;
+        data[4..8].copy_from_slice(&riff_size.to_le_bytes());
+
+        let error = MidiFile::try_from(data.as_slice()).unwrap_err();
+        assert_eq!(
+            error.kind,
+            MidiFileErrorKind::InvalidRiffSize {
+                size: riff_size as usize,
+                available: data.len() - 8,
+            }
+        );
+
+        let (midi_file, warnings) = MidiFile::parse(&data, ParseMode::Lenient).unwrap();
+        assert_eq!(midi_file.get_info().title.as_deref(), Some("Song"));
+        assert_eq!(midi_file.get_tracks().len(), 1);
+        // the RIFF size and the track length of the SMF, which starts at byte 20
+        assert_eq!(
+            warnings
+                .iter()
+                .map(|warning| warning.offset)
+                .collect::<Vec<_>>(),
+            vec![4, 20 + 18]
+        );
+    }
+}
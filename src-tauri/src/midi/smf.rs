@@ -1,10 +1,21 @@
-use std::ops::{Deref, DerefMut};
+use std::{
+    collections::HashMap,
+    ops::{Deref, DerefMut},
+};
 
-use super::message::{MidiChannel, MidiMessage, TimeStampedMidiMessage};
+use super::rmid::{RmidInfo, is_rmid, read_rmid, write_rmid};
+
+use super::message::{
+    ChannelMessage, ChannelModeMessage, ChannelVoiceMessage, MessageError, MidiChannel,
+    MidiMessage, SystemMessage, TimeStampedMidiMessage,
+};
 
 const MIDI_HEADER_CHUNK_ASCII_TYPE: &[u8; 4] = b"MThd";
 const MIDI_TRACK_CHUNK_ASCII_TYPE: &[u8; 4] = b"MTrk";
 
+/// Tempo assumed by the SMF specification until the first Set Tempo event (120 BPM).
+pub const DEFAULT_TEMPO: u32 = 500_000;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum MidiFormat {
     SingleMultiChannelTrack,
@@ -75,10 +86,54 @@ pub enum MetaEvent {
 #[derive(Debug, Clone, PartialEq)]
 pub enum Event {
     MidiEvent(MidiMessage),
-    SysExEvent,
+    /// `F0` event with the bytes following the status. The last byte is `F7` unless the
+    /// message is split and continued by `SysExContinuation` events.
+    SysEx(Vec<u8>),
+    /// `F7` event: the next packet of a split System Exclusive message, or otherwise
+    /// any bytes to be sent as they are (an "escape", e.g. for real-time messages).
+    SysExContinuation(Vec<u8>),
     MetaEvent(MetaEvent),
 }
 
+/// Joins the packets of split System Exclusive events into the messages sent to a device.
+#[derive(Debug, Default)]
+pub struct SysExAssembler {
+    // the packets of a split message received so far, starting with F0
+    pending: Option<Vec<u8>>,
+}
+
+impl SysExAssembler {
+    /// Returns the bytes to send once an event completes a message or is an escape;
+    /// other events are ignored.
+    pub fn push(&mut self, event: &Event) -> Option<Vec<u8>> {
+        let message = match (event, self.pending.take()) {
+            (Event::SysEx(data), _) => {
+                let mut message = vec![0xF0];
+                message.extend_from_slice(data);
+                message
+            }
+            (Event::SysExContinuation(data), Some(mut message)) => {
+                message.extend_from_slice(data);
+                message
+            }
+            (Event::SysExContinuation(data), None) => {
+                return (!data.is_empty()).then(|| data.clone());
+            }
+            (_, pending) => {
+                self.pending = pending;
+                return None;
+            }
+        };
+
+        if message.last() == Some(&0xF7) {
+            Some(message)
+        } else {
+            self.pending = Some(message);
+            None
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct MidiTrackEvent {
     pub delta_time: u32,
@@ -93,6 +148,14 @@ pub struct MidiHeader {
 }
 
 impl MidiHeader {
+    pub fn new(format: MidiFormat, num_tracks: u16, division: MidiDivision) -> MidiHeader {
+        MidiHeader {
+            format,
+            num_tracks,
+            division,
+        }
+    }
+
     pub fn single_multi_channel_track() -> MidiHeader {
         MidiHeader {
             format: MidiFormat::SingleMultiChannelTrack,
@@ -140,44 +203,101 @@ impl MidiTrack {
             return MidiTrack(vec![]);
         }
 
-        let mut normalized_data = data.clone();
+        let start_timestamp = data
+            .iter()
+            .map(|msg| msg.timestamp_microseconds)
+            .min()
+            .unwrap_or_default();
+        Self::from_time_stamped_messages_at(
+            data,
+            start_timestamp,
+            vec![MetaEvent::SetTempo(tempo)],
+            tempo,
+            division,
+        )
+    }
+
+    /// Creates a track that starts at the given timestamp, with the meta events placed at
+    /// its start. Messages before the start are moved to it.
+    ///
+    /// The tempo is used to convert the timestamps and should match the Set Tempo event.
+    pub fn from_time_stamped_messages_at(
+        data: Vec<TimeStampedMidiMessage>,
+        start_timestamp: u64,
+        meta_events: Vec<MetaEvent>,
+        tempo: u32,
+        division: &MidiDivision,
+    ) -> MidiTrack {
+        let mut normalized_data = data;
         normalized_data.sort_by_key(|msg| msg.timestamp_microseconds);
-        let start_timestamp = normalized_data.first().unwrap().timestamp_microseconds;
-        normalized_data.iter_mut().for_each(|msg| {
-            msg.timestamp_microseconds -= start_timestamp;
-        });
-        for index in (1..normalized_data.len()).rev() {
-            normalized_data[index].timestamp_microseconds -=
-                normalized_data[index - 1].timestamp_microseconds;
-        }
 
-        let track_data = std::iter::once(MidiTrackEvent {
-            delta_time: 0,
-            event: Event::MetaEvent(MetaEvent::SetTempo(tempo)),
-        })
-        .chain(normalized_data.into_iter().map(|msg| MidiTrackEvent {
-            delta_time: calc_delta_time(msg.timestamp_microseconds, tempo, division),
-            event: Event::MidiEvent(msg.message),
-        }))
-        .chain(std::iter::once(MidiTrackEvent {
-            delta_time: 0,
-            event: Event::MetaEvent(MetaEvent::EndOfTrack),
-        }))
-        .collect();
+        // convert absolute times, so that rounding errors do not add up over the track
+        let mut previous_tick = 0;
+        let track_data = meta_events
+            .into_iter()
+            .map(|event| MidiTrackEvent {
+                delta_time: 0,
+                event: Event::MetaEvent(event),
+            })
+            .chain(normalized_data.into_iter().map(|msg| {
+                let tick = calc_delta_time(
+                    msg.timestamp_microseconds.saturating_sub(start_timestamp),
+                    tempo,
+                    division,
+                );
+                let delta_time = tick - previous_tick;
+                previous_tick = tick;
+                MidiTrackEvent {
+                    delta_time,
+                    event: Event::MidiEvent(msg.message),
+                }
+            }))
+            .chain(std::iter::once(MidiTrackEvent {
+                delta_time: 0,
+                event: Event::MetaEvent(MetaEvent::EndOfTrack),
+            }))
+            .collect();
 
         MidiTrack(track_data)
     }
+
+    /// Returns the MIDI events of the track with their time since the start of the sequence.
+    ///
+    /// Split System Exclusive messages are joined at the time of their last packet.
+    pub fn to_time_stamped_messages(&self, tempo_map: &TempoMap) -> Vec<TimeStampedMidiMessage> {
+        let mut tick: u64 = 0;
+        let mut sysex = SysExAssembler::default();
+        self.iter()
+            .filter_map(|event| {
+                tick += event.delta_time as u64;
+                let message = match event.event {
+                    Event::MidiEvent(ref message) => message.clone(),
+                    ref event => MidiMessage::try_from(sysex.push(event)?.as_slice()).ok()?,
+                };
+                Some(TimeStampedMidiMessage {
+                    timestamp_microseconds: tempo_map.microseconds_at(tick),
+                    message,
+                })
+            })
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct MidiFile {
     header: MidiHeader,
     tracks: Vec<MidiTrack>,
+    /// Metadata of the RMID file the MIDI file was read from or is written to.
+    info: RmidInfo,
 }
 
 impl MidiFile {
     pub fn new(header: MidiHeader, tracks: Vec<MidiTrack>) -> MidiFile {
-        MidiFile { header, tracks }
+        MidiFile {
+            header,
+            tracks,
+            info: RmidInfo::default(),
+        }
     }
 
     pub fn get_header(&self) -> &MidiHeader {
@@ -187,18 +307,242 @@ impl MidiFile {
     pub fn get_tracks(&self) -> &[MidiTrack] {
         &self.tracks
     }
+
+    pub fn get_info(&self) -> &RmidInfo {
+        &self.info
+    }
+
+    pub fn set_info(&mut self, info: RmidInfo) {
+        self.info = info;
+    }
+
+    /// Returns the MIDI events of all tracks merged in time order.
+    ///
+    /// The sequences of a format 2 file are played one after another.
+    pub fn to_time_stamped_messages(&self) -> Vec<TimeStampedMidiMessage> {
+        Self::merge_track_messages(&self.to_track_messages())
+            .into_iter()
+            .map(|(_, message)| message)
+            .collect()
+    }
+
+    /// Returns the MIDI events of every track with their time since the start of the track,
+    /// along with the time at which the track starts in the file.
+    ///
+    /// All tracks start together, except for the sequences of a format 2 file: these are
+    /// played one after another, each starting at the End of Track of the one before.
+    pub fn to_track_messages(&self) -> Vec<(u64, Vec<TimeStampedMidiMessage>)> {
+        let mut sequence_offset = 0;
+        self.tracks
+            .iter()
+            .enumerate()
+            .map(|(index, track)| {
+                let tempo_map = self.get_tempo_map(index);
+                let start = sequence_offset;
+                if self.header.format == MidiFormat::MultiSequence {
+                    let end_tick = track.iter().map(|event| event.delta_time as u64).sum();
+                    sequence_offset += tempo_map.microseconds_at(end_tick);
+                }
+                (start, track.to_time_stamped_messages(&tempo_map))
+            })
+            .collect()
+    }
+
+    /// Merges the messages of tracks returned by `to_track_messages` in time order,
+    /// each with the index of the track it comes from.
+    pub fn merge_track_messages(
+        tracks: &[(u64, Vec<TimeStampedMidiMessage>)],
+    ) -> Vec<(usize, TimeStampedMidiMessage)> {
+        let mut messages: Vec<(usize, TimeStampedMidiMessage)> = tracks
+            .iter()
+            .enumerate()
+            .flat_map(|(index, (start, track_messages))| {
+                track_messages.iter().map(move |message| {
+                    (
+                        index,
+                        TimeStampedMidiMessage {
+                            timestamp_microseconds: start + message.timestamp_microseconds,
+                            message: message.message.clone(),
+                        },
+                    )
+                })
+            })
+            .collect();
+
+        // stable sort keeps the track order for simultaneous events (e.g. conductor track first)
+        messages.sort_by_key(|(_, message)| message.timestamp_microseconds);
+        messages
+    }
+
+    /// Returns a copy of the file with the messages added as a new track, e.g. to merge
+    /// a part that was recorded along with the file.
+    ///
+    /// The timestamps are moved by the offset and follow the tempo map of the file.
+    /// A single track file becomes a multi track file.
+    pub fn with_track(
+        &self,
+        messages: &[TimeStampedMidiMessage],
+        offset_microseconds: u64,
+        name: &str,
+    ) -> Result<MidiFile, MidiFileError> {
+        let format = match self.header.format {
+            MidiFormat::MultiSequence => {
+                return Err(MidiFileErrorKind::MultiSequenceMerge.into());
+            }
+            _ => MidiFormat::MultiTrackSequence,
+        };
+        let num_tracks =
+            u16::try_from(self.tracks.len() + 1).map_err(|_| MidiFileErrorKind::TooManyTracks)?;
+
+        let tempo_map = self.get_tempo_map(0);
+        let mut messages = messages.to_vec();
+        messages.sort_by_key(|msg| msg.timestamp_microseconds);
+
+        let mut previous_tick = 0;
+        let mut track_data = vec![MidiTrackEvent {
+            delta_time: 0,
+            event: Event::MetaEvent(MetaEvent::TrackName(name.to_string())),
+        }];
+        for msg in messages {
+            let tick = tempo_map.tick_at(msg.timestamp_microseconds + offset_microseconds);
+            let delta_time =
+                u32::try_from(tick - previous_tick).map_err(|_| MidiFileErrorKind::TrackTooLong)?;
+            previous_tick = tick;
+            track_data.push(MidiTrackEvent {
+                delta_time,
+                event: Event::MidiEvent(msg.message),
+            });
+        }
+        track_data.push(MidiTrackEvent {
+            delta_time: 0,
+            event: Event::MetaEvent(MetaEvent::EndOfTrack),
+        });
+
+        let mut tracks = self.tracks.clone();
+        tracks.push(MidiTrack(track_data));
+
+        Ok(MidiFile {
+            header: MidiHeader::new(format, num_tracks, self.header.division.clone()),
+            tracks,
+            info: self.info.clone(),
+        })
+    }
+
+    /// Returns the tempo map that applies to the track at the given index.
+    ///
+    /// Tracks of format 0 and 1 files share one tempo map, whereas every track
+    /// of a format 2 file is an independent sequence with its own tempo changes.
+    pub fn get_tempo_map(&self, index: usize) -> TempoMap {
+        match self.header.format {
+            MidiFormat::MultiSequence => TempoMap::from_tracks(
+                self.tracks.get(index..=index).unwrap_or_default(),
+                &self.header.division,
+            ),
+            _ => TempoMap::from_tracks(&self.tracks, &self.header.division),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct TempoChange {
+    tick: u64,
+    microseconds: u64,
+    tempo: u32,
+}
+
+/// Maps absolute tick positions to wall-clock time using all Set Tempo events of a sequence.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TempoMap {
+    division: MidiDivision,
+    changes: Vec<TempoChange>,
+}
+
+impl TempoMap {
+    /// Builds a tempo map from the Set Tempo events found in the given tracks.
+    ///
+    /// In format 1 files the tempo is usually defined in the first (conductor) track,
+    /// but it is valid to place tempo changes in any track, so all tracks are scanned.
+    pub fn from_tracks(tracks: &[MidiTrack], division: &MidiDivision) -> TempoMap {
+        let mut tempo_events: Vec<(u64, u32)> = Vec::new();
+        for track in tracks {
+            let mut tick: u64 = 0;
+            for event in track.iter() {
+                tick += event.delta_time as u64;
+                if let Event::MetaEvent(MetaEvent::SetTempo(tempo)) = event.event {
+                    tempo_events.push((tick, tempo));
+                }
+            }
+        }
+        // stable sort keeps the track order for tempo changes at the same tick
+        tempo_events.sort_by_key(|(tick, _)| *tick);
+
+        let mut changes = vec![TempoChange {
+            tick: 0,
+            microseconds: 0,
+            tempo: DEFAULT_TEMPO,
+        }];
+        for (tick, tempo) in tempo_events {
+            let last = changes.last_mut().unwrap();
+            if last.tick == tick {
+                last.tempo = tempo;
+                continue;
+            }
+
+            let microseconds = last.microseconds
+                + calc_delta_time_microseconds(tick - last.tick, last.tempo, division);
+            changes.push(TempoChange {
+                tick,
+                microseconds,
+                tempo,
+            });
+        }
+
+        TempoMap {
+            division: division.clone(),
+            changes,
+        }
+    }
+
+    /// Returns the tempo (microseconds per quarter note) in effect at the given tick.
+    pub fn tempo_at(&self, tick: u64) -> u32 {
+        self.change_at(tick).tempo
+    }
+
+    /// Converts an absolute tick position to microseconds since the start of the sequence.
+    pub fn microseconds_at(&self, tick: u64) -> u64 {
+        let change = self.change_at(tick);
+        change.microseconds
+            + calc_delta_time_microseconds(tick - change.tick, change.tempo, &self.division)
+    }
+
+    /// Converts microseconds since the start of the sequence to an absolute tick position.
+    pub fn tick_at(&self, microseconds: u64) -> u64 {
+        let index = self
+            .changes
+            .partition_point(|change| change.microseconds <= microseconds);
+        let change = &self.changes[index - 1];
+        change.tick
+            + calc_delta_time(
+                microseconds - change.microseconds,
+                change.tempo,
+                &self.division,
+            ) as u64
+    }
+
+    fn change_at(&self, tick: u64) -> &TempoChange {
+        let index = self.changes.partition_point(|change| change.tick <= tick);
+        // the first change is always at tick 0, so index is at least 1
+        &self.changes[index - 1]
+    }
 }
 
-fn to_var_length_bytes(value: u32) -> Result<Vec<u8>, String> {
+fn to_var_length_bytes(value: u32) -> Result<Vec<u8>, MidiFileErrorKind> {
     if value < 0x80 {
         return Ok(vec![value as u8]);
     }
 
     if value > 0x0F_FF_FF_FF {
-        return Err(
-            "Value exceeds larges number allowed (0x0FFFFFFF) for variable-length quantity"
-                .to_string(),
-        );
+        return Err(MidiFileErrorKind::VariableLengthOverflow { value });
     }
 
     let mut bytes: Vec<u8> = Vec::new();
@@ -221,18 +565,18 @@ fn to_var_length_bytes(value: u32) -> Result<Vec<u8>, String> {
     Ok(bytes)
 }
 
-fn from_var_length_bytes(bytes: &[u8]) -> Result<u32, String> {
+fn from_var_length_bytes(bytes: &[u8]) -> Result<u32, MidiFileErrorKind> {
     if bytes.is_empty() {
-        return Err("Input byte slice is empty".to_string());
+        return Err(MidiFileErrorKind::InvalidVariableLength);
     }
 
     if bytes.len() > 4 {
-        return Err("Input byte slice exceeds maximum length of 4 bytes".to_string());
+        return Err(MidiFileErrorKind::InvalidVariableLength);
     }
 
     let last_byte = *bytes.last().unwrap();
     if last_byte > 0x7F {
-        return Err("Last byte must not have the MSB set".to_string());
+        return Err(MidiFileErrorKind::InvalidVariableLength);
     }
 
     let mut value: u32 = 0;
@@ -248,7 +592,7 @@ fn from_var_length_bytes(bytes: &[u8]) -> Result<u32, String> {
     Ok(value)
 }
 
-fn get_var_length_bytes_length(bytes: &[u8]) -> Result<usize, String> {
+fn get_var_length_bytes_length(bytes: &[u8]) -> Result<usize, MidiFileErrorKind> {
     let mut length = 0;
     let mut last_byte_valid = false;
 
@@ -260,18 +604,19 @@ fn get_var_length_bytes_length(bytes: &[u8]) -> Result<usize, String> {
         }
     }
 
-    if length == 0 || !last_byte_valid {
-        return Err("No valid variable-length bytes found".to_string());
-    } else if length > 4 {
-        return Err("Variable-length bytes exceed maximum length of 4 bytes".to_string());
+    if length == 0 || !last_byte_valid || length > 4 {
+        return Err(MidiFileErrorKind::InvalidVariableLength);
     }
 
     Ok(length)
 }
 
-fn get_event_length(data: &[u8], running_status_length: Option<u8>) -> Result<usize, String> {
+fn get_event_length(
+    data: &[u8],
+    running_status_length: Option<u8>,
+) -> Result<usize, MidiFileErrorKind> {
     if data.len() == 0 {
-        return Err("Data is empty, cannot determine event length".to_string());
+        return Err(MidiFileErrorKind::TruncatedEvent);
     }
 
     let first_byte = data[0];
@@ -282,21 +627,19 @@ fn get_event_length(data: &[u8], running_status_length: Option<u8>) -> Result<us
             if let Some(length) = running_status_length {
                 length as usize
             } else {
-                return Err("Running status not set, cannot determine event length".to_string());
+                return Err(MidiFileErrorKind::MissingRunningStatus);
             }
         }
         // Channel messages
         0x80..=0xEF => {
             if data.len() < 2 {
-                return Err("Channel message data is too short".to_string());
+                return Err(MidiFileErrorKind::TruncatedEvent);
             }
 
             match first_byte & 0xF0 {
                 0x80 | 0x90 | 0xA0 | 0xB0 | 0xE0 => {
                     if data.len() < 3 {
-                        return Err(
-                            "Channel message data is too short for expected length".to_string()
-                        );
+                        return Err(MidiFileErrorKind::TruncatedEvent);
                     }
 
                     3
@@ -308,56 +651,56 @@ fn get_event_length(data: &[u8], running_status_length: Option<u8>) -> Result<us
         // Meta event
         0xFF => {
             if data.len() < 2 {
-                return Err("Meta event data is too short".to_string());
+                return Err(MidiFileErrorKind::TruncatedEvent);
             }
 
             let meta_type = data[1];
             match meta_type {
                 0x00 => {
                     if data.len() < 5 {
-                        return Err("Sequence number meta event data is too short".to_string());
+                        return Err(MidiFileErrorKind::TruncatedEvent);
                     }
 
                     5
                 }
                 0x20 => {
                     if data.len() < 4 {
-                        return Err("MIDI Channel Prefix meta event data is too short".to_string());
+                        return Err(MidiFileErrorKind::TruncatedEvent);
                     }
 
                     4
                 }
                 0x2F => {
                     if data.len() < 3 {
-                        return Err("End of Track meta event data is too short".to_string());
+                        return Err(MidiFileErrorKind::TruncatedEvent);
                     }
 
                     3
                 }
                 0x51 => {
                     if data.len() < 6 {
-                        return Err("Set Tempo meta event data is too short".to_string());
+                        return Err(MidiFileErrorKind::TruncatedEvent);
                     }
 
                     6
                 }
                 0x54 => {
                     if data.len() < 8 {
-                        return Err("SMPTE Offset meta event data is too short".to_string());
+                        return Err(MidiFileErrorKind::TruncatedEvent);
                     }
 
                     8
                 }
                 0x58 => {
                     if data.len() < 7 {
-                        return Err("Time Signature meta event data is too short".to_string());
+                        return Err(MidiFileErrorKind::TruncatedEvent);
                     }
 
                     7
                 }
                 0x59 => {
                     if data.len() < 5 {
-                        return Err("Key Signature meta event data is too short".to_string());
+                        return Err(MidiFileErrorKind::TruncatedEvent);
                     }
 
                     5
@@ -368,7 +711,7 @@ fn get_event_length(data: &[u8], running_status_length: Option<u8>) -> Result<us
                     let length = from_var_length_bytes(&data[2..2 + var_length])?;
 
                     if data.len() < 2 + var_length + length as usize {
-                        return Err("Meta event data length exceeds available data".to_string());
+                        return Err(MidiFileErrorKind::TruncatedEvent);
                     }
 
                     2 + var_length + length as usize
@@ -378,25 +721,24 @@ fn get_event_length(data: &[u8], running_status_length: Option<u8>) -> Result<us
         // SysEx event
         0xF0 | 0xF7 => {
             if data.len() < 2 {
-                return Err("SysEx event data is too short".to_string());
+                return Err(MidiFileErrorKind::TruncatedEvent);
             }
 
             let var_length = get_var_length_bytes_length(&data[1..])?;
             let length = from_var_length_bytes(&data[1..1 + var_length])?;
 
             if data.len() < 1 + var_length + length as usize {
-                return Err("SysEx event data length exceeds available data".to_string());
+                return Err(MidiFileErrorKind::TruncatedEvent);
             }
 
-            // ignore fragmented SysEx events for now
             1 + var_length + length as usize
         }
-        _ => return Err("Unsupported event type or running status not handled".to_string()),
+        _ => return Err(MidiFileErrorKind::InvalidEvent { status: first_byte }),
     })
 }
 
-pub fn calc_delta_time_microseconds(delta: u32, tempo: u32, division: &MidiDivision) -> u64 {
-    let delta_u64 = delta as u64;
+pub fn calc_delta_time_microseconds(delta: u64, tempo: u32, division: &MidiDivision) -> u64 {
+    let delta_u64 = delta;
     let tempo_u64 = tempo as u64;
 
     match division {
@@ -434,69 +776,17 @@ pub fn calc_delta_time(microseconds: u64, tempo: u32, division: &MidiDivision) -
     delta_u64 as u32
 }
 
-fn parse_midi_file_header(data: &[u8]) -> Result<MidiHeader, String> {
-    if data.len() < 14 {
-        return Err("Data is too short to be a valid MIDI file".to_string());
-    }
-
-    let header_chunk_type = &data[0..4];
-
-    if header_chunk_type != MIDI_HEADER_CHUNK_ASCII_TYPE {
-        return Err("Invalid MIDI header chunk type".to_string());
-    }
-
-    let header_length = u32::from_be_bytes(data[4..8].try_into().unwrap());
-    let format = u16::from_be_bytes(data[8..10].try_into().unwrap());
-    let ntrks = u16::from_be_bytes(data[10..12].try_into().unwrap());
-    let division = u16::from_be_bytes(data[12..14].try_into().unwrap());
-
-    if header_length != 6 {
-        return Err("Invalid MIDI header length".to_string());
-    }
-
-    let midi_format = match format {
-        0 => MidiFormat::SingleMultiChannelTrack,
-        1 => MidiFormat::MultiTrackSequence,
-        2 => MidiFormat::MultiSequence,
-        _ => return Err("Unsupported MIDI format".to_string()),
-    };
-
-    if ntrks == 0 {
-        return Err("MIDI file must contain at least one track".to_string());
-    } else if midi_format == MidiFormat::SingleMultiChannelTrack && ntrks != 1 {
-        return Err("Single multi-channel track format must have exactly one track".to_string());
-    }
-
-    let midi_division = if division & 0x8000 != 0 {
-        MidiDivision::TimeCode(
-            match (division >> 8) as i8 {
-                -24 => FramesPerSecond::Fps24,
-                -25 => FramesPerSecond::Fps25,
-                -30 => FramesPerSecond::Fps30,
-                -29 => FramesPerSecond::Fps30DropFrame,
-                _ => return Err("Unsupported frames per second in time code".to_string()),
-            },
-            (division & 0xFF) as u8,
-        )
-    } else {
-        MidiDivision::TicksPerQuarterNote(division)
-    };
-
-    Ok(MidiHeader {
-        format: midi_format,
-        num_tracks: ntrks,
-        division: midi_division,
-    })
-}
-
-fn parse_midi_track_event(data: &[u8], running_status: Option<u8>) -> Result<(Event, u8), String> {
+fn parse_midi_track_event(
+    data: &[u8],
+    running_status: Option<u8>,
+) -> Result<(Event, u8), MidiFileErrorKind> {
     let first_byte = data[0];
 
     match first_byte {
         // Running status
         0x00..=0x7F => {
             if running_status.is_none() {
-                return Err("Missing running status for MIDI event".to_string());
+                return Err(MidiFileErrorKind::MissingRunningStatus);
             }
             let status = running_status.unwrap();
             let mut message_data = vec![status];
@@ -510,16 +800,16 @@ fn parse_midi_track_event(data: &[u8], running_status: Option<u8>) -> Result<(Ev
         0x80..=0xEF => Ok((Event::MidiEvent(MidiMessage::try_from(data)?), first_byte)),
         // Meta event
         0xFF => {
-            let event = Event::MetaEvent(match data[1] {
+            let meta_type = data[1];
+            let invalid_meta_event = || MidiFileErrorKind::InvalidMetaEvent { meta_type };
+            let event = Event::MetaEvent(match meta_type {
                 0x00 => {
                     if data[2] != 0x02 {
-                        return Err("Invalid sequence number meta event".to_string());
+                        return Err(invalid_meta_event());
                     }
 
                     MetaEvent::SequenceNumber(u16::from_be_bytes(
-                        data[3..=4]
-                            .try_into()
-                            .map_err(|_| "Invalid sequence number bytes".to_string())?,
+                        data[3..=4].try_into().map_err(|_| invalid_meta_event())?,
                     ))
                 }
                 0x01 => {
@@ -528,7 +818,7 @@ fn parse_midi_track_event(data: &[u8], running_status: Option<u8>) -> Result<(Ev
 
                     MetaEvent::TextEvent(
                         String::from_utf8(data[2 + var_length..2 + var_length + length].to_vec())
-                            .map_err(|_| "Invalid UTF-8 in text event".to_string())?,
+                            .map_err(|_| invalid_meta_event())?,
                     )
                 }
                 0x02 => {
@@ -537,7 +827,7 @@ fn parse_midi_track_event(data: &[u8], running_status: Option<u8>) -> Result<(Ev
 
                     MetaEvent::CopyrightNotice(
                         String::from_utf8(data[2 + var_length..2 + var_length + length].to_vec())
-                            .map_err(|_| "Invalid UTF-8 in text event".to_string())?,
+                            .map_err(|_| invalid_meta_event())?,
                     )
                 }
                 0x03 => {
@@ -548,7 +838,7 @@ fn parse_midi_track_event(data: &[u8], running_status: Option<u8>) -> Result<(Ev
                     // for now we assume it's a sequence name (format 0 or first track in format 1)
                     MetaEvent::SequenceName(
                         String::from_utf8(data[2 + var_length..2 + var_length + length].to_vec())
-                            .map_err(|_| "Invalid UTF-8 in text event".to_string())?,
+                            .map_err(|_| invalid_meta_event())?,
                     )
                 }
                 0x04 => {
@@ -557,7 +847,7 @@ fn parse_midi_track_event(data: &[u8], running_status: Option<u8>) -> Result<(Ev
 
                     MetaEvent::InstrumentName(
                         String::from_utf8(data[2 + var_length..2 + var_length + length].to_vec())
-                            .map_err(|_| "Invalid UTF-8 in text event".to_string())?,
+                            .map_err(|_| invalid_meta_event())?,
                     )
                 }
                 0x05 => {
@@ -566,7 +856,7 @@ fn parse_midi_track_event(data: &[u8], running_status: Option<u8>) -> Result<(Ev
 
                     MetaEvent::Lyric(
                         String::from_utf8(data[2 + var_length..2 + var_length + length].to_vec())
-                            .map_err(|_| "Invalid UTF-8 in text event".to_string())?,
+                            .map_err(|_| invalid_meta_event())?,
                     )
                 }
                 0x06 => {
@@ -575,7 +865,7 @@ fn parse_midi_track_event(data: &[u8], running_status: Option<u8>) -> Result<(Ev
 
                     MetaEvent::Marker(
                         String::from_utf8(data[2 + var_length..2 + var_length + length].to_vec())
-                            .map_err(|_| "Invalid UTF-8 in text event".to_string())?,
+                            .map_err(|_| invalid_meta_event())?,
                     )
                 }
                 0x07 => {
@@ -584,12 +874,12 @@ fn parse_midi_track_event(data: &[u8], running_status: Option<u8>) -> Result<(Ev
 
                     MetaEvent::CuePoint(
                         String::from_utf8(data[2 + var_length..2 + var_length + length].to_vec())
-                            .map_err(|_| "Invalid UTF-8 in text event".to_string())?,
+                            .map_err(|_| invalid_meta_event())?,
                     )
                 }
                 0x20 => {
                     if data[2] != 0x01 {
-                        return Err("Invalid MIDI Channel Prefix meta event".to_string());
+                        return Err(invalid_meta_event());
                     }
 
                     let channel = MidiChannel::try_from(data[3])?;
@@ -597,29 +887,27 @@ fn parse_midi_track_event(data: &[u8], running_status: Option<u8>) -> Result<(Ev
                 }
                 0x2F => {
                     if data[2] != 0x00 {
-                        return Err("Invalid End of Track meta event".to_string());
+                        return Err(invalid_meta_event());
                     }
 
                     MetaEvent::EndOfTrack
                 }
                 0x51 => {
                     if data[2] != 0x03 {
-                        return Err("Invalid End of Track meta event".to_string());
+                        return Err(invalid_meta_event());
                     }
 
                     let mut tempo_bytes: Vec<u8> = vec![0x00];
                     tempo_bytes.extend_from_slice(&data[3..=5]);
                     let tempo = u32::from_be_bytes(
-                        tempo_bytes
-                            .try_into()
-                            .map_err(|_| "Invalid tempo bytes".to_string())?,
+                        tempo_bytes.try_into().map_err(|_| invalid_meta_event())?,
                     );
 
                     MetaEvent::SetTempo(tempo)
                 }
                 0x54 => {
                     if data[2] != 0x05 {
-                        return Err("Invalid SMPTE Offset meta event".to_string());
+                        return Err(invalid_meta_event());
                     }
 
                     let hour = data[3];
@@ -640,7 +928,7 @@ fn parse_midi_track_event(data: &[u8], running_status: Option<u8>) -> Result<(Ev
                 }
                 0x58 => {
                     if data[2] != 0x04 {
-                        return Err("Invalid SMPTE Offset meta event".to_string());
+                        return Err(invalid_meta_event());
                     }
 
                     let numerator = data[3];
@@ -649,12 +937,6 @@ fn parse_midi_track_event(data: &[u8], running_status: Option<u8>) -> Result<(Ev
                     let notated_32nd_notes_per_quarter_note = data[6];
 
                     // FIXME: add validation for time signature values
-                    if notated_32nd_notes_per_quarter_note != 8 {
-                        eprintln!(
-                            "Warning: Notated 32nd notes per quarter note is not 8, got {}",
-                            notated_32nd_notes_per_quarter_note
-                        );
-                    }
 
                     MetaEvent::TimeSignature {
                         numerator,
@@ -665,19 +947,19 @@ fn parse_midi_track_event(data: &[u8], running_status: Option<u8>) -> Result<(Ev
                 }
                 0x59 => {
                     if data[2] != 0x02 {
-                        return Err("Invalid SMPTE Offset meta event".to_string());
+                        return Err(invalid_meta_event());
                     }
 
                     let key = data[3] as i8;
 
                     if key < -7 || key > 7 {
-                        return Err("Key signature must be between -7 and 7".to_string());
+                        return Err(invalid_meta_event());
                     }
 
                     let scale = match data[4] {
                         0 => MusicalScale::Major,
                         1 => MusicalScale::Minor,
-                        _ => return Err("Invalid key signature scale".to_string()),
+                        _ => return Err(invalid_meta_event()),
                     };
 
                     MetaEvent::KeySignature { key, scale }
@@ -690,319 +972,1194 @@ fn parse_midi_track_event(data: &[u8], running_status: Option<u8>) -> Result<(Ev
                         data[2 + var_length..2 + var_length + length].to_vec(),
                     )
                 }
-                _ => return Err("Unsupported meta event type".to_string()),
+                _ => return Err(MidiFileErrorKind::UnsupportedMetaEvent { meta_type }),
             });
             Ok((event, 0))
         }
         // SysEx event
-        0xF0 | 0xF7 => Ok((Event::SysExEvent, 0)),
-        _ => return Err("Invalid MIDI event".to_string()),
+        0xF0 | 0xF7 => {
+            let var_length = get_var_length_bytes_length(&data[1..])?;
+            let sysex_data = data[1 + var_length..].to_vec();
+            Ok((
+                if first_byte == 0xF0 {
+                    Event::SysEx(sysex_data)
+                } else {
+                    Event::SysExContinuation(sysex_data)
+                },
+                0,
+            ))
+        }
+        _ => return Err(MidiFileErrorKind::InvalidEvent { status: first_byte }),
     }
 }
 
-fn parse_midi_file_track(data: &[u8], offset: &mut usize) -> Result<MidiTrack, String> {
-    if data.len() <= *offset + 8 {
-        return Err("Data is too short for MIDI track chunk".to_string());
-    }
+/// What is wrong with a Standard MIDI File, or with a file that could not be written as one.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum MidiFileErrorKind {
+    NotAMidiFile,
+    LeadingBytes {
+        count: usize,
+    },
+    TruncatedHeader,
+    InvalidHeaderLength {
+        length: u32,
+    },
+    ExtraHeaderBytes {
+        count: u32,
+    },
+    UnsupportedFormat {
+        format: u16,
+    },
+    UnsupportedTimeCode {
+        frames_per_second: i8,
+    },
+    /// A format 0 file declares more or less than one track.
+    SingleTrackCount {
+        count: u16,
+    },
+    NoTracks,
+    TrackCountMismatch {
+        declared: usize,
+        found: usize,
+    },
+    UnknownChunk {
+        chunk_type: String,
+    },
+    InvalidChunkType,
+    TrailingBytes {
+        count: usize,
+    },
+    TrackLengthExceedsData {
+        length: usize,
+        available: usize,
+    },
+    TrackLengthMismatch {
+        length: usize,
+        events_length: usize,
+    },
+    MissingEndOfTrack,
+    BytesAfterEndOfTrack {
+        count: usize,
+    },
+    EventsAfterEndOfTrack,
+    MissingNextChunk,
+    InvalidVariableLength,
+    VariableLengthOverflow {
+        value: u32,
+    },
+    TruncatedEvent,
+    MissingRunningStatus,
+    InvalidEvent {
+        status: u8,
+    },
+    UnsupportedMetaEvent {
+        meta_type: u8,
+    },
+    InvalidMetaEvent {
+        meta_type: u8,
+    },
+    /// A time signature does not put eight notated 32nd notes into a MIDI quarter note.
+    UnusualNotated32ndNotes {
+        count: u8,
+    },
+    InvalidMessage {
+        error: MessageError,
+    },
+    MultiSequenceMerge,
+    TooManyTracks,
+    TrackTooLong,
+    /// The size of a RIFF chunk does not match the data it is in.
+    InvalidRiffSize {
+        size: usize,
+        available: usize,
+    },
+    MissingRmidData,
+}
 
-    let track_chunk_type = &data[*offset..*offset + 4];
-    *offset += 4;
+impl std::fmt::Display for MidiFileErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MidiFileErrorKind::NotAMidiFile => write!(f, "No MIDI header chunk found"),
+            MidiFileErrorKind::LeadingBytes { count } => {
+                write!(f, "Skipped {} bytes before the header", count)
+            }
+            MidiFileErrorKind::TruncatedHeader => {
+                write!(f, "Data is too short to be a valid MIDI file")
+            }
+            MidiFileErrorKind::InvalidHeaderLength { length } => {
+                write!(f, "Invalid MIDI header length {}", length)
+            }
+            MidiFileErrorKind::ExtraHeaderBytes { count } => {
+                write!(f, "Ignored {} extra header bytes", count)
+            }
+            MidiFileErrorKind::UnsupportedFormat { format } => {
+                write!(f, "Unsupported MIDI format {}", format)
+            }
+            MidiFileErrorKind::UnsupportedTimeCode { frames_per_second } => write!(
+                f,
+                "Unsupported frames per second in time code: {}",
+                frames_per_second
+            ),
+            MidiFileErrorKind::SingleTrackCount { count } => write!(
+                f,
+                "Single multi-channel track format must have exactly one track, not {}",
+                count
+            ),
+            MidiFileErrorKind::NoTracks => write!(f, "MIDI file must contain at least one track"),
+            MidiFileErrorKind::TrackCountMismatch { declared, found } => write!(
+                f,
+                "Header declares {} tracks but {} were found",
+                declared, found
+            ),
+            MidiFileErrorKind::UnknownChunk { chunk_type } => {
+                write!(f, "Skipped unknown chunk \"{}\"", chunk_type)
+            }
+            MidiFileErrorKind::InvalidChunkType => write!(f, "Invalid chunk type"),
+            MidiFileErrorKind::TrailingBytes { count } => {
+                write!(f, "Ignored {} trailing bytes", count)
+            }
+            MidiFileErrorKind::TrackLengthExceedsData { length, available } => write!(
+                f,
+                "Track length is {} bytes but only {} are available",
+                length, available
+            ),
+            MidiFileErrorKind::TrackLengthMismatch {
+                length,
+                events_length,
+            } => write!(
+                f,
+                "Track length is {} bytes but its events take {} bytes",
+                length, events_length
+            ),
+            MidiFileErrorKind::MissingEndOfTrack => write!(f, "Missing End of Track"),
+            MidiFileErrorKind::BytesAfterEndOfTrack { count } => {
+                write!(f, "Ignored {} bytes after End of Track", count)
+            }
+            MidiFileErrorKind::EventsAfterEndOfTrack => {
+                write!(f, "Track contains events after End of Track")
+            }
+            MidiFileErrorKind::MissingNextChunk => write!(f, "No chunk found after the track"),
+            MidiFileErrorKind::InvalidVariableLength => {
+                write!(f, "Invalid variable-length quantity")
+            }
+            MidiFileErrorKind::VariableLengthOverflow { value } => write!(
+                f,
+                "Value {} exceeds the largest variable-length quantity (0x0FFFFFFF)",
+                value
+            ),
+            MidiFileErrorKind::TruncatedEvent => write!(f, "Event data exceeds available data"),
+            MidiFileErrorKind::MissingRunningStatus => {
+                write!(f, "Missing running status for MIDI event")
+            }
+            MidiFileErrorKind::InvalidEvent { status } => {
+                write!(f, "Invalid MIDI event with status {:#04X}", status)
+            }
+            MidiFileErrorKind::UnsupportedMetaEvent { meta_type } => {
+                write!(f, "Unsupported meta event type {:#04X}", meta_type)
+            }
+            MidiFileErrorKind::InvalidMetaEvent { meta_type } => {
+                write!(f, "Invalid meta event of type {:#04X}", meta_type)
+            }
+            MidiFileErrorKind::UnusualNotated32ndNotes { count } => write!(
+                f,
+                "Time signature notates {} instead of 8 32nd notes per quarter note",
+                count
+            ),
+            MidiFileErrorKind::InvalidMessage { error } => write!(f, "{}", error),
+            MidiFileErrorKind::MultiSequenceMerge => {
+                write!(f, "Cannot add a track to a multi sequence file")
+            }
+            MidiFileErrorKind::TooManyTracks => write!(f, "The MIDI file has too many tracks"),
+            MidiFileErrorKind::TrackTooLong => write!(f, "The track is too long"),
+            MidiFileErrorKind::InvalidRiffSize { size, available } => write!(
+                f,
+                "RIFF chunk size is {} bytes but {} are available",
+                size, available
+            ),
+            MidiFileErrorKind::MissingRmidData => {
+                write!(f, "RMID file does not contain a MIDI file")
+            }
+        }
+    }
+}
 
-    if track_chunk_type != MIDI_TRACK_CHUNK_ASCII_TYPE {
-        return Err("Invalid MIDI track chunk type".to_string());
+impl From<MessageError> for MidiFileErrorKind {
+    fn from(error: MessageError) -> Self {
+        MidiFileErrorKind::InvalidMessage { error }
     }
+}
 
-    let track_length = u32::from_be_bytes(data[*offset..*offset + 4].try_into().unwrap());
-    *offset += 4;
+/// An error reading or writing a Standard MIDI File, with where in the file it happened.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MidiFileError {
+    #[serde(flatten)]
+    pub kind: MidiFileErrorKind,
+    /// Byte offset in the file.
+    pub offset: Option<usize>,
+    /// Index of the track chunk.
+    pub track: Option<usize>,
+}
 
-    if track_length == 0 {
-        return Err("Track length cannot be zero".to_string());
+impl MidiFileError {
+    pub fn new(kind: MidiFileErrorKind, offset: Option<usize>, track: Option<usize>) -> Self {
+        Self {
+            kind,
+            offset,
+            track,
+        }
     }
+}
 
-    if data.len() < *offset + track_length as usize {
-        return Err("Track length exceeds available data".to_string());
+impl From<MidiFileErrorKind> for MidiFileError {
+    fn from(kind: MidiFileErrorKind) -> Self {
+        MidiFileError::new(kind, None, None)
     }
+}
 
-    let track_data = &data[*offset..*offset + track_length as usize];
-    *offset += track_length as usize;
-
-    let mut track: MidiTrack = MidiTrack(Vec::new());
-
-    let mut track_offset = 0;
-    let mut running_status: Option<u8> = None;
-    let mut running_status_length: Option<u8> = None;
-    loop {
-        let var_length = get_var_length_bytes_length(&track_data[track_offset..])?;
-        let delta = from_var_length_bytes(&track_data[track_offset..track_offset + var_length])?;
-        track_offset += var_length;
-        let event_length = get_event_length(&track_data[track_offset..], running_status_length)?;
-        let (event, new_status) = parse_midi_track_event(
-            &track_data[track_offset..track_offset + event_length],
-            running_status,
-        )?;
-        track_offset += event_length;
-
-        if !matches!(event, Event::MidiEvent(_)) {
-            running_status = None;
-            running_status_length = None;
-        } else if new_status != 0 {
-            running_status = Some(new_status);
-            running_status_length = Some((event_length - 1) as u8);
+impl std::fmt::Display for MidiFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(track) = self.track {
+            write!(f, " in track {}", track)?;
         }
-
-        // SysEx events are not supported
-        let is_sysex = match event {
-            Event::SysExEvent => true,
-            _ => false,
-        };
-
-        if !is_sysex {
-            track.push(MidiTrackEvent {
-                delta_time: delta,
-                event: event.clone(),
-            });
+        if let Some(offset) = self.offset {
+            write!(f, " at byte {}", offset)?;
         }
 
-        if event == Event::MetaEvent(MetaEvent::EndOfTrack) {
-            break;
-        } else if track_data[track_offset..].len() < 3 {
-            eprintln!("Not enough data for next event, breaking...");
-            // try to fix corrupted track data and add end of track event
-            track.push(MidiTrackEvent {
-                delta_time: 0,
-                event: Event::MetaEvent(MetaEvent::EndOfTrack),
-            });
-            break;
-        }
+        Ok(())
     }
-
-    Ok(track)
 }
 
-impl TryFrom<&[u8]> for MidiFile {
-    type Error = String;
+impl std::error::Error for MidiFileError {}
 
-    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
-        let header = parse_midi_file_header(data)?;
+/// How to deal with a MIDI file that does not follow the specification.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Fail on the first problem.
+    #[default]
+    Strict,
+    /// Work around the problems that allow it and report them as warnings.
+    Lenient,
+}
 
-        let mut offset: usize = 14;
-        let mut tracks = Vec::new();
-        for _ in 0..header.num_tracks {
-            let track = parse_midi_file_track(data, &mut offset)?;
-            tracks.push(track);
-        }
+/// A problem in a MIDI file that was worked around while parsing it.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParseWarning {
+    #[serde(flatten)]
+    pub kind: MidiFileErrorKind,
+    /// Byte offset in the file where the problem was found.
+    pub offset: usize,
+    /// Index of the track chunk the problem was found in.
+    pub track: Option<usize>,
+    /// The problem as text, e.g. to show it.
+    pub message: String,
+}
 
-        Ok(MidiFile { header, tracks })
-    }
+struct MidiFileParser<'a> {
+    data: &'a [u8],
+    /// Offset of the data in the file, when it is wrapped in an RMID file.
+    base: usize,
+    mode: ParseMode,
+    warnings: Vec<ParseWarning>,
 }
 
-impl TryFrom<&MidiFile> for Vec<u8> {
-    type Error = String;
+impl<'a> MidiFileParser<'a> {
+    fn new(data: &'a [u8], mode: ParseMode) -> Self {
+        Self {
+            data,
+            base: 0,
+            mode,
+            warnings: Vec::new(),
+        }
+    }
 
-    fn try_from(value: &MidiFile) -> Result<Self, Self::Error> {
-        let mut data = Vec::new();
-
-        // serialize header chunk
-        data.extend_from_slice(MIDI_HEADER_CHUNK_ASCII_TYPE);
-        data.extend_from_slice(&[0x00, 0x00, 0x00, 0x06]); // header length
-        data.extend_from_slice(&match value.header.format {
-            MidiFormat::SingleMultiChannelTrack => [0x00, 0x00],
-            MidiFormat::MultiTrackSequence => [0x00, 0x01],
-            MidiFormat::MultiSequence => [0x00, 0x02],
-        });
-        data.extend_from_slice(&value.header.num_tracks.to_be_bytes());
-        data.extend_from_slice(&match value.header.division {
-            MidiDivision::TicksPerQuarterNote(ticks) => ticks.to_be_bytes(),
-            MidiDivision::TimeCode(ref frames_per_second, ticks) => {
-                let fps_value = match frames_per_second {
-                    FramesPerSecond::Fps24 => -24 as i16,
-                    FramesPerSecond::Fps25 => -25 as i16,
-                    FramesPerSecond::Fps30 => -30 as i16,
-                    FramesPerSecond::Fps30DropFrame => -29 as i16,
-                } as u16;
-                let division_value = (fps_value << 8) | (ticks as u16);
-                division_value.to_be_bytes()
+    /// Fails with the problem in strict mode, records it as a warning in lenient mode.
+    fn recover(
+        &mut self,
+        kind: MidiFileErrorKind,
+        offset: usize,
+        track: Option<usize>,
+    ) -> Result<(), MidiFileError> {
+        match self.mode {
+            ParseMode::Strict => Err(MidiFileError::new(kind, Some(self.base + offset), track)),
+            ParseMode::Lenient => {
+                self.warn(kind, offset, track);
+                Ok(())
             }
+        }
+    }
+
+    /// Records a problem that readers are meant to ignore, in either mode.
+    fn warn(&mut self, kind: MidiFileErrorKind, offset: usize, track: Option<usize>) {
+        self.warnings.push(ParseWarning {
+            message: kind.to_string(),
+            kind,
+            offset: self.base + offset,
+            track,
         });
+    }
 
-        if value.header.num_tracks == 0 {
-            return Err("MIDI file must contain at least one track".to_string());
-        } else if (value.header.num_tracks as usize) != value.tracks.len() {
-            return Err(
-                "Number of tracks in header does not match number of tracks in file".to_string(),
-            );
-        }
+    fn read_u32(&self, offset: usize) -> u32 {
+        u32::from_be_bytes(self.data[offset..offset + 4].try_into().unwrap())
+    }
 
-        // serialize track chunks
-        for track in &value.tracks {
-            data.extend_from_slice(MIDI_TRACK_CHUNK_ASCII_TYPE);
-            let mut track_data: Vec<u8> = Vec::new();
+    /// Whether a chunk starts at the offset, or the data ends there.
+    fn is_chunk_start(&self, offset: usize) -> bool {
+        offset == self.data.len()
+            || self
+                .data
+                .get(offset..offset + 8)
+                .is_some_and(|header| header[..4].iter().all(u8::is_ascii_alphanumeric))
+    }
 
-            let mut end_of_track = false;
-            let mut running_status: Option<u8> = None;
-            for event in track.iter() {
-                if end_of_track {
-                    return Err("Track contains events after End of Track".to_string());
-                }
+    fn find_track_chunk(&self, from: usize) -> Option<usize> {
+        self.data
+            .get(from..)?
+            .windows(4)
+            .position(|window| window == MIDI_TRACK_CHUNK_ASCII_TYPE)
+            .map(|position| from + position)
+    }
 
-                let delta_bytes = to_var_length_bytes(event.delta_time)?;
-                track_data.extend(delta_bytes);
+    fn parse(mut self) -> Result<(MidiFile, Vec<ParseWarning>), MidiFileError> {
+        let info = self.unwrap_rmid()?;
+        let header_offset = self.find_header()?;
+        let (mut header, mut offset) = self.parse_header(header_offset)?;
 
-                match &event.event {
-                    Event::SysExEvent => {
-                        // SysEx events are not supported
-                        continue;
-                    }
-                    Event::MetaEvent(meta_event) => {
-                        running_status = None;
+        let data = self.data;
+        let mut tracks = Vec::new();
+        // strict mode reads the declared tracks and ignores what follows them
+        while offset < data.len()
+            && (self.mode == ParseMode::Lenient || tracks.len() < header.num_tracks as usize)
+        {
+            if data.len() - offset < 8 {
+                self.recover(
+                    MidiFileErrorKind::TrailingBytes {
+                        count: data.len() - offset,
+                    },
+                    offset,
+                    None,
+                )?;
+                break;
+            }
 
-                        track_data.push(0xFF);
-                        match meta_event {
-                            MetaEvent::SequenceNumber(seq_num) => {
-                                track_data.push(0x00);
-                                track_data.push(0x02);
-                                track_data.extend_from_slice(&seq_num.to_be_bytes());
-                            }
-                            MetaEvent::TextEvent(text) => {
-                                track_data.push(0x01);
-                                let var_length_bytes = to_var_length_bytes(text.len() as u32)?;
-                                track_data.extend(var_length_bytes);
-                                track_data.extend(text.as_bytes());
-                            }
-                            MetaEvent::CopyrightNotice(text) => {
-                                track_data.push(0x02);
-                                let var_length_bytes = to_var_length_bytes(text.len() as u32)?;
-                                track_data.extend(var_length_bytes);
-                                track_data.extend(text.as_bytes());
-                            }
-                            MetaEvent::SequenceName(text) | MetaEvent::TrackName(text) => {
-                                track_data.push(0x03);
-                                let var_length_bytes = to_var_length_bytes(text.len() as u32)?;
-                                track_data.extend(var_length_bytes);
-                                track_data.extend(text.as_bytes());
-                            }
-                            MetaEvent::InstrumentName(text) => {
-                                track_data.push(0x04);
-                                let var_length_bytes = to_var_length_bytes(text.len() as u32)?;
-                                track_data.extend(var_length_bytes);
-                                track_data.extend(text.as_bytes());
-                            }
-                            MetaEvent::Lyric(text) => {
-                                track_data.push(0x05);
-                                let var_length_bytes = to_var_length_bytes(text.len() as u32)?;
-                                track_data.extend(var_length_bytes);
-                                track_data.extend(text.as_bytes());
-                            }
-                            MetaEvent::Marker(text) => {
-                                track_data.push(0x06);
-                                let var_length_bytes = to_var_length_bytes(text.len() as u32)?;
-                                track_data.extend(var_length_bytes);
-                                track_data.extend(text.as_bytes());
-                            }
-                            MetaEvent::CuePoint(text) => {
-                                track_data.push(0x07);
-                                let var_length_bytes = to_var_length_bytes(text.len() as u32)?;
-                                track_data.extend(var_length_bytes);
-                                track_data.extend(text.as_bytes());
-                            }
-                            MetaEvent::MidiChannelPrefix(channel) => {
-                                track_data.push(0x20);
-                                track_data.push(0x01);
-                                track_data.push((*channel).clone().into());
-                            }
-                            MetaEvent::EndOfTrack => {
-                                track_data.push(0x2F);
-                                track_data.push(0x00);
-                                end_of_track = true;
-                            }
-                            MetaEvent::SetTempo(tempo) => {
-                                track_data.push(0x51);
-                                track_data.push(0x03);
-                                let tempo_bytes: [u8; 4] = tempo.to_be_bytes();
-                                track_data.push(tempo_bytes[1]);
-                                track_data.push(tempo_bytes[2]);
-                                track_data.push(tempo_bytes[3]);
-                            }
-                            MetaEvent::SmpteOffset {
-                                hour,
-                                minute,
-                                second,
-                                frame,
-                                sub_frame,
-                            } => {
-                                track_data.push(0x54);
-                                track_data.push(0x05);
-                                track_data.push(*hour);
-                                track_data.push(*minute);
-                                track_data.push(*second);
-                                track_data.push(*frame);
-                                track_data.push(*sub_frame);
-                            }
-                            MetaEvent::TimeSignature {
-                                numerator,
-                                denominator,
-                                clocks_per_click,
-                                notated_32nd_notes_per_quarter_note,
-                            } => {
-                                track_data.push(0x58);
-                                track_data.push(0x04);
-                                track_data.push(*numerator);
-                                track_data.push(*denominator);
-                                track_data.push(*clocks_per_click);
-                                track_data.push(*notated_32nd_notes_per_quarter_note);
-                            }
-                            MetaEvent::KeySignature { key, scale } => {
-                                track_data.push(0x59);
-                                track_data.push(0x02);
-                                track_data.push(*key as u8);
-                                track_data.push(match scale {
-                                    MusicalScale::Major => 0x00,
-                                    MusicalScale::Minor => 0x01,
-                                });
-                            }
-                            MetaEvent::SequencerSpecific(data) => {
-                                track_data.push(0x7F);
-                                let var_length_bytes = to_var_length_bytes(data.len() as u32)?;
-                                track_data.extend(var_length_bytes);
-                                track_data.extend(data);
-                            }
-                        }
-                    }
-                    Event::MidiEvent(midi_event) => {
-                        if let MidiMessage::Channel { .. } = midi_event {
-                            let midi_bytes: Vec<u8> = midi_event.to_owned().into();
-                            let new_status = midi_bytes[0];
-
-                            if running_status.is_some() && running_status.unwrap() == new_status {
-                                track_data.extend(&midi_bytes[1..]);
-                            } else {
-                                track_data.extend(&midi_bytes);
-                                running_status = Some(new_status);
-                            }
-                        }
-                    }
+            let chunk_type = &data[offset..offset + 4];
+            if chunk_type == MIDI_TRACK_CHUNK_ASCII_TYPE {
+                let (track, next_offset) = self.parse_track(offset, tracks.len())?;
+                tracks.push(track);
+                offset = next_offset;
+            } else if chunk_type.iter().all(u8::is_ascii_alphanumeric) {
+                self.warn(
+                    MidiFileErrorKind::UnknownChunk {
+                        chunk_type: String::from_utf8_lossy(chunk_type).to_string(),
+                    },
+                    offset,
+                    None,
+                );
+                let length = self.read_u32(offset + 4) as usize;
+                offset = (offset + 8).saturating_add(length).min(data.len());
+            } else {
+                self.recover(MidiFileErrorKind::InvalidChunkType, offset, None)?;
+                match self.find_track_chunk(offset + 1) {
+                    Some(next_offset) => offset = next_offset,
+                    None => break,
                 }
             }
+        }
 
-            if !end_of_track {
-                track_data.push(0xFF);
-                track_data.push(0x2F);
-                track_data.push(0x00);
-            }
+        if tracks.is_empty() {
+            return Err(MidiFileErrorKind::NoTracks.into());
+        } else if tracks.len() != header.num_tracks as usize {
+            self.recover(
+                MidiFileErrorKind::TrackCountMismatch {
+                    declared: header.num_tracks as usize,
+                    found: tracks.len(),
+                },
+                header_offset + 10,
+                None,
+            )?;
+            header.num_tracks = tracks.len() as u16;
+        }
+
+        Ok((
+            MidiFile {
+                header,
+                tracks,
+                info,
+            },
+            self.warnings,
+        ))
+    }
+
+    /// Narrows the data to the MIDI file in it if it is an RMID file, and returns its metadata.
+    fn unwrap_rmid(&mut self) -> Result<RmidInfo, MidiFileError> {
+        if !is_rmid(self.data) {
+            return Ok(RmidInfo::default());
+        }
 
-            data.extend_from_slice(&(track_data.len() as u32).to_be_bytes());
-            data.extend(&track_data);
+        let contents = read_rmid(self.data)?;
+        for (kind, offset) in contents.problems {
+            self.recover(kind, offset, None)?;
         }
+        let data = self.data;
+        self.data = &data[contents.smf_range.clone()];
+        self.base = contents.smf_range.start;
 
-        Ok(data)
+        Ok(contents.info)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::midi::message::{ChannelMessage, ChannelVoiceMessage};
+    /// Finds the header chunk, which lenient mode also looks for after leading bytes.
+    fn find_header(&mut self) -> Result<usize, MidiFileError> {
+        if self.data.starts_with(MIDI_HEADER_CHUNK_ASCII_TYPE) {
+            return Ok(0);
+        }
 
-    use super::*;
+        self.recover(MidiFileErrorKind::NotAMidiFile, 0, None)?;
+        let offset = self
+            .data
+            .windows(4)
+            .position(|window| window == MIDI_HEADER_CHUNK_ASCII_TYPE)
+            .ok_or(MidiFileErrorKind::NotAMidiFile)?;
+        self.warn(
+            MidiFileErrorKind::LeadingBytes { count: offset },
+            offset,
+            None,
+        );
+
+        Ok(offset)
+    }
+
+    /// Parses the header chunk and returns it with the offset of the chunk after it.
+    fn parse_header(&mut self, offset: usize) -> Result<(MidiHeader, usize), MidiFileError> {
+        let data = &self.data[offset..];
+        if data.len() < 14 {
+            return Err(MidiFileError::new(
+                MidiFileErrorKind::TruncatedHeader,
+                Some(self.base + offset),
+                None,
+            ));
+        }
+
+        let header_length = self.read_u32(offset + 4);
+        let format = u16::from_be_bytes(data[8..10].try_into().unwrap());
+        let ntrks = u16::from_be_bytes(data[10..12].try_into().unwrap());
+        let division = u16::from_be_bytes(data[12..14].try_into().unwrap());
+
+        if header_length < 6 {
+            return Err(MidiFileError::new(
+                MidiFileErrorKind::InvalidHeaderLength {
+                    length: header_length,
+                },
+                Some(self.base + offset + 4),
+                None,
+            ));
+        } else if header_length > 6 {
+            // later versions of the format may add parameters to the header
+            self.warn(
+                MidiFileErrorKind::ExtraHeaderBytes {
+                    count: header_length - 6,
+                },
+                offset + 4,
+                None,
+            );
+        }
+
+        let midi_format = match format {
+            0 => MidiFormat::SingleMultiChannelTrack,
+            1 => MidiFormat::MultiTrackSequence,
+            2 => MidiFormat::MultiSequence,
+            _ => {
+                return Err(MidiFileError::new(
+                    MidiFileErrorKind::UnsupportedFormat { format },
+                    Some(self.base + offset + 8),
+                    None,
+                ));
+            }
+        };
+
+        if midi_format == MidiFormat::SingleMultiChannelTrack && ntrks != 1 {
+            self.recover(
+                MidiFileErrorKind::SingleTrackCount { count: ntrks },
+                offset + 10,
+                None,
+            )?;
+        }
+
+        let midi_division = if division & 0x8000 != 0 {
+            MidiDivision::TimeCode(
+                match (division >> 8) as i8 {
+                    -24 => FramesPerSecond::Fps24,
+                    -25 => FramesPerSecond::Fps25,
+                    -30 => FramesPerSecond::Fps30,
+                    -29 => FramesPerSecond::Fps30DropFrame,
+                    frames_per_second => {
+                        return Err(MidiFileError::new(
+                            MidiFileErrorKind::UnsupportedTimeCode { frames_per_second },
+                            Some(self.base + offset + 12),
+                            None,
+                        ));
+                    }
+                },
+                (division & 0xFF) as u8,
+            )
+        } else {
+            MidiDivision::TicksPerQuarterNote(division)
+        };
+
+        let header = MidiHeader {
+            format: midi_format,
+            num_tracks: ntrks,
+            division: midi_division,
+        };
+        let next_offset = (offset + 8)
+            .saturating_add(header_length as usize)
+            .min(self.data.len());
+
+        Ok((header, next_offset))
+    }
+
+    /// Parses the track chunk at the offset and returns it with the offset of the chunk after it.
+    fn parse_track(
+        &mut self,
+        offset: usize,
+        index: usize,
+    ) -> Result<(MidiTrack, usize), MidiFileError> {
+        let data = self.data;
+        let track_index = Some(index);
+        let length = self.read_u32(offset + 4) as usize;
+        let start = offset + 8;
+        let mut end = start.saturating_add(length);
+
+        if end > data.len() {
+            self.recover(
+                MidiFileErrorKind::TrackLengthExceedsData {
+                    length,
+                    available: data.len() - start.min(data.len()),
+                },
+                offset + 4,
+                track_index,
+            )?;
+            end = data.len();
+        }
+
+        // lenient mode looks for the End of Track past a chunk length that is too short
+        let limit = match self.mode {
+            ParseMode::Strict => end,
+            ParseMode::Lenient => data.len(),
+        };
+
+        let mut track: MidiTrack = MidiTrack(Vec::new());
+        let mut position = start;
+        let mut running_status: Option<u8> = None;
+        let mut running_status_length: Option<u8> = None;
+        // the last channel status, to recover from running status that was cancelled
+        let mut last_status: Option<(u8, u8)> = None;
+        let mut is_truncated = false;
+        loop {
+            if position >= end && (position >= limit || self.is_chunk_start(position)) {
+                self.recover(MidiFileErrorKind::MissingEndOfTrack, position, track_index)?;
+                is_truncated = true;
+                break;
+            }
+
+            let event_start = position;
+            let (delta, var_length) = match parse_delta_time(&data[position..limit]) {
+                Ok(delta_time) => delta_time,
+                Err(e) => {
+                    self.recover(e, event_start, track_index)?;
+                    is_truncated = true;
+                    break;
+                }
+            };
+            position += var_length;
+
+            if running_status.is_none() && data.get(position).is_some_and(|byte| *byte < 0x80) {
+                self.recover(
+                    MidiFileErrorKind::MissingRunningStatus,
+                    position,
+                    track_index,
+                )?;
+                if let Some((status, length)) = last_status {
+                    running_status = Some(status);
+                    running_status_length = Some(length);
+                } else if let Some(skipped) =
+                    data[position..limit].iter().position(|byte| *byte >= 0x80)
+                {
+                    position += skipped;
+                } else {
+                    is_truncated = true;
+                    break;
+                }
+            }
+
+            let (event, new_status, event_length) = match parse_track_event(
+                &data[position..limit],
+                running_status,
+                running_status_length,
+            ) {
+                Ok(event) => event,
+                Err(e) => {
+                    self.recover(e, position, track_index)?;
+                    is_truncated = true;
+                    break;
+                }
+            };
+            if let Event::MetaEvent(MetaEvent::TimeSignature {
+                notated_32nd_notes_per_quarter_note: count,
+                ..
+            }) = event
+                && count != 8
+            {
+                self.warn(
+                    MidiFileErrorKind::UnusualNotated32ndNotes { count },
+                    position,
+                    track_index,
+                );
+            }
+            position += event_length;
+
+            if !matches!(event, Event::MidiEvent(_)) {
+                running_status = None;
+                running_status_length = None;
+            } else if new_status != 0 {
+                running_status = Some(new_status);
+                running_status_length = Some((event_length - 1) as u8);
+                last_status = Some((new_status, (event_length - 1) as u8));
+            }
+
+            let is_end_of_track = event == Event::MetaEvent(MetaEvent::EndOfTrack);
+            track.push(MidiTrackEvent {
+                delta_time: delta,
+                event,
+            });
+
+            if is_end_of_track {
+                break;
+            }
+        }
+
+        if is_truncated {
+            // keep what could be read and end the track there
+            track.push(MidiTrackEvent {
+                delta_time: 0,
+                event: Event::MetaEvent(MetaEvent::EndOfTrack),
+            });
+        }
+
+        let next_offset = if position == end || (is_truncated && self.is_chunk_start(end)) {
+            end
+        } else if position < end && self.is_chunk_start(end) {
+            self.recover(
+                MidiFileErrorKind::BytesAfterEndOfTrack {
+                    count: end - position,
+                },
+                position,
+                track_index,
+            )?;
+            end
+        } else if self.is_chunk_start(position) {
+            self.recover(
+                MidiFileErrorKind::TrackLengthMismatch {
+                    length,
+                    events_length: position - start,
+                },
+                offset + 4,
+                track_index,
+            )?;
+            position
+        } else {
+            self.recover(MidiFileErrorKind::MissingNextChunk, position, track_index)?;
+            self.find_track_chunk(position).unwrap_or(data.len())
+        };
+
+        Ok((track, next_offset))
+    }
+}
+
+fn parse_delta_time(data: &[u8]) -> Result<(u32, usize), MidiFileErrorKind> {
+    let var_length = get_var_length_bytes_length(data)?;
+    let delta = from_var_length_bytes(&data[..var_length])?;
+
+    Ok((delta, var_length))
+}
+
+fn parse_track_event(
+    data: &[u8],
+    running_status: Option<u8>,
+    running_status_length: Option<u8>,
+) -> Result<(Event, u8, usize), MidiFileErrorKind> {
+    let event_length = get_event_length(data, running_status_length)?;
+    if event_length > data.len() {
+        return Err(MidiFileErrorKind::TruncatedEvent);
+    }
+
+    let (event, new_status) = parse_midi_track_event(&data[..event_length], running_status)?;
+
+    Ok((event, new_status, event_length))
+}
+
+impl MidiFile {
+    /// Parses a Standard MIDI File, also wrapped in an RMID file, returning the problems
+    /// lenient mode worked around.
+    pub fn parse(
+        data: &[u8],
+        mode: ParseMode,
+    ) -> Result<(MidiFile, Vec<ParseWarning>), MidiFileError> {
+        MidiFileParser::new(data, mode).parse()
+    }
+}
+
+impl TryFrom<&[u8]> for MidiFile {
+    type Error = MidiFileError;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        let (midi_file, _) = MidiFile::parse(data, ParseMode::Strict)?;
+
+        Ok(midi_file)
+    }
+}
+
+/// Options for writing a MIDI file; the defaults write every event as it is.
+#[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MidiWriterConfig {
+    /// Leave out the status byte of channel messages that repeat the previous status.
+    pub running_status: bool,
+    /// Write Note Off as Note On with velocity 0, so that notes share one running status.
+    pub note_off_as_note_on: bool,
+    /// Leave out events that change nothing, e.g. a controller set to the value it already has.
+    pub strip_redundant_events: bool,
+}
+
+impl Default for MidiWriterConfig {
+    fn default() -> Self {
+        Self {
+            running_status: true,
+            note_off_as_note_on: false,
+            strip_redundant_events: false,
+        }
+    }
+}
+
+/// The state of a sequence that channel and tempo events change, to tell redundant ones.
+#[derive(Debug, Default)]
+struct SequenceState {
+    // (channel, status, number) -> value, e.g. (0, 0xB0, 7) for the volume of channel 1
+    values: HashMap<(u8, u8, u8), u32>,
+    tempo: Option<u32>,
+}
+
+impl SequenceState {
+    /// Applies an event and returns whether it changed the state or has to be kept anyway.
+    fn apply(&mut self, event: &Event) -> bool {
+        let (key, value) = match event {
+            Event::MetaEvent(MetaEvent::SetTempo(tempo)) => {
+                return self.tempo.replace(*tempo) != Some(*tempo);
+            }
+            // a reset puts every channel of the device back to its defaults
+            Event::SysEx(data) if is_reset_sysex(data) => {
+                self.values.clear();
+                return true;
+            }
+            Event::MidiEvent(MidiMessage::Channel { channel, message }) => {
+                let channel = u8::from(channel.clone());
+                match message {
+                    ChannelMessage::Voice(ChannelVoiceMessage::ControlChange {
+                        controller,
+                        value,
+                    }) => match controller {
+                        // bank select makes the next program change count
+                        0 | 32 => {
+                            self.values.remove(&(channel, 0xC0, 0));
+                            ((channel, 0xB0, *controller), *value as u32)
+                        }
+                        // data entry and parameter numbers act in sequence
+                        6 | 38 | 96..=101 => return true,
+                        _ => ((channel, 0xB0, *controller), *value as u32),
+                    },
+                    ChannelMessage::Voice(ChannelVoiceMessage::ProgramChange(program)) => {
+                        ((channel, 0xC0, 0), *program as u32)
+                    }
+                    ChannelMessage::Voice(ChannelVoiceMessage::ChannelPressure(pressure)) => {
+                        ((channel, 0xD0, 0), *pressure as u32)
+                    }
+                    ChannelMessage::Voice(ChannelVoiceMessage::PitchBendChange(value)) => {
+                        ((channel, 0xE0, 0), *value as u32)
+                    }
+                    ChannelMessage::Mode(ChannelModeMessage::ResetAllControllers) => {
+                        self.values.retain(|(key_channel, status, _), _| {
+                            *key_channel != channel || *status == 0xC0
+                        });
+                        return true;
+                    }
+                    _ => return true,
+                }
+            }
+            _ => return true,
+        };
+
+        self.values.insert(key, value) != Some(value)
+    }
+}
+
+/// Whether the bytes of a SysEx event are a GM, GS or XG system reset.
+fn is_reset_sysex(data: &[u8]) -> bool {
+    match data {
+        // GM System On and GM2 System On, for any device id
+        [0x7E, _, 0x09, 0x01 | 0x03, 0xF7] => true,
+        // Roland GS Reset
+        [0x41, _, 0x42, 0x12, 0x40, 0x00, 0x7F, 0x00, 0x41, 0xF7] => true,
+        // Yamaha XG System On
+        [0x43, device, 0x4C, 0x00, 0x00, 0x7E, 0x00, 0xF7] => device & 0xF0 == 0x10,
+        _ => false,
+    }
+}
+
+impl MidiFile {
+    pub fn to_bytes(&self, config: &MidiWriterConfig) -> Result<Vec<u8>, MidiFileError> {
+        write_midi_file(self, config)
+    }
+
+    /// Writes the file wrapped in an RMID file, with its metadata.
+    pub fn to_rmid_bytes(&self, config: &MidiWriterConfig) -> Result<Vec<u8>, MidiFileError> {
+        Ok(write_rmid(&write_midi_file(self, config)?, &self.info))
+    }
+
+    /// Returns the tracks without the events that change nothing.
+    ///
+    /// Tracks of format 0 and 1 files play together and share their state, whereas every
+    /// track of a format 2 file is a sequence of its own. The time of a removed event is
+    /// added to the next one.
+    fn get_tracks_without_redundant_events(&self) -> Vec<MidiTrack> {
+        let sequences: Vec<Vec<usize>> = match self.header.format {
+            MidiFormat::MultiSequence => (0..self.tracks.len()).map(|index| vec![index]).collect(),
+            _ => vec![(0..self.tracks.len()).collect()],
+        };
+
+        let mut is_kept: Vec<Vec<bool>> = self
+            .tracks
+            .iter()
+            .map(|track| vec![true; track.len()])
+            .collect();
+        for sequence in sequences {
+            // (tick, track index, event index) of every event in the order they are played
+            let mut events: Vec<(u64, usize, usize)> = Vec::new();
+            for track_index in sequence {
+                let mut tick: u64 = 0;
+                for (event_index, event) in self.tracks[track_index].iter().enumerate() {
+                    tick += event.delta_time as u64;
+                    events.push((tick, track_index, event_index));
+                }
+            }
+            events.sort_by_key(|(tick, _, _)| *tick);
+
+            let mut state = SequenceState::default();
+            for (_, track_index, event_index) in events {
+                is_kept[track_index][event_index] =
+                    state.apply(&self.tracks[track_index][event_index].event);
+            }
+        }
+
+        self.tracks
+            .iter()
+            .zip(is_kept)
+            .map(|(track, is_kept)| {
+                let mut removed_delta_time: u32 = 0;
+                let mut events = Vec::new();
+                for (event, is_kept) in track.iter().zip(is_kept) {
+                    if is_kept {
+                        events.push(MidiTrackEvent {
+                            delta_time: event.delta_time.saturating_add(removed_delta_time),
+                            event: event.event.clone(),
+                        });
+                        removed_delta_time = 0;
+                    } else {
+                        removed_delta_time = removed_delta_time.saturating_add(event.delta_time);
+                    }
+                }
+                MidiTrack(events)
+            })
+            .collect()
+    }
+}
+
+impl TryFrom<&MidiFile> for Vec<u8> {
+    type Error = MidiFileError;
+
+    fn try_from(value: &MidiFile) -> Result<Self, Self::Error> {
+        write_midi_file(value, &MidiWriterConfig::default())
+    }
+}
+
+fn write_midi_file(value: &MidiFile, config: &MidiWriterConfig) -> Result<Vec<u8>, MidiFileError> {
+    let mut data = Vec::new();
+
+    // serialize header chunk
+    data.extend_from_slice(MIDI_HEADER_CHUNK_ASCII_TYPE);
+    data.extend_from_slice(&[0x00, 0x00, 0x00, 0x06]); // header length
+    data.extend_from_slice(&match value.header.format {
+        MidiFormat::SingleMultiChannelTrack => [0x00, 0x00],
+        MidiFormat::MultiTrackSequence => [0x00, 0x01],
+        MidiFormat::MultiSequence => [0x00, 0x02],
+    });
+    data.extend_from_slice(&value.header.num_tracks.to_be_bytes());
+    data.extend_from_slice(&match value.header.division {
+        MidiDivision::TicksPerQuarterNote(ticks) => ticks.to_be_bytes(),
+        MidiDivision::TimeCode(ref frames_per_second, ticks) => {
+            let fps_value = match frames_per_second {
+                FramesPerSecond::Fps24 => -24 as i16,
+                FramesPerSecond::Fps25 => -25 as i16,
+                FramesPerSecond::Fps30 => -30 as i16,
+                FramesPerSecond::Fps30DropFrame => -29 as i16,
+            } as u16;
+            let division_value = (fps_value << 8) | (ticks as u16);
+            division_value.to_be_bytes()
+        }
+    });
+
+    if value.header.num_tracks == 0 {
+        return Err(MidiFileErrorKind::NoTracks.into());
+    } else if (value.header.num_tracks as usize) != value.tracks.len() {
+        return Err(MidiFileErrorKind::TrackCountMismatch {
+            declared: value.header.num_tracks as usize,
+            found: value.tracks.len(),
+        }
+        .into());
+    }
+
+    let tracks = if config.strip_redundant_events {
+        value.get_tracks_without_redundant_events()
+    } else {
+        value.tracks.clone()
+    };
+
+    // serialize track chunks
+    for (index, track) in tracks.iter().enumerate() {
+        let track_data = write_midi_track(track, config)
+            .map_err(|kind| MidiFileError::new(kind, None, Some(index)))?;
+        data.extend_from_slice(MIDI_TRACK_CHUNK_ASCII_TYPE);
+        data.extend_from_slice(&(track_data.len() as u32).to_be_bytes());
+        data.extend(&track_data);
+    }
+
+    Ok(data)
+}
+
+fn write_midi_track(
+    track: &MidiTrack,
+    config: &MidiWriterConfig,
+) -> Result<Vec<u8>, MidiFileErrorKind> {
+    let mut track_data: Vec<u8> = Vec::new();
+
+    let mut end_of_track = false;
+    let mut running_status: Option<u8> = None;
+    for event in track.iter() {
+        if end_of_track {
+            return Err(MidiFileErrorKind::EventsAfterEndOfTrack);
+        }
+
+        let delta_bytes = to_var_length_bytes(event.delta_time)?;
+        track_data.extend(delta_bytes);
+
+        match &event.event {
+            Event::SysEx(sysex_data) | Event::SysExContinuation(sysex_data) => {
+                running_status = None;
+
+                track_data.push(match event.event {
+                    Event::SysEx(_) => 0xF0,
+                    _ => 0xF7,
+                });
+                track_data.extend(to_var_length_bytes(sysex_data.len() as u32)?);
+                track_data.extend(sysex_data);
+            }
+            Event::MetaEvent(meta_event) => {
+                running_status = None;
+
+                track_data.push(0xFF);
+                match meta_event {
+                    MetaEvent::SequenceNumber(seq_num) => {
+                        track_data.push(0x00);
+                        track_data.push(0x02);
+                        track_data.extend_from_slice(&seq_num.to_be_bytes());
+                    }
+                    MetaEvent::TextEvent(text) => {
+                        track_data.push(0x01);
+                        let var_length_bytes = to_var_length_bytes(text.len() as u32)?;
+                        track_data.extend(var_length_bytes);
+                        track_data.extend(text.as_bytes());
+                    }
+                    MetaEvent::CopyrightNotice(text) => {
+                        track_data.push(0x02);
+                        let var_length_bytes = to_var_length_bytes(text.len() as u32)?;
+                        track_data.extend(var_length_bytes);
+                        track_data.extend(text.as_bytes());
+                    }
+                    MetaEvent::SequenceName(text) | MetaEvent::TrackName(text) => {
+                        track_data.push(0x03);
+                        let var_length_bytes = to_var_length_bytes(text.len() as u32)?;
+                        track_data.extend(var_length_bytes);
+                        track_data.extend(text.as_bytes());
+                    }
+                    MetaEvent::InstrumentName(text) => {
+                        track_data.push(0x04);
+                        let var_length_bytes = to_var_length_bytes(text.len() as u32)?;
+                        track_data.extend(var_length_bytes);
+                        track_data.extend(text.as_bytes());
+                    }
+                    MetaEvent::Lyric(text) => {
+                        track_data.push(0x05);
+                        let var_length_bytes = to_var_length_bytes(text.len() as u32)?;
+                        track_data.extend(var_length_bytes);
+                        track_data.extend(text.as_bytes());
+                    }
+                    MetaEvent::Marker(text) => {
+                        track_data.push(0x06);
+                        let var_length_bytes = to_var_length_bytes(text.len() as u32)?;
+                        track_data.extend(var_length_bytes);
+                        track_data.extend(text.as_bytes());
+                    }
+                    MetaEvent::CuePoint(text) => {
+                        track_data.push(0x07);
+                        let var_length_bytes = to_var_length_bytes(text.len() as u32)?;
+                        track_data.extend(var_length_bytes);
+                        track_data.extend(text.as_bytes());
+                    }
+                    MetaEvent::MidiChannelPrefix(channel) => {
+                        track_data.push(0x20);
+                        track_data.push(0x01);
+                        track_data.push((*channel).clone().into());
+                    }
+                    MetaEvent::EndOfTrack => {
+                        track_data.push(0x2F);
+                        track_data.push(0x00);
+                        end_of_track = true;
+                    }
+                    MetaEvent::SetTempo(tempo) => {
+                        track_data.push(0x51);
+                        track_data.push(0x03);
+                        let tempo_bytes: [u8; 4] = tempo.to_be_bytes();
+                        track_data.push(tempo_bytes[1]);
+                        track_data.push(tempo_bytes[2]);
+                        track_data.push(tempo_bytes[3]);
+                    }
+                    MetaEvent::SmpteOffset {
+                        hour,
+                        minute,
+                        second,
+                        frame,
+                        sub_frame,
+                    } => {
+                        track_data.push(0x54);
+                        track_data.push(0x05);
+                        track_data.push(*hour);
+                        track_data.push(*minute);
+                        track_data.push(*second);
+                        track_data.push(*frame);
+                        track_data.push(*sub_frame);
+                    }
+                    MetaEvent::TimeSignature {
+                        numerator,
+                        denominator,
+                        clocks_per_click,
+                        notated_32nd_notes_per_quarter_note,
+                    } => {
+                        track_data.push(0x58);
+                        track_data.push(0x04);
+                        track_data.push(*numerator);
+                        track_data.push(*denominator);
+                        track_data.push(*clocks_per_click);
+                        track_data.push(*notated_32nd_notes_per_quarter_note);
+                    }
+                    MetaEvent::KeySignature { key, scale } => {
+                        track_data.push(0x59);
+                        track_data.push(0x02);
+                        track_data.push(*key as u8);
+                        track_data.push(match scale {
+                            MusicalScale::Major => 0x00,
+                            MusicalScale::Minor => 0x01,
+                        });
+                    }
+                    MetaEvent::SequencerSpecific(data) => {
+                        track_data.push(0x7F);
+                        let var_length_bytes = to_var_length_bytes(data.len() as u32)?;
+                        track_data.extend(var_length_bytes);
+                        track_data.extend(data);
+                    }
+                }
+            }
+            Event::MidiEvent(midi_event) => {
+                let midi_event = match midi_event {
+                    MidiMessage::Channel {
+                        channel,
+                        message: ChannelMessage::Voice(ChannelVoiceMessage::NoteOff { note, .. }),
+                    } if config.note_off_as_note_on => MidiMessage::Channel {
+                        channel: channel.clone(),
+                        message: ChannelMessage::Voice(ChannelVoiceMessage::NoteOn {
+                            note: *note,
+                            velocity: 0,
+                        }),
+                    },
+                    _ => midi_event.to_owned(),
+                };
+                let midi_bytes: Vec<u8> = midi_event.clone().into();
+                match midi_event {
+                    MidiMessage::Channel { .. } => {
+                        let new_status = midi_bytes[0];
+
+                        if config.running_status && running_status == Some(new_status) {
+                            track_data.extend(&midi_bytes[1..]);
+                        } else {
+                            track_data.extend(&midi_bytes);
+                            running_status = Some(new_status);
+                        }
+                    }
+                    // System Exclusive messages are stored as SysEx events, any
+                    // other system message is escaped
+                    MidiMessage::System(system_message) => {
+                        running_status = None;
+
+                        let sysex_data = match system_message {
+                            SystemMessage::Exclusive(_) => {
+                                track_data.push(0xF0);
+                                let mut sysex_data = midi_bytes[1..].to_vec();
+                                if sysex_data.last() != Some(&0xF7) {
+                                    sysex_data.push(0xF7);
+                                }
+                                sysex_data
+                            }
+                            _ => {
+                                track_data.push(0xF7);
+                                midi_bytes
+                            }
+                        };
+                        track_data.extend(to_var_length_bytes(sysex_data.len() as u32)?);
+                        track_data.extend(sysex_data);
+                    }
+                }
+            }
+        }
+    }
+
+    if !end_of_track {
+        track_data.push(0xFF);
+        track_data.push(0x2F);
+        track_data.push(0x00);
+    }
+
+    Ok(track_data)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::midi::message::{ChannelMessage, ChannelVoiceMessage};
+
+    use super::*;
 
     const MIDI_FORMAT_0_EXAMPLE: &[u8] = &[
         0x4D, 0x54, 0x68, 0x64, // MThd
@@ -1126,6 +2283,7 @@ mod tests {
                     event: Event::MetaEvent(MetaEvent::EndOfTrack),
                 },
             ])],
+            info: RmidInfo::default(),
         };
 
         let serialized = Vec::try_from(&midi_file).unwrap();
@@ -1185,6 +2343,106 @@ mod tests {
         assert_eq!(result, 32_000_000);
     }
 
+    #[test]
+    fn tempo_map_without_tempo_events_uses_default_tempo() {
+        let division = MidiDivision::TicksPerQuarterNote(96);
+        let tempo_map = TempoMap::from_tracks(&[MidiTrack(vec![])], &division);
+
+        assert_eq!(tempo_map.tempo_at(0), DEFAULT_TEMPO);
+        assert_eq!(tempo_map.microseconds_at(96), 500_000);
+        assert_eq!(tempo_map.microseconds_at(6144), 32_000_000);
+    }
+
+    #[test]
+    fn tempo_map_applies_tempo_changes_from_all_tracks() {
+        let division = MidiDivision::TicksPerQuarterNote(96);
+        let conductor_track = MidiTrack(vec![
+            MidiTrackEvent {
+                delta_time: 0,
+                event: Event::MetaEvent(MetaEvent::SetTempo(1_000_000)),
+            },
+            MidiTrackEvent {
+                delta_time: 192,
+                event: Event::MetaEvent(MetaEvent::SetTempo(250_000)),
+            },
+        ]);
+        let note_track = MidiTrack(vec![
+            MidiTrackEvent {
+                delta_time: 288,
+                event: Event::MetaEvent(MetaEvent::SetTempo(500_000)),
+            },
+            MidiTrackEvent {
+                delta_time: 0,
+                event: Event::MetaEvent(MetaEvent::EndOfTrack),
+            },
+        ]);
+        let tempo_map = TempoMap::from_tracks(&[conductor_track, note_track], &division);
+
+        assert_eq!(tempo_map.tempo_at(0), 1_000_000);
+        assert_eq!(tempo_map.tempo_at(191), 1_000_000);
+        assert_eq!(tempo_map.tempo_at(192), 250_000);
+        assert_eq!(tempo_map.tempo_at(288), 500_000);
+        // two quarter notes at 60 BPM
+        assert_eq!(tempo_map.microseconds_at(192), 2_000_000);
+        // plus one quarter note at 240 BPM
+        assert_eq!(tempo_map.microseconds_at(288), 2_250_000);
+        // plus one quarter note at 120 BPM
+        assert_eq!(tempo_map.microseconds_at(384), 2_750_000);
+    }
+
+    #[test]
+    fn tempo_map_of_format_2_file_is_per_track() {
+        let midi_file = MidiFile {
+            header: MidiHeader {
+                format: MidiFormat::MultiSequence,
+                num_tracks: 2,
+                division: MidiDivision::TicksPerQuarterNote(96),
+            },
+            tracks: vec![
+                MidiTrack(vec![MidiTrackEvent {
+                    delta_time: 0,
+                    event: Event::MetaEvent(MetaEvent::SetTempo(1_000_000)),
+                }]),
+                MidiTrack(vec![]),
+            ],
+            info: RmidInfo::default(),
+        };
+
+        assert_eq!(midi_file.get_tempo_map(0).microseconds_at(96), 1_000_000);
+        assert_eq!(midi_file.get_tempo_map(1).microseconds_at(96), 500_000);
+    }
+
+    #[test]
+    fn format_2_sequences_start_at_the_end_of_track_of_the_one_before() {
+        let note_on = |delta_time: u32| MidiTrackEvent {
+            delta_time,
+            event: Event::MidiEvent(MidiMessage::note_on(MidiChannel::Channel1, 60, 100).unwrap()),
+        };
+        let end = |delta_time: u32| MidiTrackEvent {
+            delta_time,
+            event: Event::MetaEvent(MetaEvent::EndOfTrack),
+        };
+        let midi_file = MidiFile::new(
+            MidiHeader::new(
+                MidiFormat::MultiSequence,
+                2,
+                MidiDivision::TicksPerQuarterNote(96),
+            ),
+            vec![
+                // a quarter note of silence after the last event
+                MidiTrack(vec![note_on(96), end(96)]),
+                MidiTrack(vec![note_on(0), end(0)]),
+            ],
+        );
+
+        let timestamps: Vec<u64> = midi_file
+            .to_time_stamped_messages()
+            .iter()
+            .map(|message| message.timestamp_microseconds)
+            .collect();
+        assert_eq!(timestamps, vec![500_000, 1_000_000]);
+    }
+
     #[test]
     fn to_var_length_bytes_spec_examples() {
         assert_eq!(to_var_length_bytes(0x00).unwrap(), vec![0x00]);
@@ -1264,4 +2522,522 @@ mod tests {
             0xFFFFFFF
         );
     }
+
+    #[test]
+    fn time_stamped_messages_round_trip_without_drift() {
+        let division = MidiDivision::TicksPerQuarterNote(960);
+        // every timestamp is 1.7 ticks apart, so truncated deltas would drift
+        let messages: Vec<TimeStampedMidiMessage> = (0..100)
+            .map(|index| TimeStampedMidiMessage {
+                timestamp_microseconds: 1_000 + index * 885,
+                message: MidiMessage::note_on(MidiChannel::Channel1, 60, 100).unwrap(),
+            })
+            .collect();
+
+        let track =
+            MidiTrack::from_time_stamped_messages(messages.clone(), DEFAULT_TEMPO, &division);
+        let midi_file = MidiFile::new(
+            MidiHeader::new(MidiFormat::SingleMultiChannelTrack, 1, division),
+            vec![track],
+        );
+        let result = midi_file.to_time_stamped_messages();
+
+        assert_eq!(result.len(), messages.len());
+        for (original, converted) in messages.iter().zip(result.iter()) {
+            let expected = original.timestamp_microseconds - 1_000;
+            // one tick at 960 PPQ and 120 BPM is about 521 microseconds
+            assert!(expected - converted.timestamp_microseconds < 521);
+            assert_eq!(original.message, converted.message);
+        }
+    }
+
+    #[test]
+    fn time_stamped_messages_at_start_with_meta_events() {
+        let division = MidiDivision::TicksPerQuarterNote(96);
+        let note_on = MidiMessage::note_on(MidiChannel::Channel1, 60, 100).unwrap();
+        let messages = vec![
+            // played slightly before the start
+            TimeStampedMidiMessage {
+                timestamp_microseconds: 900,
+                message: note_on.clone(),
+            },
+            TimeStampedMidiMessage {
+                timestamp_microseconds: 501_000,
+                message: note_on.clone(),
+            },
+        ];
+
+        let track = MidiTrack::from_time_stamped_messages_at(
+            messages,
+            1_000,
+            vec![
+                MetaEvent::SetTempo(DEFAULT_TEMPO),
+                MetaEvent::TimeSignature {
+                    numerator: 4,
+                    denominator: 2,
+                    clocks_per_click: 24,
+                    notated_32nd_notes_per_quarter_note: 8,
+                },
+            ],
+            DEFAULT_TEMPO,
+            &division,
+        );
+
+        let delta_times: Vec<u32> = track.iter().map(|event| event.delta_time).collect();
+        assert_eq!(delta_times, vec![0, 0, 0, 96, 0]);
+        assert_eq!(
+            track[0].event,
+            Event::MetaEvent(MetaEvent::SetTempo(DEFAULT_TEMPO))
+        );
+        assert_eq!(track[2].event, Event::MidiEvent(note_on));
+    }
+
+    #[test]
+    fn with_track_adds_track_following_the_tempo_map() {
+        let division = MidiDivision::TicksPerQuarterNote(96);
+        let midi_file = MidiFile::new(
+            MidiHeader::new(MidiFormat::SingleMultiChannelTrack, 1, division),
+            vec![MidiTrack(vec![
+                MidiTrackEvent {
+                    delta_time: 0,
+                    event: Event::MetaEvent(MetaEvent::SetTempo(1_000_000)),
+                },
+                MidiTrackEvent {
+                    delta_time: 96,
+                    event: Event::MetaEvent(MetaEvent::SetTempo(500_000)),
+                },
+            ])],
+        );
+        let note_on = MidiMessage::note_on(MidiChannel::Channel2, 64, 90).unwrap();
+        // one quarter note at 60 BPM and one at 120 BPM
+        let messages = vec![TimeStampedMidiMessage {
+            timestamp_microseconds: 1_250_000,
+            message: note_on.clone(),
+        }];
+
+        let merged = midi_file.with_track(&messages, 250_000, "Overdub").unwrap();
+
+        assert_eq!(
+            *merged.get_header().get_format(),
+            MidiFormat::MultiTrackSequence
+        );
+        assert_eq!(merged.get_tracks().len(), 2);
+        let track = &merged.get_tracks()[1];
+        assert_eq!(
+            track[0].event,
+            Event::MetaEvent(MetaEvent::TrackName("Overdub".to_string()))
+        );
+        assert_eq!(track[1].delta_time, 192);
+        assert_eq!(track[1].event, Event::MidiEvent(note_on));
+        assert_eq!(merged.get_tempo_map(1).tick_at(1_500_000), 192);
+    }
+
+    const MIDI_SYSEX_EXAMPLE: &[u8] = &[
+        0x4D, 0x54, 0x68, 0x64, // MThd
+        0x00, 0x00, 0x00, 0x06, // chunk length
+        0x00, 0x00, // format 0
+        0x00, 0x01, // one track
+        0x00, 0x60, // division: 96 ticks per quarter note
+        0x4D, 0x54, 0x72, 0x6B, // MTrk
+        0x00, 0x00, 0x00, 0x26, // chunk length
+        0x00, 0xF0, 0x05, 0x7E, 0x7F, 0x09, 0x01, 0xF7, // GM System On
+        0x00, 0x90, 0x3C, 0x40, // note on: channel 0, note 60, velocity 64
+        0x10, 0xF0, 0x03, 0x43, 0x10, 0x4C, // XG parameter change, split ...
+        0x08, 0xF7, 0x05, 0x00, 0x00, 0x7E, 0x00, 0xF7, // ... and continued
+        0x00, 0xF7, 0x01, 0xF8, // escaped timing clock
+        0x00, 0x90, 0x3C, 0x00, // note off: SysEx events cancel the running status
+        0x00, 0xFF, 0x2F, 0x00, // end of track
+    ];
+
+    #[test]
+    fn sysex_events_round_trip() {
+        let midi_file = MidiFile::try_from(MIDI_SYSEX_EXAMPLE).unwrap();
+        let events: Vec<&Event> = midi_file.tracks[0]
+            .iter()
+            .map(|event| &event.event)
+            .collect();
+        assert_eq!(events[0], &Event::SysEx(vec![0x7E, 0x7F, 0x09, 0x01, 0xF7]));
+        assert_eq!(events[2], &Event::SysEx(vec![0x43, 0x10, 0x4C]));
+        assert_eq!(
+            events[3],
+            &Event::SysExContinuation(vec![0x00, 0x00, 0x7E, 0x00, 0xF7])
+        );
+        assert_eq!(events[4], &Event::SysExContinuation(vec![0xF8]));
+
+        let serialized: Vec<u8> = (&midi_file).try_into().unwrap();
+        assert_eq!(serialized, MIDI_SYSEX_EXAMPLE);
+
+        // the split message is joined at the time of its last packet
+        let messages: Vec<(u64, Vec<u8>)> = midi_file
+            .to_time_stamped_messages()
+            .into_iter()
+            .map(|message| (message.timestamp_microseconds, message.message.into()))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                (0, vec![0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7]),
+                (0, vec![0x90, 0x3C, 0x40]),
+                (
+                    125_000,
+                    vec![0xF0, 0x43, 0x10, 0x4C, 0x00, 0x00, 0x7E, 0x00, 0xF7]
+                ),
+                (125_000, vec![0xF8]),
+                (125_000, vec![0x90, 0x3C, 0x00]),
+            ]
+        );
+    }
+
+    #[test]
+    fn write_and_parse_round_trip_with_and_without_running_status() {
+        for example in [MIDI_FORMAT_0_EXAMPLE, MIDI_SYSEX_EXAMPLE] {
+            let midi_file = MidiFile::try_from(example).unwrap();
+            for running_status in [true, false] {
+                let config = MidiWriterConfig {
+                    running_status,
+                    ..Default::default()
+                };
+                let data = midi_file.to_bytes(&config).unwrap();
+                assert_eq!(MidiFile::try_from(data.as_slice()).unwrap(), midi_file);
+            }
+        }
+
+        let midi_file = MidiFile::try_from(MIDI_FORMAT_0_EXAMPLE).unwrap();
+        let compact = midi_file.to_bytes(&MidiWriterConfig::default()).unwrap();
+        let full = midi_file
+            .to_bytes(&MidiWriterConfig {
+                running_status: false,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(compact, MIDI_FORMAT_0_EXAMPLE);
+        assert!(full.len() > compact.len());
+    }
+
+    #[test]
+    fn write_note_off_as_note_on() {
+        let note = |delta_time: u32, message: ChannelVoiceMessage| MidiTrackEvent {
+            delta_time,
+            event: Event::MidiEvent(MidiMessage::Channel {
+                channel: MidiChannel::Channel1,
+                message: ChannelMessage::Voice(message),
+            }),
+        };
+        let midi_file = MidiFile::new(
+            MidiHeader::single_multi_channel_track(),
+            vec![MidiTrack(vec![
+                note(
+                    0,
+                    ChannelVoiceMessage::NoteOn {
+                        note: 60,
+                        velocity: 100,
+                    },
+                ),
+                note(
+                    96,
+                    ChannelVoiceMessage::NoteOff {
+                        note: 60,
+                        velocity: 64,
+                    },
+                ),
+                note(
+                    0,
+                    ChannelVoiceMessage::NoteOn {
+                        note: 62,
+                        velocity: 100,
+                    },
+                ),
+                note(
+                    96,
+                    ChannelVoiceMessage::NoteOff {
+                        note: 62,
+                        velocity: 64,
+                    },
+                ),
+                MidiTrackEvent {
+                    delta_time: 0,
+                    event: Event::MetaEvent(MetaEvent::EndOfTrack),
+                },
+            ])],
+        );
+        let config = MidiWriterConfig {
+            note_off_as_note_on: true,
+            ..Default::default()
+        };
+
+        let data = midi_file.to_bytes(&config).unwrap();
+        // a single status byte for all notes
+        assert_eq!(data.iter().filter(|byte| **byte == 0x90).count(), 1);
+        assert!(data.len() < midi_file.to_bytes(&Default::default()).unwrap().len());
+
+        let parsed = MidiFile::try_from(data.as_slice()).unwrap();
+        assert_eq!(
+            parsed.tracks[0][1],
+            note(
+                96,
+                ChannelVoiceMessage::NoteOn {
+                    note: 60,
+                    velocity: 0
+                }
+            )
+        );
+        // writing the parsed file again changes nothing
+        assert_eq!(parsed.to_bytes(&config).unwrap(), data);
+    }
+
+    #[test]
+    fn strip_redundant_events_across_tracks() {
+        let control = |delta_time: u32, controller: u8, value: u8| MidiTrackEvent {
+            delta_time,
+            event: Event::MidiEvent(MidiMessage::Channel {
+                channel: MidiChannel::Channel1,
+                message: ChannelMessage::Voice(ChannelVoiceMessage::ControlChange {
+                    controller,
+                    value,
+                }),
+            }),
+        };
+        let tempo = |delta_time: u32, tempo: u32| MidiTrackEvent {
+            delta_time,
+            event: Event::MetaEvent(MetaEvent::SetTempo(tempo)),
+        };
+        let end = MidiTrackEvent {
+            delta_time: 0,
+            event: Event::MetaEvent(MetaEvent::EndOfTrack),
+        };
+        let midi_file = MidiFile::new(
+            MidiHeader::new(
+                MidiFormat::MultiTrackSequence,
+                2,
+                MidiDivision::TicksPerQuarterNote(96),
+            ),
+            vec![
+                MidiTrack(vec![
+                    tempo(0, 500_000),
+                    // the same tempo again
+                    tempo(96, 500_000),
+                    tempo(96, 400_000),
+                    end.clone(),
+                ]),
+                MidiTrack(vec![
+                    control(0, 7, 100),
+                    // the same volume again
+                    control(48, 7, 100),
+                    control(48, 10, 64),
+                    // data entry always counts
+                    control(0, 6, 2),
+                    control(0, 6, 2),
+                    end.clone(),
+                ]),
+            ],
+        );
+        let config = MidiWriterConfig {
+            strip_redundant_events: true,
+            ..Default::default()
+        };
+
+        let data = midi_file.to_bytes(&config).unwrap();
+        let parsed = MidiFile::try_from(data.as_slice()).unwrap();
+        assert_eq!(
+            parsed.tracks,
+            vec![
+                MidiTrack(vec![tempo(0, 500_000), tempo(192, 400_000), end.clone()]),
+                MidiTrack(vec![
+                    control(0, 7, 100),
+                    control(96, 10, 64),
+                    control(0, 6, 2),
+                    control(0, 6, 2),
+                    end,
+                ]),
+            ]
+        );
+    }
+
+    #[test]
+    fn strip_redundant_events_keeps_events_after_a_reset() {
+        let program = |delta_time: u32, program: u8| MidiTrackEvent {
+            delta_time,
+            event: Event::MidiEvent(MidiMessage::Channel {
+                channel: MidiChannel::Channel1,
+                message: ChannelMessage::Voice(ChannelVoiceMessage::ProgramChange(program)),
+            }),
+        };
+        let volume = |delta_time: u32, value: u8| MidiTrackEvent {
+            delta_time,
+            event: Event::MidiEvent(MidiMessage::Channel {
+                channel: MidiChannel::Channel1,
+                message: ChannelMessage::Voice(ChannelVoiceMessage::ControlChange {
+                    controller: 7,
+                    value,
+                }),
+            }),
+        };
+        let gm_system_on = MidiTrackEvent {
+            delta_time: 96,
+            event: Event::SysEx(vec![0x7E, 0x7F, 0x09, 0x01, 0xF7]),
+        };
+        let end = MidiTrackEvent {
+            delta_time: 0,
+            event: Event::MetaEvent(MetaEvent::EndOfTrack),
+        };
+        let midi_file = MidiFile::new(
+            MidiHeader::new(
+                MidiFormat::SingleMultiChannelTrack,
+                1,
+                MidiDivision::TicksPerQuarterNote(96),
+            ),
+            vec![MidiTrack(vec![
+                program(0, 5),
+                volume(0, 100),
+                gm_system_on.clone(),
+                // both set again after the reset
+                program(0, 5),
+                volume(0, 100),
+                end.clone(),
+            ])],
+        );
+        let config = MidiWriterConfig {
+            strip_redundant_events: true,
+            ..Default::default()
+        };
+
+        let data = midi_file.to_bytes(&config).unwrap();
+        let parsed = MidiFile::try_from(data.as_slice()).unwrap();
+        assert_eq!(parsed.tracks, midi_file.tracks);
+    }
+
+    const MIDI_MALFORMED_EXAMPLE: &[u8] = &[
+        0x4D, 0x54, 0x68, 0x64, // MThd
+        0x00, 0x00, 0x00, 0x06, // chunk length
+        0x00, 0x01, // format 1
+        0x00, 0x02, // two tracks
+        0x00, 0x60, // division: 96 ticks per quarter note
+        0x4D, 0x54, 0x72, 0x6B, // MTrk
+        0x00, 0x00, 0x00, 0x10, // chunk length, 4 bytes too long
+        0x00, 0x90, 0x3C, 0x40, // note on: channel 0, note 60, velocity 64
+        0x60, 0x80, 0x3C, 0x40, // note off: channel 0, note 60, velocity 64
+        0x00, 0xFF, 0x2F, 0x00, // end of track
+        0x58, 0x46, 0x49, 0x48, // XFIH, a vendor chunk
+        0x00, 0x00, 0x00, 0x02, // chunk length
+        0xAA, 0xBB, // ...
+        0x4D, 0x54, 0x72, 0x6B, // MTrk
+        0x00, 0x00, 0x00, 0x0C, // chunk length
+        0x00, 0xC0, 0x05, // program change: channel 0, program 5
+        0x00, 0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20, // set tempo
+        0x00,
+        0x06, // program change without status after a meta event
+              // end of track is missing
+    ];
+
+    #[test]
+    fn lenient_parse_recovers_from_malformed_chunks() {
+        let error = MidiFile::try_from(MIDI_MALFORMED_EXAMPLE).unwrap_err();
+        assert_eq!(
+            error,
+            MidiFileError::new(
+                MidiFileErrorKind::TrackLengthMismatch {
+                    length: 16,
+                    events_length: 12
+                },
+                Some(18),
+                Some(0)
+            )
+        );
+        assert_eq!(
+            error.to_string(),
+            "Track length is 16 bytes but its events take 12 bytes in track 0 at byte 18"
+        );
+
+        let (midi_file, warnings) =
+            MidiFile::parse(MIDI_MALFORMED_EXAMPLE, ParseMode::Lenient).unwrap();
+        assert_eq!(
+            warnings
+                .iter()
+                .map(|warning| (warning.offset, warning.track))
+                .collect::<Vec<_>>(),
+            vec![(18, Some(0)), (34, None), (63, Some(1)), (64, Some(1))]
+        );
+
+        let program_change = |delta_time, program| MidiTrackEvent {
+            delta_time,
+            event: Event::MidiEvent(MidiMessage::Channel {
+                channel: MidiChannel::Channel1,
+                message: ChannelMessage::Voice(ChannelVoiceMessage::ProgramChange(program)),
+            }),
+        };
+        let end = MidiTrackEvent {
+            delta_time: 0,
+            event: Event::MetaEvent(MetaEvent::EndOfTrack),
+        };
+        assert_eq!(midi_file.tracks.len(), 2);
+        assert_eq!(midi_file.tracks[0].len(), 3);
+        assert_eq!(
+            midi_file.tracks[1],
+            MidiTrack(vec![
+                program_change(0, 5),
+                MidiTrackEvent {
+                    delta_time: 0,
+                    event: Event::MetaEvent(MetaEvent::SetTempo(500_000)),
+                },
+                program_change(0, 6),
+                end,
+            ])
+        );
+    }
+
+    #[test]
+    fn errors_serialize_with_their_kind() {
+        let error = MidiFile::try_from(&MIDI_FORMAT_0_EXAMPLE[..30]).unwrap_err();
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({
+                "kind": "trackLengthExceedsData",
+                "length": 59,
+                "available": 8,
+                "offset": 18,
+                "track": 0,
+            })
+        );
+
+        let error = MidiFile::try_from(&[0x52, 0x49, 0x46, 0x46][..]).unwrap_err();
+        assert_eq!(error.kind, MidiFileErrorKind::NotAMidiFile);
+    }
+
+    #[test]
+    fn lenient_parse_skips_bytes_before_the_header() {
+        let mut data = b"RIFF".to_vec();
+        data.extend_from_slice(MIDI_FORMAT_0_EXAMPLE);
+
+        assert!(MidiFile::try_from(data.as_slice()).is_err());
+
+        let (midi_file, warnings) = MidiFile::parse(&data, ParseMode::Lenient).unwrap();
+        assert_eq!(
+            midi_file,
+            MidiFile::try_from(MIDI_FORMAT_0_EXAMPLE).unwrap()
+        );
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[1].offset, 4);
+    }
+
+    #[test]
+    fn unusual_notated_32nd_notes_are_a_warning() {
+        let mut data = MIDI_FORMAT_0_EXAMPLE.to_vec();
+        let time_signature = data
+            .windows(3)
+            .position(|window| window == [0xFF, 0x58, 0x04])
+            .unwrap();
+        data[time_signature + 6] = 12;
+
+        let (_, warnings) = MidiFile::parse(&data, ParseMode::Strict).unwrap();
+        assert_eq!(
+            warnings,
+            vec![ParseWarning {
+                kind: MidiFileErrorKind::UnusualNotated32ndNotes { count: 12 },
+                offset: time_signature,
+                track: Some(0),
+                message: "Time signature notates 12 instead of 8 32nd notes per quarter note"
+                    .to_string(),
+            }]
+        );
+    }
 }
@@ -0,0 +1,49 @@
+use std::path::{Path, PathBuf};
+
+use tauri::{AppHandle, Manager};
+
+use crate::midi::{filter::InputFilter, metronome::MetronomeSettings, smf::MidiWriterConfig};
+
+const SETTINGS_FILE_NAME: &str = "settings.json";
+
+/// User settings that are kept between sessions.
+#[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+    pub input_filter: InputFilter,
+    pub metronome: MetronomeSettings,
+    /// How MIDI files are written when recordings are exported.
+    pub export: MidiWriterConfig,
+}
+
+impl Settings {
+    /// Falls back to the defaults if there are no settings yet or they cannot be read.
+    pub fn load(path: &Path) -> Settings {
+        let Ok(content) = std::fs::read_to_string(path) else {
+            return Settings::default();
+        };
+
+        serde_json::from_str(&content).unwrap_or_else(|e| {
+            eprintln!("Failed to parse settings, using defaults: {}", e);
+            Settings::default()
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create settings directory: {}", e))?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+        std::fs::write(path, content).map_err(|e| format!("Failed to write settings: {}", e))
+    }
+}
+
+pub fn get_settings_path(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_config_dir()
+        .map(|dir| dir.join(SETTINGS_FILE_NAME))
+        .map_err(|e| format!("Failed to resolve settings directory: {}", e))
+}
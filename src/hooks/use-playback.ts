@@ -5,16 +5,44 @@ import {
   playMidiRecording,
   resumeMidiPlayback,
   stopMidiPlayback,
-  loadMidiTrack,
+  seekMidiPlayback,
+  panicMidiPlayback,
+  setMidiPlaybackLoop,
+  clearMidiPlaybackLoop,
+  setMidiPlaybackRate,
+  setMidiPlaybackTransform,
+  setMidiChannelMuted,
+  setMidiChannelSoloed,
+  setMidiTrackMuted,
+  setMidiTrackSoloed,
+  loadMidiFile,
+  playMidiFile,
   playMidiTrack,
-  ejectMidiTrack,
+  ejectMidiFile,
+  mergeMidiOverdub,
+  playMidiTake,
+  registerMidiPlaybackChannel,
 } from "../tauri/playback-commands";
-import { Playback } from "../types/playback";
+import { CommandError } from "../types/error";
+import { MidiChannel } from "../types/midi-message";
+import { Playback, PlaybackTransform } from "../types/playback";
 
 const defaultPlayback: Playback = {
   state: "stopped",
   positionMilliseconds: 0,
-  tracks: [],
+  wallClockPositionMilliseconds: 0,
+  rate: 1,
+  transform: {
+    transpose: 0,
+    velocityScale: 1,
+    channelMap: [],
+  },
+  muteState: {
+    mutedChannels: [],
+    soloedChannels: [],
+    mutedTracks: [],
+    soloedTracks: [],
+  },
 };
 
 const globalPlayback = ref<Playback>(defaultPlayback);
@@ -27,6 +55,32 @@ getMidiPlayback()
     console.error("Error fetching initial playback state:", error);
   });
 
+registerMidiPlaybackChannel()
+  .then((channel) => {
+    channel.onmessage = (event) => {
+      switch (event.type) {
+        case "progress":
+          globalPlayback.value = {
+            ...globalPlayback.value,
+            positionMilliseconds: event.positionMilliseconds,
+            wallClockPositionMilliseconds:
+              event.positionMilliseconds / globalPlayback.value.rate,
+          };
+          break;
+        case "error":
+          console.error("Error during MIDI playback:", event.message);
+          updatePlayback();
+          break;
+        default:
+          updatePlayback();
+          break;
+      }
+    };
+  })
+  .catch((error) => {
+    console.error("Error registering MIDI playback channel:", error);
+  });
+
 function updatePlayback() {
   getMidiPlayback()
     .then((playback) => {
@@ -37,25 +91,57 @@ function updatePlayback() {
     });
 }
 
-function loadTrack() {
-  loadMidiTrack()
+function loadFile() {
+  loadMidiFile()
+    .then((playback) => {
+      console.log("File loaded:", playback);
+      globalPlayback.value = playback;
+    })
+    .catch((error: CommandError) => {
+      console.error("Error loading MIDI file:", error.message, error);
+    });
+}
+
+function ejectFile() {
+  ejectMidiFile()
+    .then((playback) => {
+      console.log("File ejected:", playback);
+      globalPlayback.value = playback;
+    })
+    .catch((error) => {
+      console.error("Error ejecting MIDI file:", error);
+    });
+}
+
+function playFile() {
+  playMidiFile()
     .then((playback) => {
-      console.log("Track loaded:", playback);
+      console.log("File playback started:", playback);
       globalPlayback.value = playback;
     })
     .catch((error) => {
-      console.error("Error loading MIDI track:", error);
+      console.error("Error playing MIDI file:", error);
+    });
+}
+
+function mergeOverdub(index: number) {
+  mergeMidiOverdub(index)
+    .then((playback) => {
+      console.log("Overdub merged:", playback);
+      globalPlayback.value = playback;
+    })
+    .catch((error: CommandError) => {
+      console.error("Error merging overdub:", error.message, error);
     });
 }
 
-function ejectTrack(index: number) {
-  ejectMidiTrack(index)
+function playTake(index: number, take: number) {
+  playMidiTake(index, take)
     .then((playback) => {
-      console.log("Track ejected:", playback);
       globalPlayback.value = playback;
     })
     .catch((error) => {
-      console.error("Error ejecting MIDI track:", error);
+      console.error("Error playing take:", error);
     });
 }
 
@@ -111,16 +197,129 @@ function stopPlayback() {
     });
 }
 
+function seekPlayback(positionMilliseconds: number) {
+  seekMidiPlayback(positionMilliseconds)
+    .then((playback) => {
+      globalPlayback.value = playback;
+    })
+    .catch((error) => {
+      console.error("Error seeking MIDI playback:", error);
+    });
+}
+
+function panicPlayback() {
+  panicMidiPlayback()
+    .then((playback) => {
+      globalPlayback.value = playback;
+    })
+    .catch((error) => {
+      console.error("Error resetting MIDI output:", error);
+    });
+}
+
+function setLoop(startMilliseconds: number, endMilliseconds: number) {
+  setMidiPlaybackLoop(startMilliseconds, endMilliseconds)
+    .then((playback) => {
+      globalPlayback.value = playback;
+    })
+    .catch((error) => {
+      console.error("Error setting MIDI playback loop:", error);
+    });
+}
+
+function clearLoop() {
+  clearMidiPlaybackLoop()
+    .then((playback) => {
+      globalPlayback.value = playback;
+    })
+    .catch((error) => {
+      console.error("Error clearing MIDI playback loop:", error);
+    });
+}
+
+function setRate(rate: number) {
+  setMidiPlaybackRate(rate)
+    .then((playback) => {
+      globalPlayback.value = playback;
+    })
+    .catch((error) => {
+      console.error("Error setting MIDI playback rate:", error);
+    });
+}
+
+function setTransform(transform: PlaybackTransform) {
+  setMidiPlaybackTransform(transform)
+    .then((playback) => {
+      globalPlayback.value = playback;
+    })
+    .catch((error) => {
+      console.error("Error setting MIDI playback transform:", error);
+    });
+}
+
+function setChannelMuted(channel: MidiChannel, muted: boolean) {
+  setMidiChannelMuted(channel, muted)
+    .then((playback) => {
+      globalPlayback.value = playback;
+    })
+    .catch((error) => {
+      console.error("Error muting MIDI channel:", error);
+    });
+}
+
+function setChannelSoloed(channel: MidiChannel, soloed: boolean) {
+  setMidiChannelSoloed(channel, soloed)
+    .then((playback) => {
+      globalPlayback.value = playback;
+    })
+    .catch((error) => {
+      console.error("Error soloing MIDI channel:", error);
+    });
+}
+
+function setTrackMuted(index: number, muted: boolean) {
+  setMidiTrackMuted(index, muted)
+    .then((playback) => {
+      globalPlayback.value = playback;
+    })
+    .catch((error) => {
+      console.error("Error muting MIDI track:", error);
+    });
+}
+
+function setTrackSoloed(index: number, soloed: boolean) {
+  setMidiTrackSoloed(index, soloed)
+    .then((playback) => {
+      globalPlayback.value = playback;
+    })
+    .catch((error) => {
+      console.error("Error soloing MIDI track:", error);
+    });
+}
+
 export function usePlayback() {
   return {
     playback: globalPlayback,
     updatePlayback,
     playRecording,
+    playTake,
     pausePlayback,
     resumePlayback,
     stopPlayback,
-    loadTrack,
+    seekPlayback,
+    panicPlayback,
+    setLoop,
+    clearLoop,
+    setRate,
+    setTransform,
+    setChannelMuted,
+    setChannelSoloed,
+    setTrackMuted,
+    setTrackSoloed,
+    loadFile,
+    playFile,
     playTrack,
-    ejectTrack,
+    ejectFile,
+    mergeOverdub,
   };
 }
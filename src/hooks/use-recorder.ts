@@ -2,18 +2,77 @@ import { ref } from "vue";
 import {
   saveMidiRecording,
   deleteMidiRecording,
+  renameMidiRecording,
+  setMidiRecordingTags,
+  recoverMidiRecording,
+  discardUnfinishedMidiRecording,
+  getMidiMetronome,
+  setMidiMetronome,
+  getMidiExportSettings,
+  setMidiExportSettings,
   getRecorder,
   startMidiRecording,
   stopMidiRecording,
+  startMidiOverdub,
+  pickMidiTake,
+  compMidiTakes,
+  pauseMidiRecording,
+  resumeMidiRecording,
+  saveMidiCapture,
+  setMidiCaptureMinutes,
+  setMidiInputFilter,
 } from "../tauri/recorder-commands";
-import { Recorder } from "../types/recorder";
+import {
+  CaptureSelection,
+  InputFilter,
+  CompSegment,
+  ExportSettings,
+  MetronomeSettings,
+  PunchRange,
+  Recorder,
+} from "../types/recorder";
+import { PlaybackIdentifier } from "../types/playback";
+import { CommandError } from "../types/error";
 
 const defaultRecorder = {
   state: "stopped",
+  canPause: false,
   recordings: [],
+  unfinishedRecordings: [],
+  captureMinutes: 5,
+  inputFilter: {
+    activeSensing: true,
+    timingClock: true,
+    systemExclusive: false,
+    aftertouch: false,
+    controllers: [],
+    channels: [],
+    applyToMonitor: false,
+  },
 } satisfies Recorder;
 
+const defaultMetronome = {
+  enabled: false,
+  beatsPerMinute: 120,
+  beatsPerBar: 4,
+  beatUnit: 4,
+  countInBars: 1,
+  channel: "channel10",
+  note: 77,
+  velocity: 80,
+  accentNote: 76,
+  accentVelocity: 110,
+} satisfies MetronomeSettings;
+
+const defaultExportSettings = {
+  runningStatus: true,
+  noteOffAsNoteOn: false,
+  stripRedundantEvents: false,
+} satisfies ExportSettings;
+
 const globalRecorder = ref<Recorder>(defaultRecorder);
+const globalMetronome = ref<MetronomeSettings>(defaultMetronome);
+const globalExportSettings = ref<ExportSettings>(defaultExportSettings);
 
 getRecorder()
   .then((recorder) => {
@@ -23,6 +82,32 @@ getRecorder()
     console.error("Error fetching initial recorder state:", error);
   });
 
+getMidiMetronome()
+  .then((metronome) => {
+    globalMetronome.value = metronome;
+  })
+  .catch((error) => {
+    console.error("Error fetching metronome settings:", error);
+  });
+
+getMidiExportSettings()
+  .then((exportSettings) => {
+    globalExportSettings.value = exportSettings;
+  })
+  .catch((error) => {
+    console.error("Error fetching export settings:", error);
+  });
+
+function updateRecorder() {
+  getRecorder()
+    .then((recorder) => {
+      globalRecorder.value = recorder;
+    })
+    .catch((error) => {
+      console.error("Error fetching recorder state:", error);
+    });
+}
+
 function startRecording() {
   console.log("Starting MIDI recording...");
   startMidiRecording()
@@ -49,13 +134,154 @@ function stopRecording() {
     });
 }
 
+function startOverdub(identifier: PlaybackIdentifier, punch?: PunchRange) {
+  startMidiOverdub(identifier, punch)
+    .then((recorder) => {
+      globalRecorder.value = recorder;
+    })
+    .catch((error) => {
+      console.error("Error starting overdub:", error);
+    });
+}
+
+function pickTake(index: number, take: number) {
+  pickMidiTake(index, take)
+    .then((recorder) => {
+      globalRecorder.value = recorder;
+    })
+    .catch((error) => {
+      console.error("Error picking take:", error);
+    });
+}
+
+function compTakes(index: number, segments: CompSegment[]) {
+  compMidiTakes(index, segments)
+    .then((recorder) => {
+      globalRecorder.value = recorder;
+    })
+    .catch((error) => {
+      console.error("Error comping takes:", error);
+    });
+}
+
+function pauseRecording() {
+  pauseMidiRecording()
+    .then((recorder) => {
+      globalRecorder.value = recorder;
+    })
+    .catch((error) => {
+      console.error("Error pausing MIDI recording:", error);
+    });
+}
+
+function resumeRecording() {
+  resumeMidiRecording()
+    .then((recorder) => {
+      globalRecorder.value = recorder;
+    })
+    .catch((error) => {
+      console.error("Error resuming MIDI recording:", error);
+    });
+}
+
+function saveCapture(selection: CaptureSelection) {
+  saveMidiCapture(selection)
+    .then((recorder) => {
+      console.log("MIDI capture saved:", recorder);
+      globalRecorder.value = recorder;
+    })
+    .catch((error) => {
+      console.error("Error saving MIDI capture:", error);
+    });
+}
+
+function setCaptureMinutes(minutes: number) {
+  setMidiCaptureMinutes(minutes)
+    .then((recorder) => {
+      globalRecorder.value = recorder;
+    })
+    .catch((error) => {
+      console.error("Error setting MIDI capture length:", error);
+    });
+}
+
+function setInputFilter(inputFilter: InputFilter) {
+  setMidiInputFilter(inputFilter)
+    .then((recorder) => {
+      globalRecorder.value = recorder;
+    })
+    .catch((error) => {
+      console.error("Error setting MIDI input filter:", error);
+    });
+}
+
+function setExportSettings(exportSettings: ExportSettings) {
+  setMidiExportSettings(exportSettings)
+    .then((exportSettings) => {
+      globalExportSettings.value = exportSettings;
+    })
+    .catch((error) => {
+      console.error("Error setting export settings:", error);
+    });
+}
+
+function setMetronome(metronome: MetronomeSettings) {
+  setMidiMetronome(metronome)
+    .then((metronome) => {
+      globalMetronome.value = metronome;
+    })
+    .catch((error) => {
+      console.error("Error setting metronome:", error);
+    });
+}
+
 function saveRecording(index: number) {
   saveMidiRecording(index)
     .then((recorder) => {
       console.log("MIDI recording saved:", recorder);
     })
+    .catch((error: CommandError) => {
+      console.error("Error saving MIDI recording:", error.message, error);
+    });
+}
+
+function renameRecording(index: number, name: string) {
+  renameMidiRecording(index, name)
+    .then((recorder) => {
+      globalRecorder.value = recorder;
+    })
+    .catch((error) => {
+      console.error("Error renaming MIDI recording:", error);
+    });
+}
+
+function setRecordingTags(index: number, tags: string[]) {
+  setMidiRecordingTags(index, tags)
+    .then((recorder) => {
+      globalRecorder.value = recorder;
+    })
+    .catch((error) => {
+      console.error("Error setting MIDI recording tags:", error);
+    });
+}
+
+function recoverRecording(index: number) {
+  recoverMidiRecording(index)
+    .then((recorder) => {
+      globalRecorder.value = recorder;
+    })
+    .catch((error) => {
+      console.error("Error recovering MIDI recording:", error);
+    });
+}
+
+function discardUnfinishedRecording(index: number) {
+  discardUnfinishedMidiRecording(index)
+    .then((recorder) => {
+      globalRecorder.value = recorder;
+    })
     .catch((error) => {
-      console.error("Error saving MIDI recording:", error);
+      console.error("Error discarding unfinished MIDI recording:", error);
     });
 }
 
@@ -74,9 +300,26 @@ function deleteRecording(index: number) {
 export function useRecorder() {
   return {
     recorder: globalRecorder,
+    metronome: globalMetronome,
+    exportSettings: globalExportSettings,
+    updateRecorder,
     startRecording,
     stopRecording,
+    startOverdub,
+    pickTake,
+    compTakes,
+    pauseRecording,
+    resumeRecording,
+    saveCapture,
+    setCaptureMinutes,
+    setInputFilter,
+    setMetronome,
+    setExportSettings,
     saveRecording,
+    renameRecording,
+    setRecordingTags,
+    recoverRecording,
+    discardUnfinishedRecording,
     deleteRecording,
   };
 }
@@ -1,25 +1,54 @@
-import { invoke } from "@tauri-apps/api/core";
-import { Playback } from "../types/playback";
+import { Channel, invoke } from "@tauri-apps/api/core";
+import { MidiChannel } from "../types/midi-message";
+import {
+  Playback,
+  PlaybackEvent,
+  PlaybackJitter,
+  PlaybackTransform,
+} from "../types/playback";
 
 const COMMAND = {
   GET_MIDI_PLAYBACK: "get_midi_playback",
+  GET_MIDI_PLAYBACK_JITTER: "get_midi_playback_jitter",
+  REGISTER_MIDI_PLAYBACK_CHANNEL: "register_midi_playback_channel",
   PLAY_MIDI_RECORDING: "play_midi_recording",
+  PLAY_MIDI_TAKE: "play_midi_take",
+  MERGE_MIDI_OVERDUB: "merge_midi_overdub",
   PAUSE_MIDI_PLAYBACK: "pause_midi_playback",
   RESUME_MIDI_PLAYBACK: "resume_midi_playback",
   STOP_MIDI_PLAYBACK: "stop_midi_playback",
-  LOAD_MIDI_TRACK: "load_midi_track",
+  SEEK_MIDI_PLAYBACK: "seek_midi_playback",
+  PANIC_MIDI_PLAYBACK: "panic_midi_playback",
+  SET_MIDI_PLAYBACK_LOOP: "set_midi_playback_loop",
+  CLEAR_MIDI_PLAYBACK_LOOP: "clear_midi_playback_loop",
+  SET_MIDI_PLAYBACK_RATE: "set_midi_playback_rate",
+  SET_MIDI_PLAYBACK_TRANSFORM: "set_midi_playback_transform",
+  SET_MIDI_CHANNEL_MUTED: "set_midi_channel_muted",
+  SET_MIDI_CHANNEL_SOLOED: "set_midi_channel_soloed",
+  SET_MIDI_TRACK_MUTED: "set_midi_track_muted",
+  SET_MIDI_TRACK_SOLOED: "set_midi_track_soloed",
+  LOAD_MIDI_FILE: "load_midi_file",
+  PLAY_MIDI_FILE: "play_midi_file",
   PLAY_MIDI_TRACK: "play_midi_track",
-  EJECT_MIDI_TRACK: "eject_midi_track",
+  EJECT_MIDI_FILE: "eject_midi_file",
 } as const;
 
 export async function getMidiPlayback() {
   return invoke<Playback>(COMMAND.GET_MIDI_PLAYBACK);
 }
 
+export async function getMidiPlaybackJitter() {
+  return invoke<PlaybackJitter>(COMMAND.GET_MIDI_PLAYBACK_JITTER);
+}
+
 export async function playMidiRecording(index: number) {
   return invoke<Playback>(COMMAND.PLAY_MIDI_RECORDING, { index });
 }
 
+export async function playMidiTake(index: number, take: number) {
+  return invoke<Playback>(COMMAND.PLAY_MIDI_TAKE, { index, take });
+}
+
 export async function pauseMidiPlayback() {
   return invoke<Playback>(COMMAND.PAUSE_MIDI_PLAYBACK);
 }
@@ -32,14 +61,76 @@ export async function stopMidiPlayback() {
   return invoke<Playback>(COMMAND.STOP_MIDI_PLAYBACK);
 }
 
-export async function loadMidiTrack() {
-  return invoke<Playback>(COMMAND.LOAD_MIDI_TRACK);
+export async function seekMidiPlayback(positionMilliseconds: number) {
+  return invoke<Playback>(COMMAND.SEEK_MIDI_PLAYBACK, { positionMilliseconds });
+}
+
+export async function panicMidiPlayback() {
+  return invoke<Playback>(COMMAND.PANIC_MIDI_PLAYBACK);
+}
+
+export async function setMidiPlaybackLoop(
+  startMilliseconds: number,
+  endMilliseconds: number
+) {
+  return invoke<Playback>(COMMAND.SET_MIDI_PLAYBACK_LOOP, {
+    startMilliseconds,
+    endMilliseconds,
+  });
+}
+
+export async function clearMidiPlaybackLoop() {
+  return invoke<Playback>(COMMAND.CLEAR_MIDI_PLAYBACK_LOOP);
+}
+
+export async function setMidiPlaybackRate(rate: number) {
+  return invoke<Playback>(COMMAND.SET_MIDI_PLAYBACK_RATE, { rate });
+}
+
+export async function setMidiPlaybackTransform(transform: PlaybackTransform) {
+  return invoke<Playback>(COMMAND.SET_MIDI_PLAYBACK_TRANSFORM, { transform });
+}
+
+export async function setMidiChannelMuted(channel: MidiChannel, muted: boolean) {
+  return invoke<Playback>(COMMAND.SET_MIDI_CHANNEL_MUTED, { channel, muted });
+}
+
+export async function setMidiChannelSoloed(channel: MidiChannel, soloed: boolean) {
+  return invoke<Playback>(COMMAND.SET_MIDI_CHANNEL_SOLOED, { channel, soloed });
+}
+
+export async function setMidiTrackMuted(index: number, muted: boolean) {
+  return invoke<Playback>(COMMAND.SET_MIDI_TRACK_MUTED, { index, muted });
+}
+
+export async function setMidiTrackSoloed(index: number, soloed: boolean) {
+  return invoke<Playback>(COMMAND.SET_MIDI_TRACK_SOLOED, { index, soloed });
+}
+
+export async function loadMidiFile() {
+  return invoke<Playback>(COMMAND.LOAD_MIDI_FILE);
+}
+
+export async function playMidiFile() {
+  return invoke<Playback>(COMMAND.PLAY_MIDI_FILE);
 }
 
 export async function playMidiTrack(index: number) {
   return invoke<Playback>(COMMAND.PLAY_MIDI_TRACK, { index });
 }
 
-export async function ejectMidiTrack(index: number) {
-  return invoke<Playback>(COMMAND.EJECT_MIDI_TRACK, { index });
+export async function ejectMidiFile() {
+  return invoke<Playback>(COMMAND.EJECT_MIDI_FILE);
+}
+
+export async function mergeMidiOverdub(index: number) {
+  return invoke<Playback>(COMMAND.MERGE_MIDI_OVERDUB, { index });
+}
+
+export async function registerMidiPlaybackChannel(): Promise<
+  Channel<PlaybackEvent>
+> {
+  const channel = new Channel<PlaybackEvent>();
+  await invoke<void>(COMMAND.REGISTER_MIDI_PLAYBACK_CHANNEL, { channel });
+  return channel;
 }
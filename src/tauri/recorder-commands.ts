@@ -1,11 +1,36 @@
 import { invoke } from "@tauri-apps/api/core";
-import { Recorder } from "../types/recorder";
+import { PlaybackIdentifier } from "../types/playback";
+import {
+  CaptureSelection,
+  CompSegment,
+  InputFilter,
+  ExportSettings,
+  MetronomeSettings,
+  PunchRange,
+  Recorder,
+} from "../types/recorder";
 
 const COMMAND = {
   GET_MIDI_RECORDER: "get_midi_recorder",
   START_MIDI_RECORDING: "start_midi_recording",
   STOP_MIDI_RECORDING: "stop_midi_recording",
+  START_MIDI_OVERDUB: "start_midi_overdub",
+  PICK_MIDI_TAKE: "pick_midi_take",
+  COMP_MIDI_TAKES: "comp_midi_takes",
+  PAUSE_MIDI_RECORDING: "pause_midi_recording",
+  RESUME_MIDI_RECORDING: "resume_midi_recording",
+  SAVE_MIDI_CAPTURE: "save_midi_capture",
+  SET_MIDI_CAPTURE_MINUTES: "set_midi_capture_minutes",
+  SET_MIDI_INPUT_FILTER: "set_midi_input_filter",
+  GET_MIDI_METRONOME: "get_midi_metronome",
+  SET_MIDI_METRONOME: "set_midi_metronome",
+  GET_MIDI_EXPORT_SETTINGS: "get_midi_export_settings",
+  SET_MIDI_EXPORT_SETTINGS: "set_midi_export_settings",
   SAVE_MIDI_RECORDING: "save_midi_recording",
+  RENAME_MIDI_RECORDING: "rename_midi_recording",
+  SET_MIDI_RECORDING_TAGS: "set_midi_recording_tags",
+  RECOVER_MIDI_RECORDING: "recover_midi_recording",
+  DISCARD_UNFINISHED_MIDI_RECORDING: "discard_unfinished_midi_recording",
   DELETE_MIDI_RECORDING: "delete_midi_recording",
 } as const;
 
@@ -21,6 +46,81 @@ export async function stopMidiRecording() {
   return invoke<Recorder>(COMMAND.STOP_MIDI_RECORDING);
 }
 
+export async function startMidiOverdub(
+  identifier: PlaybackIdentifier,
+  punch?: PunchRange,
+) {
+  return invoke<Recorder>(COMMAND.START_MIDI_OVERDUB, {
+    identifier,
+    punchStartMilliseconds: punch?.startMilliseconds,
+    punchEndMilliseconds: punch?.endMilliseconds,
+  });
+}
+
+export async function pickMidiTake(index: number, take: number) {
+  return invoke<Recorder>(COMMAND.PICK_MIDI_TAKE, { index, take });
+}
+
+export async function compMidiTakes(index: number, segments: CompSegment[]) {
+  return invoke<Recorder>(COMMAND.COMP_MIDI_TAKES, { index, segments });
+}
+
+export async function pauseMidiRecording() {
+  return invoke<Recorder>(COMMAND.PAUSE_MIDI_RECORDING);
+}
+
+export async function resumeMidiRecording() {
+  return invoke<Recorder>(COMMAND.RESUME_MIDI_RECORDING);
+}
+
+export async function saveMidiCapture(selection: CaptureSelection) {
+  return invoke<Recorder>(COMMAND.SAVE_MIDI_CAPTURE, { selection });
+}
+
+export async function setMidiCaptureMinutes(minutes: number) {
+  return invoke<Recorder>(COMMAND.SET_MIDI_CAPTURE_MINUTES, { minutes });
+}
+
+export async function setMidiInputFilter(inputFilter: InputFilter) {
+  return invoke<Recorder>(COMMAND.SET_MIDI_INPUT_FILTER, { inputFilter });
+}
+
+export async function renameMidiRecording(index: number, name: string) {
+  return invoke<Recorder>(COMMAND.RENAME_MIDI_RECORDING, { index, name });
+}
+
+export async function setMidiRecordingTags(index: number, tags: string[]) {
+  return invoke<Recorder>(COMMAND.SET_MIDI_RECORDING_TAGS, { index, tags });
+}
+
+export async function recoverMidiRecording(index: number) {
+  return invoke<Recorder>(COMMAND.RECOVER_MIDI_RECORDING, { index });
+}
+
+export async function discardUnfinishedMidiRecording(index: number) {
+  return invoke<Recorder>(COMMAND.DISCARD_UNFINISHED_MIDI_RECORDING, { index });
+}
+
+export async function getMidiMetronome() {
+  return invoke<MetronomeSettings>(COMMAND.GET_MIDI_METRONOME);
+}
+
+export async function setMidiMetronome(metronomeSettings: MetronomeSettings) {
+  return invoke<MetronomeSettings>(COMMAND.SET_MIDI_METRONOME, {
+    metronomeSettings,
+  });
+}
+
+export async function getMidiExportSettings() {
+  return invoke<ExportSettings>(COMMAND.GET_MIDI_EXPORT_SETTINGS);
+}
+
+export async function setMidiExportSettings(exportSettings: ExportSettings) {
+  return invoke<ExportSettings>(COMMAND.SET_MIDI_EXPORT_SETTINGS, {
+    exportSettings,
+  });
+}
+
 export async function deleteMidiRecording(index: number) {
   return invoke<Recorder>(COMMAND.DELETE_MIDI_RECORDING, { index });
 }
@@ -0,0 +1,70 @@
+export type MessageError = {
+  kind:
+    | "empty"
+    | "invalidChannel"
+    | "invalidDataByte"
+    | "invalidStatusByte"
+    | "wrongDataLength"
+    | "missingDataBytes"
+    | "invalidLocalControl";
+  [field: string]: unknown;
+};
+
+export type MidiFileErrorKind =
+  | "notAMidiFile"
+  | "leadingBytes"
+  | "truncatedHeader"
+  | "invalidHeaderLength"
+  | "extraHeaderBytes"
+  | "unsupportedFormat"
+  | "unsupportedTimeCode"
+  | "singleTrackCount"
+  | "noTracks"
+  | "trackCountMismatch"
+  | "unknownChunk"
+  | "invalidChunkType"
+  | "trailingBytes"
+  | "trackLengthExceedsData"
+  | "trackLengthMismatch"
+  | "missingEndOfTrack"
+  | "bytesAfterEndOfTrack"
+  | "eventsAfterEndOfTrack"
+  | "missingNextChunk"
+  | "invalidVariableLength"
+  | "variableLengthOverflow"
+  | "truncatedEvent"
+  | "missingRunningStatus"
+  | "invalidEvent"
+  | "unsupportedMetaEvent"
+  | "invalidMetaEvent"
+  | "unusualNotated32ndNotes"
+  | "invalidMessage"
+  | "multiSequenceMerge"
+  | "tooManyTracks"
+  | "trackTooLong"
+  | "invalidRiffSize"
+  | "missingRmidData";
+
+export type MidiFileError = {
+  kind: MidiFileErrorKind;
+  offset?: number;
+  track?: number;
+  // further fields depend on the kind, e.g. `length` and `available`
+  [field: string]: unknown;
+};
+
+export type CommandError =
+  | {
+      type: "midiFile";
+      error: MidiFileError;
+      message: string;
+    }
+  | {
+      type: "midiMessage";
+      error: MessageError;
+      message: string;
+    }
+  | {
+      type: "other";
+      message: string;
+    };
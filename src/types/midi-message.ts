@@ -41,7 +41,7 @@ export type ChannelMessage =
   | { mode: ChannelModeMessage };
 
 export type SystemCommonMessage =
-  | "midiTimeCodeQuarterFrame"
+  | { midiTimeCodeQuarterFrame: { messageType: number; values: number } }
   | { songPositionPointer: number }
   | { songSelect: number }
   | "tuneRequest"
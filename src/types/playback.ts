@@ -1,3 +1,6 @@
+import { MidiFileErrorKind } from "./error";
+import { MidiChannel } from "./midi-message";
+
 export type PlaybackIdentifier =
   | {
       type: "recording";
@@ -6,13 +9,59 @@ export type PlaybackIdentifier =
   | {
       type: "midiFile";
       path: string;
+      track?: number;
     };
 
 export type Track = {
   index: number;
+  name?: string;
   durationMilliseconds: number;
 };
 
+export type ParseWarning = {
+  kind: MidiFileErrorKind;
+  offset: number;
+  track?: number;
+  message: string;
+};
+
+export type RmidInfo = {
+  title?: string;
+  artist?: string;
+  copyright?: string;
+};
+
+export type MidiFile = {
+  path: string;
+  tracks: Track[];
+  durationMilliseconds: number;
+  info: RmidInfo;
+  warnings: ParseWarning[];
+};
+
+export type LoopRegion = {
+  startMilliseconds: number;
+  endMilliseconds: number;
+};
+
+export type ChannelMapping = {
+  from: MidiChannel;
+  to: MidiChannel;
+};
+
+export type PlaybackTransform = {
+  transpose: number;
+  velocityScale: number;
+  channelMap: ChannelMapping[];
+};
+
+export type MuteState = {
+  mutedChannels: MidiChannel[];
+  soloedChannels: MidiChannel[];
+  mutedTracks: number[];
+  soloedTracks: number[];
+};
+
 export type Playback = (
   | {
       state: "stopped";
@@ -20,14 +69,36 @@ export type Playback = (
   | {
       state: "playing";
       durationMilliseconds: number;
+      wallClockDurationMilliseconds: number;
       identifier: PlaybackIdentifier;
     }
   | {
       state: "paused";
       durationMilliseconds: number;
+      wallClockDurationMilliseconds: number;
       identifier: PlaybackIdentifier;
     }
 ) & {
   positionMilliseconds: number;
-  tracks: Track[];
+  wallClockPositionMilliseconds: number;
+  rate: number;
+  file?: MidiFile;
+  loopRegion?: LoopRegion;
+  transform: PlaybackTransform;
+  muteState: MuteState;
 };
+
+export type PlaybackJitter = {
+  messageCount: number;
+  meanLatenessMicroseconds: number;
+  maxLatenessMicroseconds: number;
+};
+
+export type PlaybackEvent =
+  | { type: "started" }
+  | { type: "progress"; positionMilliseconds: number }
+  | { type: "paused" }
+  | { type: "resumed" }
+  | { type: "stopped" }
+  | { type: "finished" }
+  | { type: "error"; message: string };
@@ -1,8 +1,81 @@
+import { MidiChannel } from "./midi-message";
+
+export type InputFilter = {
+  activeSensing: boolean;
+  timingClock: boolean;
+  systemExclusive: boolean;
+  aftertouch: boolean;
+  controllers: number[];
+  channels: MidiChannel[];
+  applyToMonitor: boolean;
+};
+
+export type MetronomeSettings = {
+  enabled: boolean;
+  beatsPerMinute: number;
+  beatsPerBar: number;
+  beatUnit: 2 | 4 | 8 | 16;
+  countInBars: number;
+  channel: MidiChannel;
+  note: number;
+  velocity: number;
+  accentNote: number;
+  accentVelocity: number;
+};
+
+/** How recordings are written when they are exported as MIDI files. */
+export type ExportSettings = {
+  runningStatus: boolean;
+  noteOffAsNoteOn: boolean;
+  stripRedundantEvents: boolean;
+};
+
+export type RecordingGrid = {
+  tempo: number;
+  beatsPerBar: number;
+  beatUnit: 2 | 4 | 8 | 16;
+};
+
+export type OverdubSource =
+  | { type: "recording"; id: string }
+  | { type: "midiFile"; path: string };
+
+export type CompSegment = {
+  take: number;
+  startMilliseconds: number;
+  endMilliseconds: number;
+};
+
+export type TakeSelection =
+  | { type: "take"; index: number }
+  | { type: "comp"; segments: CompSegment[] };
+
+/** Positions in the playback of a recording between which a take replaces it. */
+export type PunchRange = {
+  startMilliseconds: number;
+  endMilliseconds: number;
+};
+
 export type RecordingDuration = { secs: number; nanos: number };
 
 export type Recording = {
   index: number;
+  name: string;
+  createdMilliseconds: number;
   durationMilliseconds: number;
+  tags: string[];
+  inputDevice: string | null;
+  grid: RecordingGrid | null;
+  overdubOf: OverdubSource | null;
+  takeCount: number;
+  takeSelection: TakeSelection | null;
+};
+
+export type UnfinishedRecording = {
+  index: number;
+  createdMilliseconds: number;
+  messageCount: number;
+  inputDevice: string | null;
 };
 
 export type Recorder = (
@@ -12,4 +85,18 @@ export type Recorder = (
   | {
       state: "recording";
     }
-) & { recordings: Recording[] };
+  | {
+      state: "paused";
+    }
+) & {
+  /** False while the recording is tied to the metronome or a playback. */
+  canPause: boolean;
+  recordings: Recording[];
+  unfinishedRecordings: UnfinishedRecording[];
+  captureMinutes: number;
+  inputFilter: InputFilter;
+};
+
+export type CaptureSelection =
+  | { type: "lastSeconds"; seconds: number }
+  | { type: "lastPhrase" };